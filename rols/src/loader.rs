@@ -93,8 +93,19 @@ impl Loader for StdLoader {
         use std::path::*;
         let path = Path::new(normalized_name.as_str());
         if path.is_file() {
-            if let Ok(s) = fs::read_to_string(path) {
-                rs::parse_to_function(&s)
+            let compiled = compiled_path(path);
+            let path = compiled.as_deref().unwrap_or(path);
+            if let Ok(data) = fs::read(path) {
+                if rs::is_bytecode(&data) {
+                    rs::bytecode_to_function(&data)
+                } else if let Ok(s) = String::from_utf8(data) {
+                    rs::parse_to_function(&s)
+                } else {
+                    Err(runtime_error_fmt!(
+                        "unable to load module: \"{}\"",
+                        normalized_name.as_str()
+                    ))
+                }
             } else {
                 Err(runtime_error_fmt!(
                     "unable to load module: \"{}\"",
@@ -109,3 +120,18 @@ impl Loader for StdLoader {
         }
     }
 }
+
+/// 若源文件旁有不比它旧的同名 `.rolc` 文件，则返回该文件的路径。
+fn compiled_path(path: &Path) -> Option<std::path::PathBuf> {
+    if path.extension().is_some_and(|e| e == "rolc") {
+        return None;
+    }
+    let compiled = path.with_extension("rolc");
+    let source_time = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let compiled_time = fs::metadata(&compiled).and_then(|m| m.modified()).ok()?;
+    if compiled_time >= source_time {
+        Some(compiled)
+    } else {
+        None
+    }
+}
@@ -15,8 +15,8 @@ mod loader;
 
 use loader::StdLoader;
 
-fn load_file(path: &Path) -> String {
-    String::from_utf8(fs::read(path).unwrap()).unwrap()
+fn load_file(path: &Path) -> Vec<u8> {
+    fs::read(path).unwrap()
 }
 
 static mut STD_LOADER: StdLoader = StdLoader;
@@ -25,20 +25,122 @@ static mut STD_LOADER: StdLoader = StdLoader;
 #[command(name = "rols")]
 #[command(version = "0.1.0")]
 #[command(about = "rolscript interpreter.", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     file: Option<String>,
 
     #[arg(short, long)]
     eval: Option<String>,
+
+    /// 最大调用深度
+    #[arg(long, global = true)]
+    max_call_depth: Option<usize>,
+
+    /// 关闭字节码优化
+    #[arg(long, global = true)]
+    no_optimize: bool,
+
+    /// 可消耗的燃料（函数调用与循环迭代的次数），耗尽时停止执行
+    #[arg(long)]
+    fuel: Option<u64>,
+
+    /// gc 堆的字节数上限
+    #[arg(long, value_name = "BYTES")]
+    heap_limit: Option<usize>,
+
+    /// 执行超过该毫秒数时中断脚本
+    #[arg(long, value_name = "MS")]
+    timeout: Option<u64>,
+
+    /// 脚本函数生成的指令
+    #[arg(long, global = true, value_enum, default_value_t = BackendArg::Stack)]
+    backend: BackendArg,
+
+    /// 把 file 编译为 .rolc 文件写入该路径，不执行
+    #[arg(long, value_name = "OUT", requires = "file")]
+    compile: Option<String>,
+
+    /// 执行结束后把堆快照写入该路径，以 .dot 结尾时为 DOT 格式，否则为 JSON
+    #[arg(long, value_name = "OUT")]
+    heap_snapshot: Option<String>,
+
+    /// 退出时报告仍然存活的对象
+    #[arg(long)]
+    leak_report: bool,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// 输出脚本（.rol 或 .rolc）编译后的指令
+    Disasm { file: String },
+    /// 以 JSON 输出脚本的语法树，节点带有源码位置
+    Ast { file: String },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum BackendArg {
+    Stack,
+    Register,
 }
 
-fn eval_file(file_path: &Path, file_source: &str) -> Result<(), RError> {
+fn eval_file(file_path: &Path, file_data: &[u8]) -> Result<(), RError> {
     let module_name = RString::new(file_path.to_str().unwrap())?;
     let input_module = RModule::new(module_name, None)?;
-    rs::eval_with_module(&input_module, file_source)?;
+    if rs::is_bytecode(file_data) {
+        let func = rs::bytecode_to_function(file_data)?;
+        rs::value_call_with_this(&func.cast_value(), &input_module.cast_value(), &[])?;
+    } else {
+        let source = String::from_utf8_lossy(file_data);
+        rs::eval_with_module(&input_module, &source)?;
+    }
+    Ok(())
+}
+
+fn compile_file(file_data: &[u8], out_path: &str) -> Result<(), RError> {
+    let source = String::from_utf8_lossy(file_data);
+    let code = rs::parse_to_code(&source, true)?;
+    let data = code.serialize()?;
+    if fs::write(out_path, data.as_slice()).is_err() {
+        return Err(runtime_error_fmt!("unable to write \"{}\"", out_path));
+    }
     Ok(())
 }
 
+fn disasm_file(file_data: &[u8]) -> Result<(), RError> {
+    let code = if rs::is_bytecode(file_data) {
+        RScriptCode::deserialize(file_data)?
+    } else {
+        rs::parse_to_code(&String::from_utf8_lossy(file_data), true)?
+    };
+    let mut text = String::new();
+    rs::disassemble(&code, &mut text)?;
+    print!("{}", text);
+    Ok(())
+}
+
+fn ast_file(file_data: &[u8]) -> Result<(), RError> {
+    let mut text = String::new();
+    rs::parse_to_ast_json(&String::from_utf8_lossy(file_data), &mut text)?;
+    println!("{}", text);
+    Ok(())
+}
+
+fn write_heap_snapshot(out_path: &str) {
+    let snapshot = rs::heap_snapshot();
+    let mut text = String::new();
+    if out_path.ends_with(".dot") {
+        snapshot.write_dot(&mut text).unwrap();
+    } else {
+        snapshot.write_json(&mut text).unwrap();
+    }
+    if fs::write(out_path, text).is_err() {
+        println!("unable to write \"{}\"", out_path);
+    }
+}
+
 fn print_error(err: RError) {
     match err {
         Error::Parse(pe) => {
@@ -75,7 +177,49 @@ fn main() {
 
     initialize(allocator, loader).unwrap();
 
-    if let Some(file) = &args.file {
+    if let Some(depth) = args.max_call_depth {
+        rs::set_max_call_depth(depth);
+    }
+    if args.no_optimize {
+        rs::set_optimize_enabled(false);
+    }
+    if args.fuel.is_some() {
+        rs::set_fuel(args.fuel);
+    }
+    if args.heap_limit.is_some() {
+        rs::set_heap_limit(args.heap_limit);
+    }
+    if let Some(ms) = args.timeout {
+        let handle = rs::interrupt_handle();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(ms));
+            handle.interrupt();
+        });
+    }
+    match args.backend {
+        BackendArg::Stack => rs::set_backend(Backend::Stack),
+        BackendArg::Register => rs::set_backend(Backend::Register),
+    }
+
+    if let Some(Command::Disasm { file }) = &args.command {
+        match fs::read(file) {
+            Ok(data) => {
+                if let Err(e) = disasm_file(&data) {
+                    print_error(e);
+                }
+            }
+            Err(_) => println!("can't read {}", file),
+        }
+    } else if let Some(Command::Ast { file }) = &args.command {
+        match fs::read(file) {
+            Ok(data) => {
+                if let Err(e) = ast_file(&data) {
+                    print_error(e);
+                }
+            }
+            Err(_) => println!("can't read {}", file),
+        }
+    } else if let Some(file) = &args.file {
         functons::add_all().unwrap();
 
         let file_path = match Path::new(file).canonicalize() {
@@ -89,9 +233,12 @@ fn main() {
                 return;
             }
         };
-        let source_string = load_file(&file_path);
+        let file_data = load_file(&file_path);
 
-        let ret = eval_file(&file_path, &source_string);
+        let ret = match &args.compile {
+            Some(out) => compile_file(&file_data, out),
+            None => eval_file(&file_path, &file_data),
+        };
         match ret {
             Ok(()) => (),
             Err(e) => print_error(e),
@@ -134,5 +281,20 @@ fn main() {
         }
     }
 
-    finalize();
+    if let Some(out) = &args.heap_snapshot {
+        write_heap_snapshot(out);
+    }
+
+    if args.leak_report {
+        match rs::finalize_with_leak_report() {
+            Ok(report) => {
+                let mut text = String::new();
+                report.write_report(&mut text).unwrap();
+                eprint!("leak report: {}", text);
+            }
+            Err(e) => print_error(e),
+        }
+    } else {
+        finalize();
+    }
 }
@@ -0,0 +1,75 @@
+// 比较栈指令与寄存器指令执行同一组脚本的耗时。
+//
+//     cargo bench --bench backend
+
+use std::time::{Duration, Instant};
+
+use rolscript::*;
+
+const SCRIPTS: &[(&str, &str)] = &[
+    ("fib", include_str!("scripts/fib.rol")),
+    ("loops", include_str!("scripts/loops.rol")),
+    ("strings", include_str!("scripts/strings.rol")),
+    ("maps", include_str!("scripts/maps.rol")),
+];
+
+const RUNS: usize = 5;
+
+struct NoLoader;
+
+impl Loader for NoLoader {
+    fn normalize_name(
+        &mut self,
+        _requester: Ref<RModule>,
+        _name: Ref<RString>,
+    ) -> Result<Ref<RString>, Error> {
+        Err(new_runtime_error_str("modules are not available in benchmarks"))
+    }
+
+    fn load(&mut self, _normalized_name: Ref<RString>) -> Result<Ref<RFunction>, Error> {
+        Err(new_runtime_error_str("modules are not available in benchmarks"))
+    }
+}
+
+// 返回最快一次的耗时与脚本的结果。
+fn run(backend: Backend, source: &str) -> (Duration, String) {
+    set_backend(backend);
+
+    let mut best = Duration::MAX;
+    let mut result = String::new();
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let value = match eval(source) {
+            Ok(v) => v,
+            Err(e) => panic!("{:?} backend failed: {:?}", backend, e),
+        };
+        best = best.min(start.elapsed());
+        result = value_str(&value).unwrap().as_str().to_string();
+    }
+    (best, result)
+}
+
+fn main() {
+    let loader: &'static mut NoLoader = Box::leak(Box::new(NoLoader));
+    initialize(default_allocator(), loader).unwrap();
+
+    println!(
+        "{:<10} {:>12} {:>12} {:>8}",
+        "script", "stack", "register", "ratio"
+    );
+    for (name, source) in SCRIPTS {
+        let (stack_time, stack_result) = run(Backend::Stack, source);
+        let (register_time, register_result) = run(Backend::Register, source);
+        assert_eq!(stack_result, register_result, "{}", name);
+
+        println!(
+            "{:<10} {:>10.2}ms {:>10.2}ms {:>7.2}x",
+            name,
+            stack_time.as_secs_f64() * 1000.0,
+            register_time.as_secs_f64() * 1000.0,
+            stack_time.as_secs_f64() / register_time.as_secs_f64(),
+        );
+    }
+
+    finalize();
+}
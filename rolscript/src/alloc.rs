@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 pub trait Allocator {
     unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8;
     unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize);
@@ -61,3 +63,66 @@ static mut GLOBAL: Global = Global {};
 pub fn default_allocator() -> &'static mut dyn Allocator {
     unsafe { &mut GLOBAL }
 }
+
+/// 限制已分配总字节数的分配器，超过上限时返回空指针。
+///
+/// Runtime 要求分配器的生命周期为 'static，可以放在 static 中或通过 Box::leak 获得。
+pub struct LimitedAllocator {
+    _inner: &'static dyn Allocator,
+    _limit: AtomicUsize,
+    _used: AtomicUsize,
+}
+
+impl LimitedAllocator {
+    /// 包装 default_allocator()。
+    pub fn new(limit: usize) -> Self {
+        Self::with_allocator(default_allocator(), limit)
+    }
+
+    pub fn with_allocator(inner: &'static dyn Allocator, limit: usize) -> Self {
+        Self {
+            _inner: inner,
+            _limit: AtomicUsize::new(limit),
+            _used: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self._limit.load(Ordering::Relaxed)
+    }
+
+    /// 调小上限不会释放已经分配的内存，只会使之后的分配失败。
+    pub fn set_limit(&self, limit: usize) {
+        self._limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// 当前已分配且尚未释放的字节数。
+    pub fn used(&self) -> usize {
+        self._used.load(Ordering::Relaxed)
+    }
+}
+
+impl Allocator for LimitedAllocator {
+    unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 {
+        let limit = self.limit();
+        let reserved = self
+            ._used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(size).filter(|n| *n <= limit)
+            });
+        if reserved.is_err() {
+            return core::ptr::null_mut();
+        }
+
+        let ptr = self._inner.alloc(size, align);
+        if ptr.is_null() {
+            self._used.fetch_sub(size, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize) {
+        self._inner.free(ptr, size, align);
+        self._used.fetch_sub(size, Ordering::Relaxed);
+    }
+}
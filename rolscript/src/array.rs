@@ -195,6 +195,7 @@ fn array__iter(value: &RValue) -> Result<RValue, Error> {
     Ok(it.cast_value())
 }
 
+#[repr(C)]
 pub struct RArrayIter {
     _header: GcHeader,
     _array: Ref<RArray>,
@@ -224,13 +225,64 @@ pub(crate) fn _init_type_arrayiter(mut tp: Ref<RType>) -> Result<(), Error> {
     tp.with_hash(default_value_hash);
     tp.with_eq(default_value_eq);
 
+    tp.with_visit(array_iter__visit);
+    tp.with_destory(array_iter__destory);
+
     tp.with_next(array_iter__next);
 
     Ok(())
 }
 
+fn array_iter__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let it = value_ptr.cast::<RArrayIter>();
+        visitor.visit_value(it.as_ref()._array.cast_value_ref());
+    }
+}
+
+fn array_iter__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut it = value.expect_cast::<RArrayIter>(array_iter_type())?;
+        addr_of_mut!(it._array).drop_in_place();
+    }
+    Ok(())
+}
+
 fn array_iter__next(value: &RValue) -> Result<Ref<ROption>, Error> {
     let mut it = unsafe { value.expect_cast::<RArrayIter>(array_iter_type())? };
     let nv = it.next()?;
     ROption::new(nv)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::{eval, initialize, run_gc};
+    use crate::test_util::{allocator, loader};
+
+    fn ref_count<T>(value: &Ref<T>) -> usize {
+        value.cast_value_ref().as_ref().ref_count()
+    }
+
+    #[test]
+    fn test_array_iter() {
+        initialize(allocator(), loader()).unwrap();
+
+        let v = eval("s = 0; for (n : [1, 2, 3]) s = s + n; s").unwrap();
+        assert_eq!(unsafe { v.cast_ref::<RInt>().as_number() }, 6);
+
+        // array -> iter -> array 的循环引用可以被回收。
+        let s = RString::new("item").unwrap();
+        let count = ref_count(&s);
+
+        let mut array = RArray::new().unwrap();
+        array.push(s.cast_value()).unwrap();
+        let it = RArrayIter::new(&array).unwrap();
+        array.push(it.cast_value()).unwrap();
+        drop(it);
+        drop(array);
+
+        run_gc().unwrap();
+        assert_eq!(ref_count(&s), count);
+    }
+}
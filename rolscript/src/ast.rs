@@ -14,9 +14,11 @@ use crate::error::*;
 use crate::runtime_error_fmt;
 
 use crate::number::*;
+use crate::register;
 use crate::script_code::RScriptCode;
 use crate::script_code::ScriptCodeBuilder;
 use crate::string::RString;
+use crate::token::Pos;
 use crate::type_::*;
 use crate::value::*;
 
@@ -73,6 +75,10 @@ pub enum Ast {
         paramets: Array<Ref<RString>>,
         body: Ref<RAst>,
     },
+    EnumDef {
+        name: Ref<RString>,
+        variants: Array<(Ref<RString>, Array<Ref<RString>>)>,
+    },
     TypePublic {
         name: Ref<RString>,
         expr: Ref<RAst>,
@@ -126,6 +132,9 @@ pub enum Ast {
     Return {
         expr: Option<Ref<RAst>>,
     },
+    Yield {
+        expr: Option<Ref<RAst>>,
+    },
     Stat {
         expr: Option<Ref<RAst>>,
     },
@@ -222,6 +231,17 @@ impl Debug for Ref<RAst> {
                 write_slice(f, '(', ')', paramets.as_slice())?;
                 write!(f, "{{{:?}}}", body)
             }
+            EnumDef { name, variants } => {
+                write!(f, "EnumDef {:?}{{", name)?;
+                for (i, (vname, fields)) in variants.as_slice().iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}", vname)?;
+                    write_slice(f, '(', ')', fields.as_slice())?;
+                }
+                write!(f, "}}")
+            }
             TypePublic { name, expr } => {
                 write!(f, "TypePublic {:?}{{{:?}}}", name, expr)
             }
@@ -278,6 +298,13 @@ impl Debug for Ref<RAst> {
                     write!(f, "Return")
                 }
             }
+            Yield { expr } => {
+                if let Some(expr) = expr {
+                    write!(f, "Yield {:?}", expr)
+                } else {
+                    write!(f, "Yield")
+                }
+            }
             Stat { expr } => {
                 if let Some(expr) = expr {
                     write!(f, "Stat {:?}", expr)
@@ -293,11 +320,13 @@ impl Debug for Ref<RAst> {
 pub struct RAst {
     _header: GcHeader,
     _ast: Ast,
+    _pos: Pos,
 }
 
 impl RAst {
-    unsafe fn init(mut ptr: NonNull<Self>, ast: Ast) {
-        addr_of_mut!(ptr.as_mut()._ast).write(ast)
+    unsafe fn init(mut ptr: NonNull<Self>, ast: Ast, pos: Pos) {
+        addr_of_mut!(ptr.as_mut()._ast).write(ast);
+        addr_of_mut!(ptr.as_mut()._pos).write(pos);
     }
 
     unsafe fn _drop(&mut self) {
@@ -305,10 +334,14 @@ impl RAst {
     }
 
     pub fn new(ast: Ast) -> Result<Ref<Self>, Error> {
+        Self::new_at(ast, Pos::default())
+    }
+
+    pub fn new_at(ast: Ast, pos: Pos) -> Result<Ref<Self>, Error> {
         let tp = ast_type().clone();
         unsafe {
             let v = new_gc_obj(size_of::<Self>(), tp)?.cast::<Self>();
-            Self::init(v.as_nonnull_ptr(), ast);
+            Self::init(v.as_nonnull_ptr(), ast, pos);
             Ok(v)
         }
     }
@@ -316,6 +349,11 @@ impl RAst {
     pub fn as_ast(&self) -> &Ast {
         &self._ast
     }
+
+    /// 节点在源码中的起始位置。
+    pub fn pos(&self) -> Pos {
+        self._pos
+    }
 }
 
 fn _arith_op_to_opcode(op: ArithOp) -> Option<Opcode> {
@@ -393,8 +431,8 @@ fn _unary_ast_as_code(
     expr: &Ref<RAst>,
 ) -> Result<usize, Error> {
     let opcode = match op {
-        UnaryOp::Not => Opcode::Nop,
-        UnaryOp::BitNot => Opcode::BitAnd,
+        UnaryOp::Not => Opcode::Not,
+        UnaryOp::BitNot => Opcode::BitNot,
     };
 
     let n = _ast_as_code(builder, true, expr)?;
@@ -410,17 +448,7 @@ fn _lambda_ast_as_code(
     paramets: &Array<Ref<RString>>,
     body: &Ref<RAst>,
 ) -> Result<usize, Error> {
-    let mut new_builder = ScriptCodeBuilder::new(Some(builder))?;
-
-    for name in paramets.as_slice() {
-        new_builder.with_paramet(name)?;
-    }
-
-    let n = _ast_as_code(&mut new_builder, true, body)?;
-    new_builder.balance_stack(n, 1)?;
-    new_builder.with_opcode(Opcode::Return)?;
-
-    let code = new_builder.build()?;
+    let (code, _) = _function_body_as_code(builder, None, paramets, body)?;
 
     let child_idx = builder.with_child(code.clone())?;
 
@@ -452,8 +480,38 @@ fn _lambda_ast_as_code(
     Ok(1)
 }
 
+/// 生成函数体的代码，self_name 为函数名时一并返回函数自身在捕获列表中的位置。
+/// 寄存器指令不支持的函数体仍生成栈指令。
+pub(crate) fn _function_body_as_code(
+    parent_builder: &mut ScriptCodeBuilder,
+    self_name: Option<&Ref<RString>>,
+    paramets: &Array<Ref<RString>>,
+    body: &Ref<RAst>,
+) -> Result<(Ref<RScriptCode>, Option<u32>), Error> {
+    if backend() == Backend::Register && register::is_supported(body) {
+        return register::function_as_code(parent_builder, self_name, paramets, body);
+    }
+
+    let mut func_builder = ScriptCodeBuilder::new(Some(parent_builder))?;
+
+    for name in paramets.as_slice() {
+        func_builder.with_paramet(name)?;
+    }
+
+    let n = _ast_as_code(&mut func_builder, true, body)?;
+    func_builder.balance_stack(n, 1)?;
+    func_builder.with_opcode(Opcode::Return)?;
+
+    let capture_self_idx = match self_name {
+        Some(name) if func_builder.has_captured(name) => Some(func_builder.with_captured(name)?),
+        _ => None,
+    };
+
+    Ok((func_builder.build()?, capture_self_idx))
+}
+
 #[allow(non_snake_case)]
-fn __capture_collect_sort(
+pub(crate) fn __capture_collect_sort(
     iter: impl Iterator<Item = (Ref<RString>, u32)>,
     size_hit: Option<usize>,
 ) -> Result<Array<(Ref<RString>, u32)>, Error> {
@@ -481,23 +539,8 @@ fn _function_def_ast_as_code(
 ) -> Result<usize, Error> {
     let local_idx = parent_builder.with_local(function_name)?;
 
-    let mut func_builder = ScriptCodeBuilder::new(Some(parent_builder))?;
-
-    for name in paramets.as_slice() {
-        func_builder.with_paramet(name)?;
-    }
-
-    let n = _ast_as_code(&mut func_builder, true, body)?;
-    func_builder.balance_stack(n, 1)?;
-    func_builder.with_opcode(Opcode::Return)?;
-
-    let capture_self_idx = if func_builder.has_captured(function_name) {
-        Some(func_builder.with_captured(function_name)?)
-    } else {
-        None
-    };
-
-    let code = func_builder.build()?;
+    let (code, capture_self_idx) =
+        _function_body_as_code(parent_builder, Some(function_name), paramets, body)?;
     let code_idx = parent_builder.with_child(code.clone())?;
 
     // 用Code对象生成闭包。
@@ -607,6 +650,48 @@ fn _type_def_ast_as_code(
     }
 }
 
+fn _enum_def_ast_as_code(
+    builder: &mut ScriptCodeBuilder,
+    request_value: bool,
+    enum_name: &Ref<RString>,
+    variants: &Array<(Ref<RString>, Array<Ref<RString>>)>,
+) -> Result<usize, Error> {
+    if variants.len() > (u32::MAX as usize) {
+        return Err(runtime_error_fmt!("too many enum variants"));
+    }
+
+    let enum_local_idx = builder.with_local(enum_name)?;
+
+    let name_c_idx = builder.with_string(enum_name)?;
+    builder.with_opcode(Opcode::LoadConstStr(name_c_idx as u32))?;
+
+    for (variant_name, fields) in variants.as_slice() {
+        if fields.len() > (u32::MAX as usize) {
+            return Err(runtime_error_fmt!("too many enum variant fields"));
+        }
+
+        let variant_c_idx = builder.with_string(variant_name)?;
+        builder.with_opcode(Opcode::LoadConstStr(variant_c_idx as u32))?;
+
+        for field in fields.as_slice() {
+            let field_c_idx = builder.with_string(field)?;
+            builder.with_opcode(Opcode::LoadConstStr(field_c_idx as u32))?;
+        }
+        builder.with_opcode(Opcode::NewTuple(fields.len() as u32))?;
+    }
+
+    builder.with_opcode(Opcode::NewEnum(variants.len() as u32))?;
+
+    if request_value {
+        builder.with_opcode(Opcode::Dup)?;
+        builder.with_opcode(Opcode::SetLocal(enum_local_idx))?;
+        Ok(1)
+    } else {
+        builder.with_opcode(Opcode::SetLocal(enum_local_idx))?;
+        Ok(0)
+    }
+}
+
 fn _type_public_ast_as_code(
     builder: &mut ScriptCodeBuilder,
     name: &Ref<RString>,
@@ -629,17 +714,7 @@ fn _overload_def_ast_as_code(
     paramets: &Array<Ref<RString>>,
     body: &Ref<RAst>,
 ) -> Result<usize, Error> {
-    let mut func_builder = ScriptCodeBuilder::new(Some(parent_builder))?;
-
-    for name in paramets.as_slice() {
-        func_builder.with_paramet(name)?;
-    }
-
-    let n = _ast_as_code(&mut func_builder, true, body)?;
-    func_builder.balance_stack(n, 1)?;
-    func_builder.with_opcode(Opcode::Return)?;
-
-    let code = func_builder.build()?;
+    let (code, _) = _function_body_as_code(parent_builder, None, paramets, body)?;
     let code_idx = parent_builder.with_child(code.clone())?;
 
     parent_builder.with_opcode(Opcode::LoadThis)?;
@@ -880,6 +955,28 @@ fn _call_ast_as_code(
     func: &Ref<RAst>,
     args: &Array<Ref<RAst>>,
 ) -> Result<usize, Error> {
+    _callee_and_args_ast_as_code(builder, func, args)?;
+    builder.with_opcode(Opcode::Call(args.len() as u32))?;
+
+    Ok(1)
+}
+
+fn _tail_call_ast_as_code(
+    builder: &mut ScriptCodeBuilder,
+    func: &Ref<RAst>,
+    args: &Array<Ref<RAst>>,
+) -> Result<usize, Error> {
+    _callee_and_args_ast_as_code(builder, func, args)?;
+    builder.with_opcode(Opcode::TailCall(args.len() as u32))?;
+
+    Ok(1)
+}
+
+fn _callee_and_args_ast_as_code(
+    builder: &mut ScriptCodeBuilder,
+    func: &Ref<RAst>,
+    args: &Array<Ref<RAst>>,
+) -> Result<(), Error> {
     let n = _ast_as_code(builder, true, func)?;
     builder.balance_stack(n, 1)?;
 
@@ -888,9 +985,7 @@ fn _call_ast_as_code(
         builder.balance_stack(n, 1)?;
     }
 
-    builder.with_opcode(Opcode::Call(args.len() as u32))?;
-
-    Ok(1)
+    Ok(())
 }
 
 fn _method_call_ast_as_code(
@@ -1072,6 +1167,10 @@ fn _ast_as_code(
             let n = _type_def_ast_as_code(builder, request_value, name, stats)?;
             Ok(n)
         }
+        Ast::EnumDef { name, variants } => {
+            let n = _enum_def_ast_as_code(builder, request_value, name, variants)?;
+            Ok(n)
+        }
         Ast::TypePublic { name, expr } => {
             let n = _type_public_ast_as_code(builder, name, expr)?;
             Ok(n)
@@ -1146,7 +1245,13 @@ fn _ast_as_code(
         }
         Ast::Return { expr } => {
             if let Some(expr) = expr {
-                let n = _ast_as_code(builder, true, expr)?;
+                let n = match expr.as_ast() {
+                    // 尾调用 => return f(...)
+                    Ast::Call { func, args } if builder.has_parent() => {
+                        _tail_call_ast_as_code(builder, func, args)?
+                    }
+                    _ => _ast_as_code(builder, true, expr)?,
+                };
                 builder.balance_stack(n, 1)?;
             } else {
                 builder.with_opcode(Opcode::LoadNull)?;
@@ -1154,6 +1259,22 @@ fn _ast_as_code(
             builder.with_opcode(Opcode::Return)?;
             Ok(0)
         }
+        Ast::Yield { expr } => {
+            if !builder.has_parent() {
+                return Err(runtime_error_fmt!(
+                    "\"yield\" cannot be used at the top level"
+                ));
+            }
+            if let Some(expr) = expr {
+                let n = _ast_as_code(builder, true, expr)?;
+                builder.balance_stack(n, 1)?;
+            } else {
+                builder.with_opcode(Opcode::LoadNull)?;
+            }
+            builder.with_opcode(Opcode::Yield)?;
+            builder.with_generator(true);
+            Ok(1)
+        }
         Ast::Stat { expr } => {
             let n = _stat_ast_as_code(builder, expr)?;
             Ok(n)
@@ -1163,6 +1284,10 @@ fn _ast_as_code(
 
 pub(crate) fn ast_as_code(ast: Ref<RAst>, _ret_value: bool) -> Result<Ref<RScriptCode>, Error> {
     if let Ast::Program { stats, expr } = ast.as_ast() {
+        if backend() == Backend::Register && register::is_supported(&ast) {
+            return register::program_as_code(stats, expr);
+        }
+
         let mut builder = ScriptCodeBuilder::new(None)?;
 
         for stat in stats.as_slice() {
@@ -1289,6 +1414,15 @@ fn ast__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
                 }
                 visitor.visit_value(body.cast_value_ref());
             }
+            EnumDef { name, variants } => {
+                visitor.visit_value(name.cast_value_ref());
+                for (vname, fields) in variants.as_slice() {
+                    visitor.visit_value(vname.cast_value_ref());
+                    for s in fields.as_slice() {
+                        visitor.visit_value(s.cast_value_ref());
+                    }
+                }
+            }
             TypePublic { name, expr } => {
                 visitor.visit_value(name.cast_value_ref());
                 visitor.visit_value(expr.cast_value_ref());
@@ -1356,7 +1490,7 @@ fn ast__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
                     visitor.visit_value(v.cast_value_ref());
                 }
             }
-            Return { expr } => {
+            Return { expr } | Yield { expr } => {
                 if let Some(expr) = expr {
                     visitor.visit_value(expr.cast_value_ref());
                 }
@@ -0,0 +1,341 @@
+//! 把语法树导出为 JSON，供编辑器等外部工具使用。
+//!
+//! 每个节点是一个对象，`"type"` 为节点种类，`"line"`、`"column"` 从 1 开始，
+//! `"offset"` 为源码中的字节偏移，其余字段随节点种类而定：
+//!
+//! ```text
+//! {"type":"ArithExpr","line":1,"column":1,"offset":0,"op":"Add",
+//!  "left":{"type":"Int",...,"value":1},"right":{...}}
+//! ```
+//!
+//! 导出格式只依赖这里的约定，不随 `Ast` 的内部布局改变。
+
+use core::fmt::{self, Debug, Write};
+
+use crate::collections::Array;
+
+use crate::ast::{Ast, RAst};
+use crate::string::RString;
+use crate::value::*;
+
+/// 以紧凑的单行 JSON 写出 ast 及其所有子节点。
+pub(crate) fn write_json(ast: &RAst, out: &mut dyn Write) -> fmt::Result {
+    let pos = ast.pos();
+    out.write_str("{\"type\":")?;
+    write_str(out, variant_name(ast.as_ast()))?;
+    write!(
+        out,
+        ",\"line\":{},\"column\":{},\"offset\":{}",
+        pos.line, pos.column, pos.byte_pos
+    )?;
+
+    match ast.as_ast() {
+        Ast::Int(n) => write!(out, ",\"value\":{}", n)?,
+        Ast::Float(n) => write!(out, ",\"value\":{:?}", n)?,
+        Ast::String(s) => {
+            out.write_str(",\"value\":")?;
+            write_str(out, s.as_str())?;
+        }
+        Ast::Tuple(items) | Ast::Array(items) => {
+            out.write_str(",\"items\":")?;
+            nodes(out, items)?;
+        }
+        Ast::Map(entries) => {
+            out.write_str(",\"entries\":[")?;
+            for (i, (k, v)) in entries.iter().enumerate() {
+                if i != 0 {
+                    out.write_str(",")?;
+                }
+                out.write_str("{\"key\":")?;
+                write_json(k, out)?;
+                out.write_str(",\"value\":")?;
+                write_json(v, out)?;
+                out.write_str("}")?;
+            }
+            out.write_str("]")?;
+        }
+        Ast::Program { stats, expr } | Ast::Block { stats, expr } => {
+            out.write_str(",\"stats\":")?;
+            nodes(out, stats)?;
+            opt_node(out, "expr", expr)?;
+        }
+        Ast::ProgramPublic { name, expr } | Ast::TypePublic { name, expr } => {
+            name_field(out, "name", name)?;
+            node(out, "expr", expr)?;
+        }
+        Ast::ArithExpr { op, left, right } => {
+            op_field(out, op)?;
+            node(out, "left", left)?;
+            node(out, "right", right)?;
+        }
+        Ast::CmpExpr { op, left, right } => {
+            op_field(out, op)?;
+            node(out, "left", left)?;
+            node(out, "right", right)?;
+        }
+        Ast::UnaryExpr { op, expr } => {
+            op_field(out, op)?;
+            node(out, "expr", expr)?;
+        }
+        Ast::Lambda { paramets, body } => {
+            out.write_str(",\"paramets\":")?;
+            names(out, paramets)?;
+            node(out, "body", body)?;
+        }
+        Ast::FunctionDef {
+            name,
+            paramets,
+            body,
+        } => {
+            name_field(out, "name", name)?;
+            out.write_str(",\"paramets\":")?;
+            names(out, paramets)?;
+            node(out, "body", body)?;
+        }
+        Ast::TypeDef { name, stats } => {
+            name_field(out, "name", name)?;
+            out.write_str(",\"stats\":")?;
+            nodes(out, stats)?;
+        }
+        Ast::OverloadDef { op, paramets, body } => {
+            op_field(out, op)?;
+            out.write_str(",\"paramets\":")?;
+            names(out, paramets)?;
+            node(out, "body", body)?;
+        }
+        Ast::EnumDef { name, variants } => {
+            name_field(out, "name", name)?;
+            out.write_str(",\"variants\":[")?;
+            for (i, (vname, fields)) in variants.iter().enumerate() {
+                if i != 0 {
+                    out.write_str(",")?;
+                }
+                out.write_str("{\"name\":")?;
+                write_str(out, vname.as_str())?;
+                out.write_str(",\"fields\":")?;
+                names(out, fields)?;
+                out.write_str("}")?;
+            }
+            out.write_str("]")?;
+        }
+        Ast::If {
+            is_expr,
+            cond,
+            truebody,
+            falsebody,
+        } => {
+            write!(out, ",\"is_expr\":{}", is_expr)?;
+            node(out, "cond", cond)?;
+            node(out, "truebody", truebody)?;
+            opt_node(out, "falsebody", falsebody)?;
+        }
+        Ast::While {
+            is_expr,
+            cond,
+            body,
+        } => {
+            write!(out, ",\"is_expr\":{}", is_expr)?;
+            node(out, "cond", cond)?;
+            node(out, "body", body)?;
+        }
+        Ast::For {
+            is_expr,
+            name,
+            expr,
+            body,
+        } => {
+            write!(out, ",\"is_expr\":{}", is_expr)?;
+            name_field(out, "name", name)?;
+            node(out, "expr", expr)?;
+            node(out, "body", body)?;
+        }
+        Ast::Ident { name } => name_field(out, "name", name)?,
+        Ast::Assign { target, expr } => {
+            node(out, "target", target)?;
+            node(out, "expr", expr)?;
+        }
+        Ast::Attr { expr, name } => {
+            node(out, "expr", expr)?;
+            name_field(out, "name", name)?;
+        }
+        Ast::Index { expr, index } => {
+            node(out, "expr", expr)?;
+            node(out, "index", index)?;
+        }
+        Ast::Call { func, args } => {
+            node(out, "func", func)?;
+            out.write_str(",\"args\":")?;
+            nodes(out, args)?;
+        }
+        Ast::MethodCall { target, name, args } | Ast::AttrCall { target, name, args } => {
+            node(out, "target", target)?;
+            name_field(out, "name", name)?;
+            out.write_str(",\"args\":")?;
+            nodes(out, args)?;
+        }
+        Ast::Return { expr } | Ast::Yield { expr } | Ast::Stat { expr } => {
+            opt_node(out, "expr", expr)?;
+        }
+    }
+
+    out.write_str("}")
+}
+
+fn variant_name(ast: &Ast) -> &'static str {
+    match ast {
+        Ast::Int(_) => "Int",
+        Ast::Float(_) => "Float",
+        Ast::String(_) => "String",
+        Ast::Tuple(_) => "Tuple",
+        Ast::Array(_) => "Array",
+        Ast::Map(_) => "Map",
+        Ast::Program { .. } => "Program",
+        Ast::ProgramPublic { .. } => "ProgramPublic",
+        Ast::Block { .. } => "Block",
+        Ast::ArithExpr { .. } => "ArithExpr",
+        Ast::CmpExpr { .. } => "CmpExpr",
+        Ast::UnaryExpr { .. } => "UnaryExpr",
+        Ast::Lambda { .. } => "Lambda",
+        Ast::FunctionDef { .. } => "FunctionDef",
+        Ast::TypeDef { .. } => "TypeDef",
+        Ast::OverloadDef { .. } => "OverloadDef",
+        Ast::EnumDef { .. } => "EnumDef",
+        Ast::TypePublic { .. } => "TypePublic",
+        Ast::If { .. } => "If",
+        Ast::While { .. } => "While",
+        Ast::For { .. } => "For",
+        Ast::Ident { .. } => "Ident",
+        Ast::Assign { .. } => "Assign",
+        Ast::Attr { .. } => "Attr",
+        Ast::Index { .. } => "Index",
+        Ast::Call { .. } => "Call",
+        Ast::MethodCall { .. } => "MethodCall",
+        Ast::AttrCall { .. } => "AttrCall",
+        Ast::Return { .. } => "Return",
+        Ast::Yield { .. } => "Yield",
+        Ast::Stat { .. } => "Stat",
+    }
+}
+
+pub(crate) fn write_str(out: &mut dyn Write, s: &str) -> fmt::Result {
+    out.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_str("\"")
+}
+
+fn op_field(out: &mut dyn Write, op: &dyn Debug) -> fmt::Result {
+    let mut buf = OpName::default();
+    write!(buf, "{:?}", op)?;
+    out.write_str(",\"op\":")?;
+    write_str(out, buf.as_str())
+}
+
+fn name_field(out: &mut dyn Write, key: &str, name: &Ref<RString>) -> fmt::Result {
+    write!(out, ",\"{}\":", key)?;
+    write_str(out, name.as_str())
+}
+
+fn node(out: &mut dyn Write, key: &str, ast: &Ref<RAst>) -> fmt::Result {
+    write!(out, ",\"{}\":", key)?;
+    write_json(ast, out)
+}
+
+fn opt_node(out: &mut dyn Write, key: &str, ast: &Option<Ref<RAst>>) -> fmt::Result {
+    match ast {
+        Some(ast) => node(out, key, ast),
+        None => write!(out, ",\"{}\":null", key),
+    }
+}
+
+fn nodes(out: &mut dyn Write, asts: &Array<Ref<RAst>>) -> fmt::Result {
+    out.write_str("[")?;
+    for (i, ast) in asts.iter().enumerate() {
+        if i != 0 {
+            out.write_str(",")?;
+        }
+        write_json(ast, out)?;
+    }
+    out.write_str("]")
+}
+
+fn names(out: &mut dyn Write, names: &Array<Ref<RString>>) -> fmt::Result {
+    out.write_str("[")?;
+    for (i, name) in names.iter().enumerate() {
+        if i != 0 {
+            out.write_str(",")?;
+        }
+        write_str(out, name.as_str())?;
+    }
+    out.write_str("]")
+}
+
+// 运算符的名字都很短，用定长缓冲区接收 Debug 的输出。
+#[derive(Default)]
+struct OpName {
+    buf: [u8; 16],
+    len: usize,
+}
+
+impl OpName {
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for OpName {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.buf.len() {
+            return Err(fmt::Error);
+        }
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::{initialize, parse_to_ast_json};
+    use crate::test_util::{allocator, loader};
+
+    fn dump(source: &str) -> String {
+        let mut out = String::new();
+        parse_to_ast_json(source, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_ast_json() {
+        initialize(allocator(), loader()).unwrap();
+
+        assert_eq!(
+            dump("a = 1 + 2.5;\nf(\"x\\n\")"),
+            concat!(
+                r#"{"type":"Program","line":1,"column":1,"offset":0,"stats":["#,
+                r#"{"type":"Assign","line":1,"column":1,"offset":0,"#,
+                r#""target":{"type":"Ident","line":1,"column":1,"offset":0,"name":"a"},"#,
+                r#""expr":{"type":"ArithExpr","line":1,"column":5,"offset":4,"op":"Add","#,
+                r#""left":{"type":"Int","line":1,"column":5,"offset":4,"value":1},"#,
+                r#""right":{"type":"Float","line":1,"column":9,"offset":8,"value":2.5}}}],"#,
+                r#""expr":{"type":"Call","line":2,"column":1,"offset":13,"#,
+                r#""func":{"type":"Ident","line":2,"column":1,"offset":13,"name":"f"},"#,
+                r#""args":[{"type":"String","line":2,"column":3,"offset":15,"value":"x\n"}]}}"#,
+            )
+        );
+
+        let json = dump("if (x) {\n  return !y;\n}");
+        assert!(json.contains(r#"{"type":"If","line":1,"column":1,"offset":0,"is_expr":true"#));
+        assert!(json.contains(r#"{"type":"Return","line":2,"column":3,"offset":11"#));
+    }
+}
@@ -7,7 +7,11 @@ use crate::error::*;
 use crate::ast::_init_type_ast;
 
 use crate::array::*;
+use crate::coroutine::*;
+use crate::enum_::*;
 use crate::function::*;
+use crate::gc::_new_gc_module;
+use crate::generator::*;
 use crate::map::*;
 use crate::module::*;
 use crate::number::*;
@@ -17,95 +21,168 @@ use crate::string::*;
 use crate::tuple::*;
 use crate::type_::*;
 use crate::value::*;
+use crate::weak::*;
+
+/// 每个 Runtime 各自的内建类型与内建值，初始化时依次写入。
+/// 其中的引用不会被 drop，Runtime 销毁时直接释放全部对象。
+pub(crate) struct Builtins {
+    _type_type: MaybeUninit<Ref<RType>>,
+    _null_type: MaybeUninit<Ref<RType>>,
+    _bool_type: MaybeUninit<Ref<RType>>,
+    _int_type: MaybeUninit<Ref<RType>>,
+    _float_type: MaybeUninit<Ref<RType>>,
+    _string_type: MaybeUninit<Ref<RType>>,
+    _tuple_type: MaybeUninit<Ref<RType>>,
+    _array_type: MaybeUninit<Ref<RType>>,
+    _map_type: MaybeUninit<Ref<RType>>,
+    _function_type: MaybeUninit<Ref<RType>>,
+    _option_type: MaybeUninit<Ref<RType>>,
+    _enum_variant_type: MaybeUninit<Ref<RType>>,
+
+    _module_type: MaybeUninit<Ref<RType>>,
+
+    _ast_type: MaybeUninit<Ref<RType>>,
+    _script_code_type: MaybeUninit<Ref<RType>>,
+
+    _array_iter_type: MaybeUninit<Ref<RType>>,
+    _tuple_iter_type: MaybeUninit<Ref<RType>>,
+    _generator_type: MaybeUninit<Ref<RType>>,
+    _coroutine_type: MaybeUninit<Ref<RType>>,
+
+    _weak_ref_type: MaybeUninit<Ref<RType>>,
+    _weak_map_type: MaybeUninit<Ref<RType>>,
+
+    _null_value: MaybeUninit<Ref<RNull>>,
+    _true_value: MaybeUninit<Ref<RBool>>,
+    _false_value: MaybeUninit<Ref<RBool>>,
+    _none_value: MaybeUninit<Ref<ROption>>,
+    _coroutine_yield_func: MaybeUninit<Ref<RFunction>>,
+}
 
-static mut _TYPE_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _NULL_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _BOOL_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _INT_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _FLOAT_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _STRING_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _TUPLE_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _ARRAY_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _MAP_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _FUNCTION_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _OPTION_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-
-static mut _MODULE_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-
-static mut _AST_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _SCRIPT_CODE_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
+impl Builtins {
+    pub(crate) fn new() -> Self {
+        Self {
+            _type_type: MaybeUninit::uninit(),
+            _null_type: MaybeUninit::uninit(),
+            _bool_type: MaybeUninit::uninit(),
+            _int_type: MaybeUninit::uninit(),
+            _float_type: MaybeUninit::uninit(),
+            _string_type: MaybeUninit::uninit(),
+            _tuple_type: MaybeUninit::uninit(),
+            _array_type: MaybeUninit::uninit(),
+            _map_type: MaybeUninit::uninit(),
+            _function_type: MaybeUninit::uninit(),
+            _option_type: MaybeUninit::uninit(),
+            _enum_variant_type: MaybeUninit::uninit(),
+            _module_type: MaybeUninit::uninit(),
+            _ast_type: MaybeUninit::uninit(),
+            _script_code_type: MaybeUninit::uninit(),
+            _array_iter_type: MaybeUninit::uninit(),
+            _tuple_iter_type: MaybeUninit::uninit(),
+            _generator_type: MaybeUninit::uninit(),
+            _coroutine_type: MaybeUninit::uninit(),
+            _weak_ref_type: MaybeUninit::uninit(),
+            _weak_map_type: MaybeUninit::uninit(),
+            _null_value: MaybeUninit::uninit(),
+            _true_value: MaybeUninit::uninit(),
+            _false_value: MaybeUninit::uninit(),
+            _none_value: MaybeUninit::uninit(),
+            _coroutine_yield_func: MaybeUninit::uninit(),
+        }
+    }
+}
 
-static mut _ARRAY_ITER_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
-static mut _TUPLE_ITER_TYPE_: MaybeUninit<Ref<RType>> = MaybeUninit::uninit();
+#[inline]
+fn builtins() -> &'static Builtins {
+    runtime().builtins()
+}
 
-static mut _NULL_VALUE_: MaybeUninit<Ref<RNull>> = MaybeUninit::uninit();
-static mut _TRUE_VALUE_: MaybeUninit<Ref<RBool>> = MaybeUninit::uninit();
-static mut _FALSE_VALUE_: MaybeUninit<Ref<RBool>> = MaybeUninit::uninit();
-static mut _NONE_VALUE_: MaybeUninit<Ref<ROption>> = MaybeUninit::uninit();
+fn builtins_mut() -> &'static mut Builtins {
+    runtime().builtins_mut()
+}
 
 pub fn type_type() -> &'static Ref<RType> {
-    unsafe { _TYPE_TYPE_.assume_init_ref() }
+    unsafe { builtins()._type_type.assume_init_ref() }
 }
 pub fn null_type() -> &'static Ref<RType> {
-    unsafe { _NULL_TYPE_.assume_init_ref() }
+    unsafe { builtins()._null_type.assume_init_ref() }
 }
 pub fn bool_type() -> &'static Ref<RType> {
-    unsafe { _BOOL_TYPE_.assume_init_ref() }
+    unsafe { builtins()._bool_type.assume_init_ref() }
 }
 pub fn int_type() -> &'static Ref<RType> {
-    unsafe { _INT_TYPE_.assume_init_ref() }
+    unsafe { builtins()._int_type.assume_init_ref() }
 }
 pub fn float_type() -> &'static Ref<RType> {
-    unsafe { _FLOAT_TYPE_.assume_init_ref() }
+    unsafe { builtins()._float_type.assume_init_ref() }
 }
 pub fn string_type() -> &'static Ref<RType> {
-    unsafe { _STRING_TYPE_.assume_init_ref() }
+    unsafe { builtins()._string_type.assume_init_ref() }
 }
 pub fn tuple_type() -> &'static Ref<RType> {
-    unsafe { _TUPLE_TYPE_.assume_init_ref() }
+    unsafe { builtins()._tuple_type.assume_init_ref() }
 }
 pub fn array_type() -> &'static Ref<RType> {
-    unsafe { _ARRAY_TYPE_.assume_init_ref() }
+    unsafe { builtins()._array_type.assume_init_ref() }
 }
 pub fn map_type() -> &'static Ref<RType> {
-    unsafe { _MAP_TYPE_.assume_init_ref() }
+    unsafe { builtins()._map_type.assume_init_ref() }
 }
 pub fn function_type() -> &'static Ref<RType> {
-    unsafe { _FUNCTION_TYPE_.assume_init_ref() }
+    unsafe { builtins()._function_type.assume_init_ref() }
 }
 pub fn option_type() -> &'static Ref<RType> {
-    unsafe { _OPTION_TYPE_.assume_init_ref() }
+    unsafe { builtins()._option_type.assume_init_ref() }
+}
+pub fn enum_variant_type() -> &'static Ref<RType> {
+    unsafe { builtins()._enum_variant_type.assume_init_ref() }
 }
 
 pub fn module_type() -> &'static Ref<RType> {
-    unsafe { _MODULE_TYPE_.assume_init_ref() }
+    unsafe { builtins()._module_type.assume_init_ref() }
 }
 
 pub fn ast_type() -> &'static Ref<RType> {
-    unsafe { _AST_TYPE_.assume_init_ref() }
+    unsafe { builtins()._ast_type.assume_init_ref() }
 }
 pub fn script_code_type() -> &'static Ref<RType> {
-    unsafe { _SCRIPT_CODE_TYPE_.assume_init_ref() }
+    unsafe { builtins()._script_code_type.assume_init_ref() }
 }
 
 pub fn array_iter_type() -> &'static Ref<RType> {
-    unsafe { _ARRAY_ITER_TYPE_.assume_init_ref() }
+    unsafe { builtins()._array_iter_type.assume_init_ref() }
 }
 pub fn tuple_iter_type() -> &'static Ref<RType> {
-    unsafe { _TUPLE_ITER_TYPE_.assume_init_ref() }
+    unsafe { builtins()._tuple_iter_type.assume_init_ref() }
+}
+pub fn generator_type() -> &'static Ref<RType> {
+    unsafe { builtins()._generator_type.assume_init_ref() }
+}
+pub fn coroutine_type() -> &'static Ref<RType> {
+    unsafe { builtins()._coroutine_type.assume_init_ref() }
+}
+
+pub fn weak_ref_type() -> &'static Ref<RType> {
+    unsafe { builtins()._weak_ref_type.assume_init_ref() }
+}
+pub fn weak_map_type() -> &'static Ref<RType> {
+    unsafe { builtins()._weak_map_type.assume_init_ref() }
 }
 
 pub fn null() -> &'static Ref<RNull> {
-    unsafe { _NULL_VALUE_.assume_init_ref() }
+    unsafe { builtins()._null_value.assume_init_ref() }
 }
 pub fn true_() -> &'static Ref<RBool> {
-    unsafe { _TRUE_VALUE_.assume_init_ref() }
+    unsafe { builtins()._true_value.assume_init_ref() }
 }
 pub fn false_() -> &'static Ref<RBool> {
-    unsafe { _FALSE_VALUE_.assume_init_ref() }
+    unsafe { builtins()._false_value.assume_init_ref() }
 }
 pub fn none() -> &'static Ref<ROption> {
-    unsafe { _NONE_VALUE_.assume_init_ref() }
+    unsafe { builtins()._none_value.assume_init_ref() }
+}
+pub fn coroutine_yield_func() -> &'static Ref<RFunction> {
+    unsafe { builtins()._coroutine_yield_func.assume_init_ref() }
 }
 
 pub(crate) fn _create_type_and_string_type() -> Result<(), Error> {
@@ -120,59 +197,72 @@ pub(crate) fn _create_type_and_string_type() -> Result<(), Error> {
         let type_tp = type_tp.init_type(tp.force_into());
         let string_tp = string_tp.init_type(type_tp.clone());
 
-        type_name.init_type(type_tp.clone());
-        string_name.init_type(type_tp.clone());
+        type_name.init_type(string_tp.clone());
+        string_name.init_type(string_tp.clone());
 
-        _TYPE_TYPE_.write(type_tp.clone());
-        _STRING_TYPE_.write(string_tp.clone());
+        builtins_mut()._type_type.write(type_tp.clone());
+        builtins_mut()._string_type.write(string_tp.clone());
     }
     Ok(())
 }
 
 pub(crate) fn _create_builtin_types() -> Result<(), Error> {
-    unsafe {
-        let tp = RType::new_with_str("Null")?;
-        _NULL_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Null")?;
+    builtins_mut()._null_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Bool")?;
-        _BOOL_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Bool")?;
+    builtins_mut()._bool_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Int")?;
-        _INT_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Int")?;
+    builtins_mut()._int_type.write(tp.clone());
 
-        let tp = RType::new_with_str("FLoat")?;
-        _FLOAT_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("FLoat")?;
+    builtins_mut()._float_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Tuple")?;
-        _TUPLE_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Tuple")?;
+    builtins_mut()._tuple_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Array")?;
-        _ARRAY_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Array")?;
+    builtins_mut()._array_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Map")?;
-        _MAP_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Map")?;
+    builtins_mut()._map_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Function")?;
-        _FUNCTION_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Function")?;
+    builtins_mut()._function_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Option")?;
-        _OPTION_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Option")?;
+    builtins_mut()._option_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Module")?;
-        _MODULE_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("EnumVariant")?;
+    builtins_mut()._enum_variant_type.write(tp.clone());
 
-        let tp = RType::new_with_str("Ast")?;
-        _AST_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Module")?;
+    builtins_mut()._module_type.write(tp.clone());
 
-        let tp = RType::new_with_str("ScriptCode")?;
-        _SCRIPT_CODE_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("Ast")?;
+    builtins_mut()._ast_type.write(tp.clone());
 
-        let tp = RType::new_with_str("ArrayIter")?;
-        _ARRAY_ITER_TYPE_.write(tp.clone());
+    let tp = RType::new_with_str("ScriptCode")?;
+    builtins_mut()._script_code_type.write(tp.clone());
 
-        let tp = RType::new_with_str("TupleIter")?;
-        _TUPLE_ITER_TYPE_.write(tp.clone());
-    }
+    let tp = RType::new_with_str("ArrayIter")?;
+    builtins_mut()._array_iter_type.write(tp.clone());
+
+    let tp = RType::new_with_str("TupleIter")?;
+    builtins_mut()._tuple_iter_type.write(tp.clone());
+
+    let tp = RType::new_with_str("Generator")?;
+    builtins_mut()._generator_type.write(tp.clone());
+
+    let tp = RType::new_with_str("Coroutine")?;
+    builtins_mut()._coroutine_type.write(tp.clone());
+
+    let tp = RType::new_with_str("WeakRef")?;
+    builtins_mut()._weak_ref_type.write(tp.clone());
+
+    let tp = RType::new_with_str("WeakMap")?;
+    builtins_mut()._weak_map_type.write(tp.clone());
 
     Ok(())
 }
@@ -190,6 +280,7 @@ pub(crate) fn _init_builtin_types() -> Result<(), Error> {
     _init_type_map(map_type().clone())?;
     _init_type_function(function_type().clone())?;
     _init_type_option(option_type().clone())?;
+    _init_type_enum_variant(enum_variant_type().clone())?;
 
     _init_type_module(module_type().clone())?;
 
@@ -198,6 +289,11 @@ pub(crate) fn _init_builtin_types() -> Result<(), Error> {
 
     _init_type_arrayiter(array_iter_type().clone())?;
     _init_type_tupleiter(tuple_iter_type().clone())?;
+    _init_type_generator(generator_type().clone())?;
+    _init_type_coroutine(coroutine_type().clone())?;
+
+    _init_type_weak_ref(weak_ref_type().clone())?;
+    _init_type_weak_map(weak_map_type().clone())?;
 
     Ok(())
 }
@@ -220,16 +316,61 @@ pub(crate) fn _init_builtin_global() -> Result<(), Error> {
     set_global_with_str("Option", option_type().cast_value())?;
     set_global_with_str("ScriptCode", script_code_type().cast_value())?;
     set_global_with_str("Module", module_type().cast_value())?;
+    set_global_with_str("Coroutine", coroutine_type().cast_value())?;
+    set_global_with_str("WeakRef", weak_ref_type().cast_value())?;
+    set_global_with_str("WeakMap", weak_map_type().cast_value())?;
+
+    set_global_with_str("gc", _new_gc_module()?.cast_value())?;
 
     Ok(())
 }
 
 pub(crate) fn _init_builtin_values() -> Result<(), Error> {
-    unsafe {
-        _NULL_VALUE_.write(_new_null_value()?);
-        _TRUE_VALUE_.write(_new_bool_value(true)?);
-        _FALSE_VALUE_.write(_new_bool_value(false)?);
-        _NONE_VALUE_.write(ROption::new_(None)?);
-    }
+    builtins_mut()._null_value.write(_new_null_value()?);
+    builtins_mut()._true_value.write(_new_bool_value(true)?);
+    builtins_mut()._false_value.write(_new_bool_value(false)?);
+    builtins_mut()._none_value.write(ROption::new_(None)?);
+    builtins_mut()
+        ._coroutine_yield_func
+        .write(_new_coroutine_yield_func()?);
+
+    let mut tp = coroutine_type().clone();
+    tp.set_attr_str("yield", coroutine_yield_func().cast_value())?;
     Ok(())
 }
+
+/// 访问当前 Runtime 的 Builtins 持有的全部对象。
+pub(crate) fn _visit_builtin(visitor: &mut dyn Visitor) {
+    let types = [
+        type_type(),
+        null_type(),
+        bool_type(),
+        int_type(),
+        float_type(),
+        string_type(),
+        tuple_type(),
+        array_type(),
+        map_type(),
+        function_type(),
+        option_type(),
+        enum_variant_type(),
+        module_type(),
+        ast_type(),
+        script_code_type(),
+        array_iter_type(),
+        tuple_iter_type(),
+        generator_type(),
+        coroutine_type(),
+        weak_ref_type(),
+        weak_map_type(),
+    ];
+    for tp in types {
+        visitor.visit_value(tp.cast_value_ref());
+    }
+
+    visitor.visit_value(null().cast_value_ref());
+    visitor.visit_value(true_().cast_value_ref());
+    visitor.visit_value(false_().cast_value_ref());
+    visitor.visit_value(none().cast_value_ref());
+    visitor.visit_value(coroutine_yield_func().cast_value_ref());
+}
@@ -0,0 +1,572 @@
+//! `.rolc` 文件的二进制格式。
+//!
+//! 文件由头部与内容组成，所有整数均为小端序：
+//!
+//! ```text
+//! magic    b"ROLC"
+//! version  u16
+//! length   u32   内容的字节数
+//! checksum u32   内容的 FNV-1a 校验值
+//! payload  ...   顶层 RScriptCode，子代码递归地跟在其后，见 RScriptCode::serialize
+//! ```
+//!
+//! 指令与其中的寄存器、常量下标等都会原样写入，
+//! RScriptCode::deserialize 读取后会用 verify 检查它们是否有效，未通过检查的代码不会被返回。
+
+use crate::collections::Array;
+
+use crate::runtime::*;
+
+use crate::op::*;
+use crate::register::RegOp;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+#[cfg(test)]
+use crate::script_code::RScriptCode;
+
+pub(crate) const MAGIC: &[u8; 4] = b"ROLC";
+
+/// 格式改变时（包括 Opcode、RegOp 的增删与重新排序）需要递增。
+pub const BYTECODE_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 4 + 2 + 4 + 4;
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for b in data {
+        hash ^= *b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+/// 文件的内容是否以 `.rolc` 的 magic 开头。
+pub fn is_bytecode(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub(crate) struct Writer {
+    _buf: Array<u8>,
+}
+
+impl Writer {
+    /// 预留头部，内容写完后由 finish 填写。
+    pub(crate) fn new() -> Result<Self, Error> {
+        let mut buf = Array::new(allocator());
+        buf.resize(HEADER_LEN, 0).map_err(|_| Error::OutOfMemory)?;
+        Ok(Self { _buf: buf })
+    }
+
+    pub(crate) fn bytes(&mut self, b: &[u8]) -> Result<(), Error> {
+        self._buf.append_slice(b).map_err(|_| Error::OutOfMemory)
+    }
+
+    pub(crate) fn u8(&mut self, n: u8) -> Result<(), Error> {
+        self.bytes(&[n])
+    }
+    pub(crate) fn u16(&mut self, n: u16) -> Result<(), Error> {
+        self.bytes(&n.to_le_bytes())
+    }
+    pub(crate) fn u32(&mut self, n: u32) -> Result<(), Error> {
+        self.bytes(&n.to_le_bytes())
+    }
+    pub(crate) fn i32(&mut self, n: i32) -> Result<(), Error> {
+        self.bytes(&n.to_le_bytes())
+    }
+    pub(crate) fn i64(&mut self, n: i64) -> Result<(), Error> {
+        self.bytes(&n.to_le_bytes())
+    }
+    pub(crate) fn f64(&mut self, n: f64) -> Result<(), Error> {
+        self.bytes(&n.to_bits().to_le_bytes())
+    }
+    pub(crate) fn len(&mut self, n: usize) -> Result<(), Error> {
+        let n =
+            u32::try_from(n).map_err(|_| runtime_error_fmt!("in serialize, length too large"))?;
+        self.u32(n)
+    }
+    pub(crate) fn str(&mut self, s: &str) -> Result<(), Error> {
+        self.len(s.len())?;
+        self.bytes(s.as_bytes())
+    }
+
+    pub(crate) fn opcode(&mut self, op: Opcode) -> Result<(), Error> {
+        use Opcode::*;
+        match op {
+            Nop => self.u8(0),
+            LoadNull => self.u8(1),
+            LoadTrue => self.u8(2),
+            LoadFalse => self.u8(3),
+            LoadInt(n) => self.u8(4).and_then(|_| self.i32(n)),
+            LoadConstStr(n) => self.u8(5).and_then(|_| self.u32(n)),
+            LoadConstNum(n) => self.u8(6).and_then(|_| self.u32(n)),
+            LoadThis => self.u8(7),
+            NewTuple(n) => self.u8(8).and_then(|_| self.u32(n)),
+            NewArray(n) => self.u8(9).and_then(|_| self.u32(n)),
+            NewMap(n) => self.u8(10).and_then(|_| self.u32(n)),
+            NewClosure(n) => self.u8(11).and_then(|_| self.u32(n)),
+            NewType => self.u8(12),
+            NewEnum(n) => self.u8(13).and_then(|_| self.u32(n)),
+            SetOverload(n) => self.u8(14).and_then(|_| self.u8(n)),
+            GetCapture(n) => self.u8(15).and_then(|_| self.u32(n)),
+            SetCapture(n) => self.u8(16).and_then(|_| self.u32(n)),
+            GetLocal(n) => self.u8(17).and_then(|_| self.u32(n)),
+            SetLocal(n) => self.u8(18).and_then(|_| self.u32(n)),
+            GetGlobal(n) => self.u8(19).and_then(|_| self.u32(n)),
+            GetAttr(n) => self.u8(20).and_then(|_| self.u32(n)),
+            GetAttrDup(n) => self.u8(21).and_then(|_| self.u32(n)),
+            SetAttr(n) => self.u8(22).and_then(|_| self.u32(n)),
+            GetItem => self.u8(23),
+            SetItem => self.u8(24),
+            Add => self.u8(25),
+            Sub => self.u8(26),
+            Mul => self.u8(27),
+            Div => self.u8(28),
+            IDiv => self.u8(29),
+            Mod => self.u8(30),
+            Pow => self.u8(31),
+            And => self.u8(32),
+            Or => self.u8(33),
+            Not => self.u8(34),
+            BitAnd => self.u8(35),
+            BitOr => self.u8(36),
+            BitXor => self.u8(37),
+            BitNot => self.u8(38),
+            Shl => self.u8(39),
+            Shr => self.u8(40),
+            Cmp => self.u8(41),
+            Eq => self.u8(42),
+            Ne => self.u8(43),
+            Lt => self.u8(44),
+            Le => self.u8(45),
+            Gt => self.u8(46),
+            Ge => self.u8(47),
+            Iter => self.u8(48),
+            IfFalse(n) => self.u8(49).and_then(|_| self.i32(n)),
+            Jmp(n) => self.u8(50).and_then(|_| self.i32(n)),
+            IterNext(n) => self.u8(51).and_then(|_| self.i32(n)),
+            Call(n) => self.u8(52).and_then(|_| self.u32(n)),
+            CallThis(n) => self.u8(53).and_then(|_| self.u32(n)),
+            CallMethod(a, b) => self
+                .u8(54)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u16(b)),
+            CallAttr(a, b) => self
+                .u8(55)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u16(b)),
+            TailCall(n) => self.u8(56).and_then(|_| self.u32(n)),
+            Apply(n) => self.u8(57).and_then(|_| self.u32(n)),
+            Return => self.u8(58),
+            Yield => self.u8(59),
+            Pop => self.u8(60),
+            Dup => self.u8(61),
+            Rot => self.u8(62),
+            Rot3 => self.u8(63),
+            Rot4 => self.u8(64),
+            // 标签在 build 时已被替换为偏移。
+            IfFalseLabel(_) | JmpLabel(_) | IterNextLabel(_) => Err(runtime_error_fmt!(
+                "in serialize, unresolved label in opcode"
+            )),
+        }
+    }
+
+    pub(crate) fn reg_opcode(&mut self, op: RegOp) -> Result<(), Error> {
+        use RegOp::*;
+        match op {
+            Move(a, b) => self.u8(0).and_then(|_| self.regs(&[a, b])),
+            LoadNull(a) => self.u8(1).and_then(|_| self.u16(a)),
+            LoadInt(a, n) => self
+                .u8(2)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.i32(n)),
+            LoadConstStr(a, n) => self
+                .u8(3)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u32(n)),
+            LoadConstNum(a, n) => self
+                .u8(4)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u32(n)),
+            LoadThis(a) => self.u8(5).and_then(|_| self.u16(a)),
+            NewTuple(a, b, n) => self.u8(6).and_then(|_| self.regs(&[a, b, n])),
+            NewArray(a, b, n) => self.u8(7).and_then(|_| self.regs(&[a, b, n])),
+            NewMap(a, b, n) => self.u8(8).and_then(|_| self.regs(&[a, b, n])),
+            NewClosure(a, c, b, n) => self.u8(9).and_then(|_| self.regs(&[a, c, b, n])),
+            GetCapture(a, n) => self
+                .u8(10)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u32(n)),
+            SetCapture(a, n, b) => self
+                .u8(11)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u32(n))
+                .and_then(|_| self.u16(b)),
+            GetGlobal(a, n) => self
+                .u8(12)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u32(n)),
+            GetAttr(a, b, n) => self
+                .u8(13)
+                .and_then(|_| self.regs(&[a, b]))
+                .and_then(|_| self.u32(n)),
+            SetAttr(a, n, b) => self
+                .u8(14)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.u32(n))
+                .and_then(|_| self.u16(b)),
+            GetItem(a, b, c) => self.u8(15).and_then(|_| self.regs(&[a, b, c])),
+            SetItem(a, b, c) => self.u8(16).and_then(|_| self.regs(&[a, b, c])),
+            Arith(op, a, b, c) => self
+                .u8(17)
+                .and_then(|_| self.u8(op as u8))
+                .and_then(|_| self.regs(&[a, b, c])),
+            Cmp(op, a, b, c) => self
+                .u8(18)
+                .and_then(|_| self.u8(op as u8))
+                .and_then(|_| self.regs(&[a, b, c])),
+            Unary(op, a, b) => self
+                .u8(19)
+                .and_then(|_| self.u8(op as u8))
+                .and_then(|_| self.regs(&[a, b])),
+            Iter(a, b) => self.u8(20).and_then(|_| self.regs(&[a, b])),
+            IfFalse(a, n) => self
+                .u8(21)
+                .and_then(|_| self.u16(a))
+                .and_then(|_| self.i32(n)),
+            Jmp(n) => self.u8(22).and_then(|_| self.i32(n)),
+            IterNext(a, b, n) => self
+                .u8(23)
+                .and_then(|_| self.regs(&[a, b]))
+                .and_then(|_| self.i32(n)),
+            Call(a, n) => self.u8(24).and_then(|_| self.regs(&[a, n])),
+            CallMethod(a, b, n) => self.u8(25).and_then(|_| self.regs(&[a, b, n])),
+            CallAttr(a, b, n) => self.u8(26).and_then(|_| self.regs(&[a, b, n])),
+            TailCall(a, n) => self.u8(27).and_then(|_| self.regs(&[a, n])),
+            Return(a) => self.u8(28).and_then(|_| self.u16(a)),
+        }
+    }
+
+    fn regs(&mut self, ns: &[u16]) -> Result<(), Error> {
+        for n in ns {
+            self.u16(*n)?;
+        }
+        Ok(())
+    }
+
+    /// 填写头部并返回整个文件的内容。
+    pub(crate) fn finish(mut self) -> Result<Array<u8>, Error> {
+        let buf = self._buf.as_slice_mut();
+        let (header, payload) = buf.split_at_mut(HEADER_LEN);
+        let length = u32::try_from(payload.len())
+            .map_err(|_| runtime_error_fmt!("in serialize, code too large"))?;
+        header[0..4].copy_from_slice(MAGIC);
+        header[4..6].copy_from_slice(&BYTECODE_VERSION.to_le_bytes());
+        header[6..10].copy_from_slice(&length.to_le_bytes());
+        header[10..14].copy_from_slice(&checksum(payload).to_le_bytes());
+        Ok(self._buf)
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    _data: &'a [u8],
+    _pos: usize,
+}
+
+fn invalid(what: &str) -> Error {
+    runtime_error_fmt!("invalid bytecode: {}", what)
+}
+
+impl<'a> Reader<'a> {
+    /// 检查头部与校验值，返回位于内容开头的 Reader。
+    pub(crate) fn new(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_LEN || !is_bytecode(data) {
+            return Err(invalid("not a .rolc file"));
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != BYTECODE_VERSION {
+            return Err(runtime_error_fmt!(
+                "incompatible bytecode version {}, this runtime supports version {}",
+                version,
+                BYTECODE_VERSION
+            ));
+        }
+        let length = u32::from_le_bytes([data[6], data[7], data[8], data[9]]) as usize;
+        let expected = u32::from_le_bytes([data[10], data[11], data[12], data[13]]);
+        let payload = &data[HEADER_LEN..];
+        if payload.len() != length {
+            return Err(invalid("length mismatch"));
+        }
+        if checksum(payload) != expected {
+            return Err(invalid("checksum mismatch"));
+        }
+        Ok(Self {
+            _data: payload,
+            _pos: 0,
+        })
+    }
+
+    pub(crate) fn is_end(&self) -> bool {
+        self._pos == self._data.len()
+    }
+
+    pub(crate) fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            ._pos
+            .checked_add(n)
+            .filter(|e| *e <= self._data.len())
+            .ok_or_else(|| invalid("unexpected end"))?;
+        let b = &self._data[self._pos..end];
+        self._pos = end;
+        Ok(b)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut a = [0; N];
+        a.copy_from_slice(self.bytes(N)?);
+        Ok(a)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+    pub(crate) fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    pub(crate) fn i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+    pub(crate) fn i64(&mut self) -> Result<i64, Error> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+    pub(crate) fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_bits(u64::from_le_bytes(self.array()?)))
+    }
+    pub(crate) fn len(&mut self) -> Result<usize, Error> {
+        Ok(self.u32()? as usize)
+    }
+    pub(crate) fn str(&mut self) -> Result<&'a str, Error> {
+        let n = self.len()?;
+        core::str::from_utf8(self.bytes(n)?).map_err(|_| invalid("string is not utf-8"))
+    }
+    pub(crate) fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("bad bool")),
+        }
+    }
+
+    pub(crate) fn opcode(&mut self) -> Result<Opcode, Error> {
+        use Opcode::*;
+        let op = match self.u8()? {
+            0 => Nop,
+            1 => LoadNull,
+            2 => LoadTrue,
+            3 => LoadFalse,
+            4 => LoadInt(self.i32()?),
+            5 => LoadConstStr(self.u32()?),
+            6 => LoadConstNum(self.u32()?),
+            7 => LoadThis,
+            8 => NewTuple(self.u32()?),
+            9 => NewArray(self.u32()?),
+            10 => NewMap(self.u32()?),
+            11 => NewClosure(self.u32()?),
+            12 => NewType,
+            13 => NewEnum(self.u32()?),
+            14 => SetOverload(self.u8()?),
+            15 => GetCapture(self.u32()?),
+            16 => SetCapture(self.u32()?),
+            17 => GetLocal(self.u32()?),
+            18 => SetLocal(self.u32()?),
+            19 => GetGlobal(self.u32()?),
+            20 => GetAttr(self.u32()?),
+            21 => GetAttrDup(self.u32()?),
+            22 => SetAttr(self.u32()?),
+            23 => GetItem,
+            24 => SetItem,
+            25 => Add,
+            26 => Sub,
+            27 => Mul,
+            28 => Div,
+            29 => IDiv,
+            30 => Mod,
+            31 => Pow,
+            32 => And,
+            33 => Or,
+            34 => Not,
+            35 => BitAnd,
+            36 => BitOr,
+            37 => BitXor,
+            38 => BitNot,
+            39 => Shl,
+            40 => Shr,
+            41 => Cmp,
+            42 => Eq,
+            43 => Ne,
+            44 => Lt,
+            45 => Le,
+            46 => Gt,
+            47 => Ge,
+            48 => Iter,
+            49 => IfFalse(self.i32()?),
+            50 => Jmp(self.i32()?),
+            51 => IterNext(self.i32()?),
+            52 => Call(self.u32()?),
+            53 => CallThis(self.u32()?),
+            54 => CallMethod(self.u16()?, self.u16()?),
+            55 => CallAttr(self.u16()?, self.u16()?),
+            56 => TailCall(self.u32()?),
+            57 => Apply(self.u32()?),
+            58 => Return,
+            59 => Yield,
+            60 => Pop,
+            61 => Dup,
+            62 => Rot,
+            63 => Rot3,
+            64 => Rot4,
+            _ => return Err(invalid("unknown opcode")),
+        };
+        Ok(op)
+    }
+
+    fn arith_op(&mut self) -> Result<ArithOp, Error> {
+        let n = self.u8()?;
+        if (n as usize) < ARITH_OP_COUNT {
+            Ok(unsafe { core::mem::transmute::<u8, ArithOp>(n) })
+        } else {
+            Err(invalid("unknown arith op"))
+        }
+    }
+
+    fn cmp_op(&mut self) -> Result<CmpOp, Error> {
+        let n = self.u8()?;
+        if (n as usize) < CMP_OP_COUNT {
+            Ok(unsafe { core::mem::transmute::<u8, CmpOp>(n) })
+        } else {
+            Err(invalid("unknown cmp op"))
+        }
+    }
+
+    fn unary_op(&mut self) -> Result<UnaryOp, Error> {
+        let n = self.u8()?;
+        if (n as usize) < UNARY_OP_COUNT {
+            Ok(unsafe { core::mem::transmute::<u8, UnaryOp>(n) })
+        } else {
+            Err(invalid("unknown unary op"))
+        }
+    }
+
+    pub(crate) fn reg_opcode(&mut self) -> Result<RegOp, Error> {
+        use RegOp::*;
+        let op = match self.u8()? {
+            0 => Move(self.u16()?, self.u16()?),
+            1 => LoadNull(self.u16()?),
+            2 => LoadInt(self.u16()?, self.i32()?),
+            3 => LoadConstStr(self.u16()?, self.u32()?),
+            4 => LoadConstNum(self.u16()?, self.u32()?),
+            5 => LoadThis(self.u16()?),
+            6 => NewTuple(self.u16()?, self.u16()?, self.u16()?),
+            7 => NewArray(self.u16()?, self.u16()?, self.u16()?),
+            8 => NewMap(self.u16()?, self.u16()?, self.u16()?),
+            9 => NewClosure(self.u16()?, self.u16()?, self.u16()?, self.u16()?),
+            10 => GetCapture(self.u16()?, self.u32()?),
+            11 => SetCapture(self.u16()?, self.u32()?, self.u16()?),
+            12 => GetGlobal(self.u16()?, self.u32()?),
+            13 => GetAttr(self.u16()?, self.u16()?, self.u32()?),
+            14 => SetAttr(self.u16()?, self.u32()?, self.u16()?),
+            15 => GetItem(self.u16()?, self.u16()?, self.u16()?),
+            16 => SetItem(self.u16()?, self.u16()?, self.u16()?),
+            17 => Arith(self.arith_op()?, self.u16()?, self.u16()?, self.u16()?),
+            18 => Cmp(self.cmp_op()?, self.u16()?, self.u16()?, self.u16()?),
+            19 => Unary(self.unary_op()?, self.u16()?, self.u16()?),
+            20 => Iter(self.u16()?, self.u16()?),
+            21 => IfFalse(self.u16()?, self.i32()?),
+            22 => Jmp(self.i32()?),
+            23 => IterNext(self.u16()?, self.u16()?, self.i32()?),
+            24 => Call(self.u16()?, self.u16()?),
+            25 => CallMethod(self.u16()?, self.u16()?, self.u16()?),
+            26 => CallAttr(self.u16()?, self.u16()?, self.u16()?),
+            27 => TailCall(self.u16()?, self.u16()?),
+            28 => Return(self.u16()?),
+            _ => return Err(invalid("unknown register opcode")),
+        };
+        Ok(op)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::{bytecode_to_function, eval, initialize, parse_to_code};
+    use crate::test_util::{allocator, loader};
+    use crate::value::{value_call, value_str, Ref};
+
+    #[test]
+    fn test_bytecode() {
+        initialize(allocator(), loader()).unwrap();
+
+        let sources = [
+            "((1 << 60) + 3, 0.25, 1 + 0.5)",
+            "s = \"ab\"; (s + \"cd\", [1, 2], (3,))",
+            "i = 0; s = 0; while (i < 10) { s = s + i * 2; i = i + 1; } s",
+            "function f(n) { if (n <= 2) return 1; return f(n - 1) + f(n - 2); } f(10)",
+            "a = 1; g = (x) => x + a; g(2)",
+        ];
+
+        for source in sources {
+            let expected = value_str(&eval(source).unwrap()).unwrap();
+            let data = parse_to_code(source, true).unwrap().serialize().unwrap();
+            let func = bytecode_to_function(data.as_slice()).unwrap();
+            let v = value_call(func.cast_value_ref(), &[]).unwrap();
+            assert_eq!(
+                value_str(&v).unwrap().as_str(),
+                expected.as_str(),
+                "{}",
+                source
+            );
+        }
+
+        let data = parse_to_code("1", true).unwrap().serialize().unwrap();
+        let mut bytes = data.as_slice().to_vec();
+        bytes[4] = bytes[4].wrapping_add(1);
+        assert!(RScriptCode::deserialize(&bytes).is_err());
+
+        let mut bytes = data.as_slice().to_vec();
+        *bytes.last_mut().unwrap() ^= 0xff;
+        assert!(RScriptCode::deserialize(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_bytecode_closure() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 闭包捕获外层函数的局部变量，通过它在多次调用之间保存状态。
+        let source = "
+            function counter(step) {
+                n = [0];
+                return () => { n[0] = n[0] + step; n[0] };
+            }
+            c = counter(2);
+            c(); c();
+            c()
+        ";
+        let code = parse_to_code(source, true).unwrap();
+        let data = code.serialize().unwrap();
+        let code = RScriptCode::deserialize(data.as_slice()).unwrap();
+
+        // 子代码的 parent 指向加载出的外层代码。
+        let counter = code.get_child(0).unwrap();
+        assert!(Ref::ptr_eq(&counter.parent().unwrap(), &code));
+        let lambda = counter.get_child(0).unwrap();
+        assert!(Ref::ptr_eq(&lambda.parent().unwrap(), &counter));
+
+        let func = bytecode_to_function(data.as_slice()).unwrap();
+        let v = value_call(func.cast_value_ref(), &[]).unwrap();
+        assert_eq!(value_str(&v).unwrap().as_str(), "6");
+    }
+}
@@ -147,11 +147,15 @@ impl<K, V> FixedMap<K, V> {
         }
     }
 
+    // _data 只声明了一个元素，其余元素紧随其后，不能用切片下标访问。
+    unsafe fn _get_entry_ptr(&self, i: usize) -> *mut (Bucket<K, V>, Node<K, V>) {
+        self._data.as_ptr().add(i) as *mut _
+    }
     unsafe fn _get_bucket_ptr(&self, i: usize) -> NonNull<Bucket<K, V>> {
-        nonnull_of!(self._data.get_unchecked(i).0)
+        nonnull_of!((*self._get_entry_ptr(i)).0)
     }
     unsafe fn _get_node_ptr(&self, i: usize) -> NonNull<Node<K, V>> {
-        nonnull_of!(self._data.get_unchecked(i).1)
+        nonnull_of!((*self._get_entry_ptr(i)).1)
     }
 
     pub unsafe fn free(allocator: &dyn Allocator, mut ptr: NonNull<Self>) {
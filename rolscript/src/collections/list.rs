@@ -203,6 +203,57 @@ impl ListBase {
         }
     }
 
+    /// 把 other 的全部节点移动到末尾，other 成为空链表。
+    #[inline]
+    pub fn append(&mut self, other: &mut ListBase) {
+        unsafe {
+            let other_guard = other._as_guard_ptr();
+            let mut first = other._as_guard()._next;
+            if first == other_guard {
+                return;
+            }
+            let mut last = other._as_guard()._prev;
+
+            let guard_ptr = self._as_guard_ptr();
+            let mut old_last = self._as_guard()._prev;
+
+            old_last.as_mut()._next = first;
+            first.as_mut()._prev = old_last;
+            last.as_mut()._next = guard_ptr;
+            self._as_guard_mut()._prev = last;
+            self._len += other._len;
+
+            other._as_guard_mut()._clear_node();
+            other._len = 0;
+        }
+    }
+
+    #[inline]
+    pub fn first(&self) -> Option<NonNull<ListNodeBase>> {
+        let first = self._as_guard()._next;
+        if first != self._as_guard_ptr() {
+            Some(first)
+        } else {
+            None
+        }
+    }
+
+    /// node 之后的节点。
+    /// 与 iter 不同，遍历时可以在末尾插入节点，插入的节点也会被遍历到。
+    ///
+    /// # Safety
+    ///
+    /// node 必须是该链表中尚未移除的节点。
+    #[inline]
+    pub unsafe fn next_of(&self, node: NonNull<ListNodeBase>) -> Option<NonNull<ListNodeBase>> {
+        let next = node.as_ref()._next;
+        if next != self._as_guard_ptr() {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
     pub fn iter(&self) -> ListIter {
         ListIter::new(self)
     }
@@ -0,0 +1,367 @@
+use core::mem::size_of;
+use core::ptr::addr_of_mut;
+use core::ptr::NonNull;
+
+use crate::collections::Array;
+
+use crate::runtime::*;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+use crate::function::*;
+use crate::string::RString;
+use crate::type_::*;
+use crate::value::*;
+
+use crate::builtin::*;
+
+use crate::util::expect_arg1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CoroutineStatus {
+    Suspended,
+    Running,
+    Dead,
+}
+
+/// 协程，类似 Lua 的 coroutine。
+/// 协程中的脚本函数调用不会在 Rust 栈上递归，所有的帧都保存在 _frames 中，
+/// 因此 Coroutine::yield 可以在任意深度的脚本调用中挂起整个协程。
+/// 协程执行期间，这些帧也记录在 Runtime 的调用栈中，计入最大调用深度并出现在 traceback 中。
+#[repr(C)]
+pub struct RCoroutine {
+    _header: GcHeader,
+    _func: RValue,
+    _frames: Array<ScriptFrame>,
+    _started: bool,
+    _status: CoroutineStatus,
+}
+
+impl RCoroutine {
+    unsafe fn init(mut ptr: NonNull<Self>, func: RValue) {
+        let r = ptr.as_mut();
+        addr_of_mut!(r._func).write(func);
+        addr_of_mut!(r._frames).write(Array::new(allocator()));
+        addr_of_mut!(r._started).write(false);
+        addr_of_mut!(r._status).write(CoroutineStatus::Suspended);
+    }
+
+    unsafe fn _drop(&mut self) {
+        addr_of_mut!(self._func).drop_in_place();
+        addr_of_mut!(self._frames).drop_in_place();
+    }
+
+    pub fn new(func: RValue) -> Result<Ref<Self>, Error> {
+        unsafe {
+            let tp = coroutine_type().clone();
+            let v = new_gc_obj(size_of::<Self>(), tp)?.cast::<Self>();
+            Self::init(v.as_nonnull_ptr(), func);
+            Ok(v)
+        }
+    }
+
+    pub fn status(&self) -> &'static str {
+        match self._status {
+            CoroutineStatus::Suspended => "suspended",
+            CoroutineStatus::Running => "running",
+            CoroutineStatus::Dead => "dead",
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self._status == CoroutineStatus::Dead
+    }
+
+    /// 开始或继续执行协程，直到协程 yield 或结束。
+    /// 第一次调用时 args 作为函数的参数，之后 args 的第一个值作为 Coroutine::yield 的返回值。
+    pub fn resume(&mut self, args: &[RValue]) -> Result<RValue, Error> {
+        match self._status {
+            CoroutineStatus::Dead => {
+                return Err(runtime_error_fmt!("cannot resume dead coroutine"));
+            }
+            CoroutineStatus::Running => {
+                return Err(runtime_error_fmt!("cannot resume running coroutine"));
+            }
+            CoroutineStatus::Suspended => (),
+        }
+
+        self._status = CoroutineStatus::Running;
+        let res = if self._started {
+            let sent = args.first().cloned().unwrap_or(null().cast_value());
+            self._run(sent)
+        } else {
+            self._started = true;
+            self._start(args)
+        };
+
+        match res {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                self._status = CoroutineStatus::Dead;
+                while self._frames.pop().is_some() {}
+                Err(e)
+            }
+        }
+    }
+
+    fn _start(&mut self, args: &[RValue]) -> Result<RValue, Error> {
+        let func = self._func.clone();
+        match as_plain_script_function(&func) {
+            Some(f) => {
+                let mut frame = ScriptFrame::new(&f, null().cast_value_ref(), args)?;
+                frame.set_coroutine(true);
+                frame.detach()?;
+                self._push_frame(frame)?;
+                self._run(null().cast_value())
+            }
+            None => {
+                // 非脚本函数与寄存器指令的函数无法挂起，直接调用，调用结束时协程也随之结束。
+                let ret = value_call(&func, args)?;
+                self._status = CoroutineStatus::Dead;
+                Ok(ret)
+            }
+        }
+    }
+
+    fn _push_frame(&mut self, frame: ScriptFrame) -> Result<(), Error> {
+        self._frames.push(frame).map_err(|_| Error::OutOfMemory)
+    }
+
+    fn _run(&mut self, sent: RValue) -> Result<RValue, Error> {
+        let depth = runtime()._call_depth();
+        let res = self._enter().and_then(|_| self._run_frames(sent));
+        runtime()._truncate_frames(depth);
+        res
+    }
+
+    // 把挂起时保存的帧重新记录到 Runtime 的调用栈中。
+    fn _enter(&self) -> Result<(), Error> {
+        for frame in self._frames.as_slice() {
+            runtime()._push_frame(frame.callee().cast_value_ref())?;
+        }
+        Ok(())
+    }
+
+    fn _run_frames(&mut self, mut sent: RValue) -> Result<RValue, Error> {
+        loop {
+            let frame = self
+                ._frames
+                .as_slice_mut()
+                .last_mut()
+                .ok_or_else(|| runtime_error_fmt!("coroutine has no frame"))?;
+
+            match frame.resume(sent)? {
+                FrameState::Return(v) => {
+                    self._frames.pop();
+                    runtime()._pop_frame();
+                    if self._frames.len() == 0 {
+                        self._status = CoroutineStatus::Dead;
+                        return Ok(v);
+                    }
+                    sent = v;
+                }
+                FrameState::Call(new_frame) => {
+                    runtime()._push_frame(new_frame.callee().cast_value_ref())?;
+                    self._push_frame(new_frame)?;
+                    sent = null().cast_value();
+                }
+                FrameState::Yield(v) => {
+                    self._status = CoroutineStatus::Suspended;
+                    return Ok(v);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn _init_type_coroutine(mut tp: Ref<RType>) -> Result<(), Error> {
+    tp.with_new(coroutine__new);
+    tp.with_visit(coroutine__visit);
+    tp.with_destory(coroutine__destory);
+
+    tp.with_str(default_value_str);
+    tp.with_hash(default_value_hash);
+    tp.with_eq(default_value_eq);
+
+    tp.add_method_str_light("resume", coroutine__resume)?;
+    tp.add_method_str_light("status", coroutine__status)?;
+
+    Ok(())
+}
+
+/// Coroutine::yield 只有在协程中被脚本直接调用时才会被解释器拦截，
+/// 其余情况（协程外，或经由 Rust 函数间接调用）都无法挂起。
+pub(crate) fn _new_coroutine_yield_func() -> Result<Ref<RFunction>, Error> {
+    RFunction::from_rust_func(coroutine__yield)
+}
+
+#[allow(non_snake_case)]
+fn coroutine__new(_tp: &Ref<RType>, args: &[RValue]) -> Result<RValue, Error> {
+    let func = expect_arg1(args)?;
+    Ok(RCoroutine::new(func)?.cast_value())
+}
+
+#[allow(non_snake_case)]
+fn coroutine__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let co = value_ptr.cast::<RCoroutine>();
+        let co = co.as_ref();
+        visitor.visit_value(&co._func);
+        for frame in co._frames.as_slice() {
+            frame.visit(visitor);
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+fn coroutine__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut co = value.expect_cast::<RCoroutine>(coroutine_type())?;
+        co._drop();
+        Ok(())
+    }
+}
+
+#[allow(non_snake_case)]
+fn coroutine__resume(this: &RValue, args: &[RValue]) -> Result<RValue, Error> {
+    let mut co = unsafe { this.expect_cast::<RCoroutine>(coroutine_type())? };
+    co.resume(args)
+}
+
+#[allow(non_snake_case)]
+fn coroutine__status(this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    let co = unsafe { this.expect_cast::<RCoroutine>(coroutine_type())? };
+    Ok(RString::new(co.status())?.cast_value())
+}
+
+#[allow(non_snake_case)]
+fn coroutine__yield(_this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    Err(runtime_error_fmt!(
+        "attempt to yield outside a coroutine or across a rust function"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::RCoroutine;
+    use crate::builtin::null;
+    use crate::error::Error;
+    use crate::function::RFunction;
+    use crate::number::RBool;
+    use crate::runtime::{eval, initialize, runtime, set_global_with_str, traceback};
+    use crate::test_util::{allocator, loader};
+    use core::ptr::addr_of;
+
+    fn eval_bool(script: &str) -> bool {
+        let v = eval(script).unwrap();
+        unsafe { v.cast_ref::<RBool>().as_bool() }
+    }
+
+    const PRODUCE_CONSUME: &str = "
+        function produce(n) {
+            Coroutine::yield(n);
+            return n + 1;
+        }
+        function consume(a) {
+            b = produce(a);
+            c = Coroutine::yield(b);
+            return c;
+        }
+        co = Coroutine(consume);
+    ";
+
+    #[test]
+    fn test_coroutine_resume() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 第一次 resume 的参数作为函数参数，yield 可以发生在嵌套调用中；
+        // 之后 resume 的参数作为 yield 的返回值。
+        let script = format!(
+            "{}
+            s0 = co.status();
+            a = co.resume(1);
+            s1 = co.status();
+            b = co.resume();
+            c = co.resume(3);
+            (s0, a, s1, b, c, co.status()) == (\"suspended\", 1, \"suspended\", 2, 3, \"dead\")
+            ",
+            PRODUCE_CONSUME
+        );
+        assert!(eval_bool(&script));
+
+        let script = format!(
+            "{}co.resume(1); co.resume(); co.resume(3); co.resume()",
+            PRODUCE_CONSUME
+        );
+        assert!(eval(&script).is_err());
+    }
+
+    #[test]
+    fn test_coroutine_status() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 协程内部观察到自身处于运行状态。
+        let script = "
+            function f(co) {
+                Coroutine::yield(co.status());
+            }
+            co = Coroutine(f);
+            co.resume(co) == \"running\"
+        ";
+        assert!(eval_bool(script));
+
+        // 协程不能 resume 自身。
+        let script = "
+            function f(co) {
+                co.resume();
+            }
+            co = Coroutine(f);
+            co.resume(co)
+        ";
+        assert!(eval(script).is_err());
+
+        // 出错的协程随即结束。
+        let co = eval("function g() { return 1 + null; } Coroutine(g)").unwrap();
+        let mut co = unsafe { co.cast::<RCoroutine>() };
+        assert_eq!(co.status(), "suspended");
+        assert!(co.resume(&[]).is_err());
+        assert_eq!(co.status(), "dead");
+        assert!(co.is_dead());
+
+        // 协程外 yield 会报错。
+        assert!(eval("Coroutine::yield(1)").is_err());
+    }
+
+    #[test]
+    fn test_coroutine_call_depth() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 协程中的脚本调用也计入最大调用深度，无限递归不会耗尽内存。
+        let script = "function f(n) { return 1 + f(n + 1); } Coroutine(f).resume(0)";
+        assert!(matches!(eval(script), Err(Error::Recursion { .. })));
+        assert_eq!(runtime()._call_depth(), 0);
+
+        static mut TRACEBACK: Option<String> = None;
+        let tb = RFunction::from_rust_func(|_, _| {
+            let tb = traceback()?;
+            unsafe { TRACEBACK = Some(tb.as_str().to_string()) };
+            Ok(null().cast_value())
+        })
+        .unwrap();
+        set_global_with_str("tb", tb.cast_value()).unwrap();
+
+        // 挂起后恢复的帧也出现在回溯中：脚本、resume、g、h 与 tb。
+        let script = "
+            function h() { Coroutine::yield(1); tb(); return 0; }
+            function g() { x = h(); return x; }
+            co = Coroutine(g);
+            co.resume();
+            co.resume();
+        ";
+        eval(script).unwrap();
+        let tb = unsafe { (*addr_of!(TRACEBACK)).clone().unwrap() };
+        assert_eq!(tb.lines().count(), 6);
+        assert_eq!(runtime()._call_depth(), 0);
+    }
+}
@@ -0,0 +1,451 @@
+use core::fmt::{self, Debug, Write};
+
+use crate::collections::Array;
+
+use crate::runtime::*;
+
+use crate::number::*;
+use crate::op::*;
+use crate::register::RegOp;
+use crate::script_code::RScriptCode;
+use crate::string::RString;
+use crate::value::*;
+
+use crate::builtin::*;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+/// 把 code 及其子代码反汇编为可读的文本写入 out。
+///
+/// 常量与变量名直接显示在指令中，跳转目标显示为标签，
+/// 子代码以 `<main>.0.1` 的形式命名并跟在父代码之后。
+pub fn disassemble(code: &RScriptCode, out: &mut dyn Write) -> Result<(), Error> {
+    let mut path = Array::new(allocator());
+    _disassemble(code, &mut path, out)
+        .map_err(|_| runtime_error_fmt!("in disassemble, failed to write"))
+}
+
+// 写入 Debug 的文本时只保留 `(` 之前的部分，即枚举变体名。
+struct NameOnly<'a> {
+    out: &'a mut dyn Write,
+    done: bool,
+}
+
+impl Write for NameOnly<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.done {
+            return Ok(());
+        }
+        match s.find('(') {
+            Some(n) => {
+                self.done = true;
+                self.out.write_str(&s[..n])
+            }
+            None => self.out.write_str(s),
+        }
+    }
+}
+
+// 返回变体是否带有字段。
+fn write_name(out: &mut dyn Write, op: &dyn Debug) -> Result<bool, fmt::Error> {
+    let mut w = NameOnly { out, done: false };
+    write!(w, "{:?}", op)?;
+    Ok(w.done)
+}
+
+fn write_path(out: &mut dyn Write, path: &Array<usize>) -> fmt::Result {
+    out.write_str("<main>")?;
+    for n in path.as_slice() {
+        write!(out, ".{}", n)?;
+    }
+    Ok(())
+}
+
+// 把 (名字, 下标) 按下标排好，缺少的下标为 None。
+fn names(iter: impl Iterator<Item = (Ref<RString>, u32)>) -> Array<Option<Ref<RString>>> {
+    let mut arr = Array::new(allocator());
+    for (name, idx) in iter {
+        let idx = idx as usize;
+        if arr.len() <= idx && arr.resize(idx + 1, None).is_err() {
+            break;
+        }
+        arr.as_slice_mut()[idx] = Some(name);
+    }
+    arr
+}
+
+struct Ctx<'a> {
+    code: &'a RScriptCode,
+    path: &'a Array<usize>,
+    locals: Array<Option<Ref<RString>>>,
+    captured: Array<Option<Ref<RString>>>,
+    labels: Array<usize>,
+}
+
+impl Ctx<'_> {
+    fn label(&self, ip: usize) -> Option<usize> {
+        self.labels.as_slice().binary_search(&ip).ok()
+    }
+
+    fn jump(&self, out: &mut dyn Write, ip: usize, off: i32) -> fmt::Result {
+        let target = (ip as isize + off as isize) as usize;
+        match self.label(target) {
+            Some(n) => write!(out, "L{}", n),
+            None => write!(out, "{:+}", off),
+        }
+    }
+
+    fn string(&self, out: &mut dyn Write, idx: u32) -> fmt::Result {
+        match self.code.get_const_string(idx as usize) {
+            Some(s) => write!(out, "{:?}", s.as_str()),
+            None => write!(out, "<string {}>", idx),
+        }
+    }
+
+    fn number(&self, out: &mut dyn Write, idx: u32) -> fmt::Result {
+        match self.code.get_const_number(idx as usize) {
+            Some(n) if n.is_type(int_type()) => {
+                write!(out, "{}", unsafe { n.cast_ref::<RInt>().as_number() })
+            }
+            Some(n) if n.is_type(float_type()) => {
+                write!(out, "{:?}", unsafe { n.cast_ref::<RFloat>().as_number() })
+            }
+            _ => write!(out, "<number {}>", idx),
+        }
+    }
+
+    fn var(out: &mut dyn Write, names: &Array<Option<Ref<RString>>>, idx: usize) -> fmt::Result {
+        match names.get(idx) {
+            Some(Some(name)) => write!(out, "{}", name.as_str()),
+            _ => write!(out, "#{}", idx),
+        }
+    }
+
+    fn child(&self, out: &mut dyn Write, idx: usize) -> fmt::Result {
+        write_path(out, self.path)?;
+        write!(out, ".{}", idx)
+    }
+
+    fn reg(&self, out: &mut dyn Write, r: u16) -> fmt::Result {
+        write!(out, "r{}", r)?;
+        if let Some(Some(name)) = self.locals.get(r as usize) {
+            write!(out, "({})", name.as_str())?;
+        }
+        Ok(())
+    }
+
+    fn regs(&self, out: &mut dyn Write, rs: &[u16]) -> fmt::Result {
+        for (i, r) in rs.iter().enumerate() {
+            if i != 0 {
+                out.write_str(", ")?;
+            }
+            self.reg(out, *r)?;
+        }
+        Ok(())
+    }
+
+    fn opcode(&self, out: &mut dyn Write, ip: usize, op: Opcode) -> fmt::Result {
+        use Opcode::*;
+        if write_name(out, &op)? {
+            out.write_str(" ")?;
+        }
+        match op {
+            LoadConstStr(n) | GetGlobal(n) | GetAttr(n) | GetAttrDup(n) | SetAttr(n) => {
+                self.string(out, n)
+            }
+            LoadConstNum(n) => self.number(out, n),
+            GetLocal(n) | SetLocal(n) => Self::var(out, &self.locals, n as usize),
+            GetCapture(n) => Self::var(out, &self.captured, n as usize),
+            NewClosure(n) => self.child(out, n as usize),
+            SetOverload(n) => match OverloadOp::from_u8(n) {
+                Some(oop) => write!(out, "{:?}", oop),
+                None => write!(out, "{}", n),
+            },
+            IfFalse(off) | Jmp(off) | IterNext(off) => self.jump(out, ip, off),
+            CallMethod(name, argc) | CallAttr(name, argc) => {
+                self.string(out, name as u32)?;
+                write!(out, ", {}", argc)
+            }
+            LoadInt(n) => write!(out, "{}", n),
+            SetCapture(n) | NewTuple(n) | NewArray(n) | NewMap(n) | NewEnum(n) | Call(n)
+            | CallThis(n) | TailCall(n) | Apply(n) | IfFalseLabel(n) | JmpLabel(n)
+            | IterNextLabel(n) => write!(out, "{}", n),
+            _ => Ok(()),
+        }
+    }
+
+    fn reg_opcode(&self, out: &mut dyn Write, ip: usize, op: RegOp) -> fmt::Result {
+        use RegOp::*;
+        match op {
+            Arith(aop, ..) => write!(out, "{:?} ", aop)?,
+            Cmp(cop, ..) => write!(out, "{:?} ", cop)?,
+            Unary(uop, ..) => write!(out, "{:?} ", uop)?,
+            _ => {
+                write_name(out, &op)?;
+                out.write_str(" ")?;
+            }
+        }
+        match op {
+            Move(a, b) | Iter(a, b) | Unary(_, a, b) => self.regs(out, &[a, b]),
+            LoadNull(a) | LoadThis(a) | Return(a) => self.reg(out, a),
+            LoadInt(a, n) => {
+                self.reg(out, a)?;
+                write!(out, ", {}", n)
+            }
+            LoadConstStr(a, n) | GetGlobal(a, n) => {
+                self.reg(out, a)?;
+                out.write_str(", ")?;
+                self.string(out, n)
+            }
+            LoadConstNum(a, n) => {
+                self.reg(out, a)?;
+                out.write_str(", ")?;
+                self.number(out, n)
+            }
+            NewTuple(a, b, n) | NewArray(a, b, n) | NewMap(a, b, n) => {
+                self.regs(out, &[a, b])?;
+                write!(out, ", {}", n)
+            }
+            NewClosure(a, c, b, n) => {
+                self.reg(out, a)?;
+                out.write_str(", ")?;
+                self.child(out, c as usize)?;
+                out.write_str(", ")?;
+                self.reg(out, b)?;
+                write!(out, ", {}", n)
+            }
+            GetCapture(a, n) => {
+                self.reg(out, a)?;
+                out.write_str(", ")?;
+                Self::var(out, &self.captured, n as usize)
+            }
+            SetCapture(a, n, b) => {
+                self.reg(out, a)?;
+                write!(out, ", {}, ", n)?;
+                self.reg(out, b)
+            }
+            GetAttr(a, b, n) => {
+                self.regs(out, &[a, b])?;
+                out.write_str(", ")?;
+                self.string(out, n)
+            }
+            SetAttr(a, n, b) => {
+                self.reg(out, a)?;
+                out.write_str(", ")?;
+                self.string(out, n)?;
+                out.write_str(", ")?;
+                self.reg(out, b)
+            }
+            GetItem(a, b, c) | SetItem(a, b, c) | Arith(_, a, b, c) | Cmp(_, a, b, c) => {
+                self.regs(out, &[a, b, c])
+            }
+            IfFalse(a, off) => {
+                self.reg(out, a)?;
+                out.write_str(", ")?;
+                self.jump(out, ip, off)
+            }
+            Jmp(off) => self.jump(out, ip, off),
+            IterNext(a, b, off) => {
+                self.regs(out, &[a, b])?;
+                out.write_str(", ")?;
+                self.jump(out, ip, off)
+            }
+            Call(a, n) | TailCall(a, n) => {
+                self.reg(out, a)?;
+                write!(out, ", {}", n)
+            }
+            CallMethod(a, name, n) | CallAttr(a, name, n) => {
+                self.reg(out, a)?;
+                out.write_str(", ")?;
+                self.string(out, name as u32)?;
+                write!(out, ", {}", n)
+            }
+        }
+    }
+}
+
+fn jump_targets(code: &RScriptCode) -> Array<usize> {
+    let mut targets = Array::new(allocator());
+    let mut add = |ip: usize, off: i32| {
+        let _ = targets.push((ip as isize + off as isize) as usize);
+    };
+    for (ip, op) in code.opcode().iter().enumerate() {
+        match *op {
+            Opcode::IfFalse(off) | Opcode::Jmp(off) | Opcode::IterNext(off) => add(ip, off),
+            _ => {}
+        }
+    }
+    for (ip, op) in code.reg_opcode().iter().enumerate() {
+        match *op {
+            RegOp::IfFalse(_, off) | RegOp::Jmp(off) | RegOp::IterNext(_, _, off) => add(ip, off),
+            _ => {}
+        }
+    }
+    let slice = targets.as_slice_mut();
+    slice.sort_unstable();
+    let mut n = 0;
+    for i in 0..slice.len() {
+        if i == 0 || slice[i] != slice[n - 1] {
+            slice[n] = slice[i];
+            n += 1;
+        }
+    }
+    while targets.len() > n {
+        targets.pop();
+    }
+    targets
+}
+
+fn _disassemble(code: &RScriptCode, path: &mut Array<usize>, out: &mut dyn Write) -> fmt::Result {
+    let ctx = Ctx {
+        code,
+        path,
+        locals: names(code.local_iter()),
+        captured: names(code.captured_iter()),
+        labels: jump_targets(code),
+    };
+
+    out.write_str("function ")?;
+    write_path(out, path)?;
+    write!(out, " paramets={}", code.paramet_count())?;
+    if code.is_register() {
+        write!(out, " registers={}", code.register_count())?;
+    } else {
+        write!(out, " max_stack={}", code.max_stack())?;
+    }
+    if code.is_variable() {
+        out.write_str(" variable")?;
+    }
+    if code.is_generator() {
+        out.write_str(" generator")?;
+    }
+    out.write_str("\n")?;
+
+    for (title, vars) in [("locals", &ctx.locals), ("captured", &ctx.captured)] {
+        if vars.len() == 0 {
+            continue;
+        }
+        write!(out, "  {}:", title)?;
+        for i in 0..vars.len() {
+            out.write_str(" ")?;
+            Ctx::var(out, vars, i)?;
+        }
+        out.write_str("\n")?;
+    }
+
+    let len = code.opcode().len().max(code.reg_opcode().len());
+    for ip in 0..len {
+        if let Some(n) = ctx.label(ip) {
+            writeln!(out, "L{}:", n)?;
+        }
+        write!(out, "  {:>4}  ", ip)?;
+        if code.is_register() {
+            ctx.reg_opcode(out, ip, code.reg_opcode()[ip])?;
+        } else {
+            ctx.opcode(out, ip, code.opcode()[ip])?;
+        }
+        out.write_str("\n")?;
+    }
+
+    drop(ctx);
+
+    for i in 0..code.children_count() {
+        out.write_str("\n")?;
+        let child = code.get_child(i).unwrap();
+        path.push(i).map_err(|_| fmt::Error)?;
+        _disassemble(&child, path, out)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::{initialize, parse_to_code, set_backend, set_optimize_enabled, Backend};
+    use crate::test_util::{allocator, loader};
+
+    fn disasm(source: &str) -> String {
+        let code = parse_to_code(source, true).unwrap();
+        let mut text = String::new();
+        disassemble(&code, &mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_disassemble() {
+        initialize(allocator(), loader()).unwrap();
+        set_optimize_enabled(true);
+
+        let text = disasm("a = 1; function f(x) { return x + a; } f(2)");
+        let expect = "\
+function <main> paramets=0 max_stack=2
+  locals: a f
+     0  LoadInt 1
+     1  SetLocal a
+     2  GetLocal a
+     3  NewArray 1
+     4  NewClosure <main>.0
+     5  SetLocal f
+     6  GetLocal f
+     7  LoadInt 2
+     8  Call 1
+     9  Return
+
+function <main>.0 paramets=1 max_stack=2
+  locals: x
+  captured: a
+     0  GetLocal x
+     1  GetCapture a
+     2  Add
+     3  Return
+";
+        assert_eq!(text, expect);
+
+        // 跳转目标显示为标签。
+        let text = disasm("i = 0; while (i < 3) i = i + 1; i");
+        let expect = "\
+function <main> paramets=0 max_stack=2
+  locals: i
+     0  LoadInt 0
+     1  SetLocal i
+L0:
+     2  GetLocal i
+     3  LoadInt 3
+     4  Lt
+     5  IfFalse L1
+     6  GetLocal i
+     7  LoadInt 1
+     8  Add
+     9  SetLocal i
+    10  Jmp L0
+L1:
+    11  GetLocal i
+    12  Return
+";
+        assert_eq!(text, expect);
+
+        set_backend(Backend::Register);
+        let text = disasm("function f(x) { return x + 1; } f(2)");
+        set_backend(Backend::Stack);
+        let expect = "\
+function <main> paramets=0 registers=3
+  locals: f
+     0  NewClosure r0(f), <main>.0, r1, 0
+     1  Move r1, r0(f)
+     2  LoadInt r2, 2
+     3  Call r1, 1
+     4  Return r1
+
+function <main>.0 paramets=1 registers=4
+  locals: x
+     0  LoadInt r3, 1
+     1  Add r2, r0(x), r3
+     2  Return r2
+     3  LoadNull r1
+     4  Return r1
+";
+        assert_eq!(text, expect);
+    }
+}
@@ -107,13 +107,23 @@ fn _dyn__new(tp: &Ref<RType>, args: &[RValue]) -> Result<RValue, Error> {
     }
 }
 
+/// [destory] 重载是终结器，由 gc 在析构同一批垃圾中的任何对象之前调用，见 Runtime::_finalize。
+pub(crate) fn _has_finalizer(value: &GcHeader) -> bool {
+    let tp = value.get_type();
+    tp._isdyn && tp._destory_dyn.is_some()
+}
+
+pub(crate) fn _run_finalizer(this: &RValue) -> Result<(), Error> {
+    let func = this.get_type()._destory_dyn.clone();
+    if let Some(func) = func {
+        value_call_with_this(&func, this, &[])?;
+    }
+    Ok(())
+}
+
 fn _dyn__destory(this: &RValue) -> Result<(), Error> {
     let tp = this.get_type();
     if tp._isdyn {
-        if let Some(func) = &tp._destory_dyn {
-            value_call_with_this(func, this, &[])?;
-        }
-
         unsafe {
             let mut v = this.clone().cast::<RDyn>();
             addr_of_mut!(v.as_mut()._attr).drop_in_place();
@@ -0,0 +1,513 @@
+#![allow(non_snake_case)]
+
+use core::mem::size_of;
+use core::ptr::addr_of_mut;
+use core::ptr::NonNull;
+
+use crate::runtime::*;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+use crate::number::*;
+use crate::string::RString;
+use crate::tuple::*;
+use crate::type_::*;
+use crate::value::*;
+
+use crate::builtin::*;
+
+/// 枚举的一个变体，同时也是该变体的构造函数。
+///
+/// `enum Shape { Circle(r), Rect(w, h), Empty }` 中，
+/// `Shape.Circle` 与 `Shape.Rect` 即为该对象，调用时创建对应的枚举值；
+/// 没有字段的变体（如 `Shape.Empty`）在类型上直接存放其唯一的枚举值。
+#[repr(C)]
+pub struct REnumVariant {
+    _header: GcHeader,
+    _enum_type: Ref<RType>,
+    _name: Ref<RString>,
+    _index: usize,
+    /// 字段名，元素均为 RString。
+    _fields: Ref<RTuple>,
+}
+
+impl REnumVariant {
+    unsafe fn init(
+        mut ptr: NonNull<Self>,
+        enum_type: Ref<RType>,
+        name: Ref<RString>,
+        index: usize,
+        fields: Ref<RTuple>,
+    ) {
+        let r = ptr.as_mut();
+        addr_of_mut!(r._enum_type).write(enum_type);
+        addr_of_mut!(r._name).write(name);
+        addr_of_mut!(r._index).write(index);
+        addr_of_mut!(r._fields).write(fields);
+    }
+
+    unsafe fn _drop(&mut self) {
+        addr_of_mut!(self._enum_type).drop_in_place();
+        addr_of_mut!(self._name).drop_in_place();
+        addr_of_mut!(self._fields).drop_in_place();
+    }
+
+    pub(crate) fn new(
+        enum_type: &Ref<RType>,
+        name: &Ref<RString>,
+        index: usize,
+        fields: &Ref<RTuple>,
+    ) -> Result<Ref<Self>, Error> {
+        unsafe {
+            let tp = enum_variant_type().clone();
+            let v = new_gc_obj(size_of::<Self>(), tp)?.cast::<Self>();
+            Self::init(
+                v.as_nonnull_ptr(),
+                enum_type.clone(),
+                name.clone(),
+                index,
+                fields.clone(),
+            );
+            Ok(v)
+        }
+    }
+
+    pub fn enum_type(&self) -> &Ref<RType> {
+        &self._enum_type
+    }
+
+    pub fn name(&self) -> &Ref<RString> {
+        &self._name
+    }
+
+    pub fn index(&self) -> usize {
+        self._index
+    }
+
+    pub fn arity(&self) -> usize {
+        self._fields.len()
+    }
+
+    pub fn field_names(&self) -> &[RValue] {
+        self._fields.as_slice()
+    }
+
+    fn field_index(&self, name: &Ref<RString>) -> Option<usize> {
+        self._fields
+            .as_slice()
+            .iter()
+            .position(|v| Ref::ptr_eq(v, name.cast_value_ref()))
+    }
+}
+
+/// 枚举值，由变体标签与字段构成，其类型为所属的枚举类型。
+#[repr(C)]
+pub struct REnumValue {
+    _header: GcHeader,
+    _variant: Ref<REnumVariant>,
+    _len: usize,
+    _items: [RValue; 1],
+}
+
+impl REnumValue {
+    fn need_size(len: usize) -> usize {
+        size_of::<Self>() + size_of::<RValue>() * len
+    }
+
+    unsafe fn init(mut ptr: NonNull<Self>, variant: Ref<REnumVariant>, fields: &[RValue]) {
+        let r = ptr.as_mut();
+        addr_of_mut!(r._variant).write(variant);
+        addr_of_mut!(r._len).write(fields.len());
+
+        let item_ptr = r._items.as_mut_ptr();
+        for (i, v) in fields.iter().enumerate() {
+            item_ptr.add(i).write(v.clone());
+        }
+    }
+
+    unsafe fn _drop(&mut self) {
+        addr_of_mut!(self._variant).drop_in_place();
+        let item_ptr = self._items.as_mut_ptr();
+        for i in 0..self._len {
+            item_ptr.add(i).drop_in_place();
+        }
+    }
+
+    pub fn new(variant: &Ref<REnumVariant>, fields: &[RValue]) -> Result<Ref<Self>, Error> {
+        if fields.len() != variant.arity() {
+            return Err(runtime_error_fmt!(
+                "{}.{} expects {} arguments, got {}",
+                variant.enum_type().name().as_str(),
+                variant.name().as_str(),
+                variant.arity(),
+                fields.len()
+            ));
+        }
+        unsafe {
+            let tp = variant.enum_type().clone();
+            let v = new_gc_obj(Self::need_size(fields.len()), tp)?.cast::<Self>();
+            Self::init(v.as_nonnull_ptr(), variant.clone(), fields);
+            Ok(v)
+        }
+    }
+
+    pub fn variant(&self) -> &Ref<REnumVariant> {
+        &self._variant
+    }
+
+    pub fn tag(&self) -> &Ref<RString> {
+        self._variant.name()
+    }
+
+    pub fn fields(&self) -> &[RValue] {
+        unsafe { core::slice::from_raw_parts(self._items.as_ptr(), self._len) }
+    }
+
+    pub fn get(&self, index: Int) -> Option<&RValue> {
+        let len = self._len as Int;
+        if index >= -len && index < 0 {
+            self.fields().get((len + index) as usize)
+        } else if index >= 0 && index < len {
+            self.fields().get(index as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// 枚举值的方法名，变体不能使用这些名字，否则会覆盖对应的方法。
+pub(crate) const ENUM_METHODS: [&str; 3] = ["tag", "fields", "is"];
+
+/// 创建一个枚举类型，variants 为 (变体名, 字段名元组)。
+///
+/// 有字段的变体以 REnumVariant 的形式设置为类型属性，调用即可构造枚举值；
+/// 无字段的变体直接把唯一的枚举值设置为类型属性，与 `Option.none` 类似。
+pub fn type_new_enum(
+    type_name: &Ref<RString>,
+    variants: &[(Ref<RString>, Ref<RTuple>)],
+) -> Result<Ref<RType>, Error> {
+    let mut tp = RType::new(type_name.clone())?;
+    tp.set_enum(true);
+
+    tp.with_visit(_enum__visit);
+    tp.with_destory(_enum__destory);
+
+    tp.with_get_attr(_enum__get_attr);
+    tp.with_get_item(_enum__get_item);
+
+    tp.with_eq(_enum__eq);
+    tp.with_hash(_enum__hash);
+    tp.with_str(_enum__to_string);
+
+    tp.with_iter(_enum__iter);
+
+    tp.add_method_str_light("tag", _enum__tag)?;
+    tp.add_method_str_light("fields", _enum__fields)?;
+    tp.add_method_str_light("is", _enum__is)?;
+
+    for (index, (name, fields)) in variants.iter().enumerate() {
+        if ENUM_METHODS.contains(&name.as_str()) {
+            return Err(runtime_error_fmt!(
+                "enum variant can not be named \"{}\"",
+                name.as_str()
+            ));
+        }
+        for f in fields.as_slice() {
+            if !f.is_type(string_type()) {
+                return Err(runtime_error_fmt!("enum field name must be string"));
+            }
+        }
+        let variant = REnumVariant::new(&tp, name, index, fields)?;
+        if variant.arity() == 0 {
+            let v = REnumValue::new(&variant, &[])?;
+            tp.set_attr(name, v.cast_value())?;
+        } else {
+            tp.set_attr(name, variant.cast_value())?;
+        }
+    }
+
+    Ok(tp)
+}
+
+unsafe fn _expect_enum(value: &RValue) -> Result<Ref<REnumValue>, Error> {
+    if value.get_type()._isenum {
+        Ok(value.clone().cast::<REnumValue>())
+    } else {
+        Err(runtime_error_fmt!("{:?} is not an enum value", value))
+    }
+}
+
+fn _enum__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let v = value_ptr.cast::<REnumValue>();
+        let v = v.as_ref();
+        visitor.visit_value(v._variant.cast_value_ref());
+        for f in v.fields() {
+            visitor.visit_value(f);
+        }
+    }
+}
+
+fn _enum__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut v = _expect_enum(value)?;
+        v._drop();
+    }
+    Ok(())
+}
+
+fn _enum__get_attr(value: &RValue, name: &Ref<RString>) -> Result<RValue, Error> {
+    let v = unsafe { _expect_enum(value)? };
+    if let Some(idx) = v.variant().field_index(name) {
+        Ok(v.fields()[idx].clone())
+    } else {
+        let vs = value_str(value)?;
+        Err(runtime_error_fmt!(
+            "{} has no field \"{}\"",
+            vs.as_str(),
+            name.as_str()
+        ))
+    }
+}
+
+fn _enum__get_item(value: &RValue, index: &RValue) -> Result<RValue, Error> {
+    let v = unsafe { _expect_enum(value)? };
+    let index = unsafe { index.expect_cast::<RInt>(int_type())? };
+    if let Some(f) = v.get(index.as_number()) {
+        Ok(f.clone())
+    } else {
+        Err(Error::new_outofrange())
+    }
+}
+
+fn _enum__eq(a: &RValue, b: &RValue) -> Result<bool, Error> {
+    if !b.is_type(a.get_type()) {
+        return Ok(false);
+    }
+    let (av, bv) = unsafe { (_expect_enum(a)?, _expect_enum(b)?) };
+    if !Ref::ptr_eq(av.variant(), bv.variant()) {
+        return Ok(false);
+    }
+    for (a, b) in av.fields().iter().zip(bv.fields().iter()) {
+        if !value_eq(a, b)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn _enum__hash(value: &RValue) -> Result<Int, Error> {
+    let v = unsafe { _expect_enum(value)? };
+    let mut h = v.variant().index() as Int;
+    for f in v.fields() {
+        h = h.wrapping_mul(31).wrapping_add(value_hash(f)?);
+    }
+    Ok(h)
+}
+
+fn _enum__to_string(value: &RValue) -> Result<Ref<RString>, Error> {
+    use crate::collections::Array;
+    use core::fmt::Write;
+
+    fn _e(value: &RValue) -> Error {
+        match default_value_str(value) {
+            Ok(s) => runtime_error_fmt!("{} connot to string", s.as_str()),
+            Err(e) => e,
+        }
+    }
+
+    let v = unsafe { _expect_enum(value)? };
+    let tp_name = value.get_type().name();
+    let mut buf = Array::new(allocator());
+    write!(&mut buf, "{}.{}", tp_name.as_str(), v.tag().as_str()).map_err(|_| _e(value))?;
+
+    if let Some((first, rest)) = v.fields().split_first() {
+        let fs = value_repr(first)?;
+        write!(&mut buf, "({}", fs.as_str()).map_err(|_| _e(value))?;
+        for f in rest {
+            let fs = value_repr(f)?;
+            write!(&mut buf, ", {}", fs.as_str()).map_err(|_| _e(value))?;
+        }
+        write!(&mut buf, ")").map_err(|_| _e(value))?;
+    }
+
+    unsafe { RString::new(buf.as_str_unchecked()) }
+}
+
+fn _enum__iter(value: &RValue) -> Result<RValue, Error> {
+    let v = unsafe { _expect_enum(value)? };
+    let fields = RTuple::from_slice(v.fields())?;
+    let it = RTupleIter::new(&fields)?;
+    Ok(it.cast_value())
+}
+
+fn _enum__tag(this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    let v = unsafe { _expect_enum(this)? };
+    Ok(v.tag().cast_value())
+}
+
+fn _enum__fields(this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    let v = unsafe { _expect_enum(this)? };
+    Ok(RTuple::from_slice(v.fields())?.cast_value())
+}
+
+/// `v.is(Shape.Circle)` 或 `v.is(Shape.Empty)`。
+fn _enum__is(this: &RValue, args: &[RValue]) -> Result<RValue, Error> {
+    use crate::util::expect_arg1;
+
+    let v = unsafe { _expect_enum(this)? };
+    let arg = expect_arg1(args)?;
+    let res = if arg.is_type(enum_variant_type()) {
+        let variant = unsafe { arg.cast_ref::<REnumVariant>() };
+        Ref::ptr_eq(variant, v.variant())
+    } else if arg.get_type()._isenum {
+        let other = unsafe { arg.cast_ref::<REnumValue>() };
+        Ref::ptr_eq(other.variant(), v.variant())
+    } else {
+        false
+    };
+    if res {
+        Ok(true_().cast_value())
+    } else {
+        Ok(false_().cast_value())
+    }
+}
+
+pub(crate) fn _init_type_enum_variant(mut tp: Ref<RType>) -> Result<(), Error> {
+    tp.with_visit(enum_variant__visit);
+    tp.with_destory(enum_variant__destory);
+
+    tp.with_call(enum_variant__call);
+
+    tp.with_eq(default_value_eq);
+    tp.with_hash(default_value_hash);
+    tp.with_str(enum_variant__to_string);
+
+    Ok(())
+}
+
+fn enum_variant__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let v = value_ptr.cast::<REnumVariant>();
+        let v = v.as_ref();
+        visitor.visit_value(v._enum_type.cast_value_ref());
+        visitor.visit_value(v._name.cast_value_ref());
+        visitor.visit_value(v._fields.cast_value_ref());
+    }
+}
+
+fn enum_variant__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut v = value.expect_cast::<REnumVariant>(enum_variant_type())?;
+        v._drop();
+    }
+    Ok(())
+}
+
+fn enum_variant__call(callee: &RValue, _this: &RValue, args: &[RValue]) -> Result<RValue, Error> {
+    let variant = unsafe { callee.expect_cast::<REnumVariant>(enum_variant_type())? };
+    let v = REnumValue::new(&variant, args)?;
+    Ok(v.cast_value())
+}
+
+fn enum_variant__to_string(value: &RValue) -> Result<Ref<RString>, Error> {
+    let variant = unsafe { value.expect_cast::<REnumVariant>(enum_variant_type())? };
+    RString::format(format_args!(
+        "<variant {}.{}>",
+        variant.enum_type().name().as_str(),
+        variant.name().as_str()
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::number::{Int, RBool, RInt};
+    use crate::runtime::{eval, initialize};
+    use crate::test_util::{allocator, loader};
+    use crate::value::{value_hash, value_str};
+
+    const SHAPE: &str = "enum Shape { Circle(r), Rect(w, h), Empty, } ";
+
+    fn eval_bool(script: &str) -> bool {
+        let v = eval(&format!("{}{}", SHAPE, script)).unwrap();
+        unsafe { v.cast_ref::<RBool>().as_bool() }
+    }
+
+    fn eval_int(script: &str) -> Int {
+        let v = eval(&format!("{}{}", SHAPE, script)).unwrap();
+        unsafe { v.cast_ref::<RInt>().as_number() }
+    }
+
+    fn eval_str(script: &str) -> String {
+        let v = eval(&format!("{}{}", SHAPE, script)).unwrap();
+        value_str(&v).unwrap().as_str().to_string()
+    }
+
+    #[test]
+    fn test_enum_construct() {
+        initialize(allocator(), loader()).unwrap();
+
+        assert_eq!(eval_int("Shape::Circle(2).r"), 2);
+        assert_eq!(eval_int("Shape::Rect(3, 4).h"), 4);
+        assert_eq!(eval_int("Shape::Rect(3, 4)[0]"), 3);
+        assert_eq!(
+            eval_int("s = 0; for (x : Shape::Rect(3, 4)) s = s + x; s"),
+            7
+        );
+
+        assert_eq!(eval_str("Shape::Rect(1, 2)"), "Shape.Rect(1, 2)");
+        assert_eq!(eval_str("Shape.Empty"), "Shape.Empty");
+        assert_eq!(eval_str("Shape.Circle"), "<variant Shape.Circle>");
+
+        // 参数个数不匹配或字段不存在时报错。
+        let script = format!("{}Shape::Rect(1)", SHAPE);
+        assert!(eval(&script).is_err());
+        let script = format!("{}Shape::Circle(1).w", SHAPE);
+        assert!(eval(&script).is_err());
+    }
+
+    #[test]
+    fn test_enum_methods() {
+        initialize(allocator(), loader()).unwrap();
+
+        assert!(eval_bool("Shape::Circle(2).tag() == \"Circle\""));
+        assert!(eval_bool("Shape.Empty.tag() == \"Empty\""));
+        assert!(eval_bool("Shape::Rect(3, 4).fields() == (3, 4)"));
+
+        assert!(eval_bool("Shape::Circle(2).is(Shape.Circle)"));
+        assert!(!eval_bool("Shape::Circle(2).is(Shape.Rect)"));
+        assert!(eval_bool("Shape.Empty.is(Shape.Empty)"));
+        assert!(eval_bool("Shape::Rect(1, 2).is(Shape::Rect(3, 4))"));
+        assert!(!eval_bool("Shape.Empty.is(1)"));
+
+        // 变体不能覆盖枚举值的方法。
+        for name in ENUM_METHODS {
+            let script = format!("enum E {{ A, {}(x) }}", name);
+            assert!(matches!(eval(&script), Err(Error::Parse(_))));
+
+            let variants = [(
+                RString::new(name).unwrap(),
+                RTuple::from_slice(&[]).unwrap(),
+            )];
+            let type_name = RString::new("E").unwrap();
+            assert!(type_new_enum(&type_name, &variants).is_err());
+        }
+    }
+
+    #[test]
+    fn test_enum_eq_hash() {
+        initialize(allocator(), loader()).unwrap();
+
+        assert!(eval_bool("Shape::Rect(1, 2) == Shape::Rect(1, 2)"));
+        assert!(!eval_bool("Shape::Rect(1, 2) == Shape::Rect(2, 1)"));
+        assert!(!eval_bool("Shape::Circle(1) == Shape::Rect(1, 1)"));
+        assert!(eval_bool("Shape.Empty == Shape.Empty"));
+        assert!(!eval_bool("Shape.Empty == null"));
+
+        let a = eval(&format!("{}Shape::Rect(1, 2)", SHAPE)).unwrap();
+        let b = eval(&format!("{}Shape::Rect(1, 2)", SHAPE)).unwrap();
+        assert_eq!(value_hash(&a).unwrap(), value_hash(&b).unwrap());
+    }
+}
@@ -31,6 +31,20 @@ pub enum Error {
     OutOfRange,
     Parse(ParseError),
     Runtime(RValue),
+    /// 调用深度超过了 Runtime 允许的最大值，或调用使用的 Rust 栈超过了预算。
+    /// max_depth 为此时允许的最大调用深度。
+    Recursion {
+        max_depth: usize,
+    },
+    /// Runtime 设置的燃料已耗尽且钩子没有补充。
+    /// 脚本中无法捕获错误，它总会沿着调用链返回到宿主。
+    OutOfFuel,
+    /// 脚本被 InterruptHandle 中断。
+    Interrupted,
+    /// 完整回收之后，分配仍会使 gc 堆超过 Runtime 设置的上限。
+    HeapLimit {
+        limit: usize,
+    },
     Type {
         expect: Ref<RType>,
         give: Ref<RType>,
@@ -57,6 +71,22 @@ impl Error {
         Self::Runtime(err)
     }
 
+    pub fn new_recursion(max_depth: usize) -> Self {
+        Self::Recursion { max_depth }
+    }
+
+    pub fn new_outoffuel() -> Self {
+        Self::OutOfFuel
+    }
+
+    pub fn new_interrupted() -> Self {
+        Self::Interrupted
+    }
+
+    pub fn new_heaplimit(limit: usize) -> Self {
+        Self::HeapLimit { limit }
+    }
+
     pub fn new_type(expect: Ref<RType>, give: Ref<RType>) -> Self {
         Self::Type { expect, give }
     }
@@ -75,6 +105,16 @@ impl Debug for Error {
                 pe._pos.column
             )),
             Self::Runtime(re) => f.write_fmt(format_args!("runtime error: {:?}", re)),
+            Self::Recursion { max_depth } => f.write_fmt(format_args!(
+                "recursion error, maximum call depth {} exceeded",
+                max_depth
+            )),
+            Self::OutOfFuel => f.write_str("out of fuel"),
+            Self::Interrupted => f.write_str("interrupted"),
+            Self::HeapLimit { limit } => f.write_fmt(format_args!(
+                "heap limit error, maximum heap size {} bytes exceeded",
+                limit
+            )),
             Self::Type { expect, give } => f.write_fmt(format_args!(
                 "type error, expect \"{:?}\", but give \"{:?}\"",
                 expect.name(),
@@ -7,8 +7,11 @@ use crate::collections::Array;
 use crate::runtime::*;
 
 use crate::array::RArray;
+use crate::generator::RGenerator;
 use crate::number::*;
+use crate::register::eval_register_function;
 use crate::script_code::*;
+use crate::stack::VmStack;
 use crate::string::*;
 use crate::type_::*;
 use crate::value::*;
@@ -220,6 +223,17 @@ impl RFunction {
         }
     }
 
+    pub(crate) fn get_captured(&self) -> Option<Ref<RArray>> {
+        if self._type == FuncType::Script {
+            unsafe {
+                let _ScriptFunc { code: _, captured } = self.as_script();
+                Some(captured.clone())
+            }
+        } else {
+            None
+        }
+    }
+
     pub fn captured_count(&self) -> usize {
         if self._type == FuncType::Script {
             unsafe {
@@ -267,10 +281,7 @@ fn function__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
                 let _ScriptFunc { code, captured } = func.as_script();
 
                 visitor.visit_value(code.cast_value_ref());
-
-                for cap in captured.as_slice() {
-                    visitor.visit_value(cap);
-                }
+                visitor.visit_value(captured.cast_value_ref());
             }
             FuncType::Rust => (),
             FuncType::Native => {
@@ -296,42 +307,340 @@ fn function__call(callee: &RValue, this_value: &RValue, args: &[RValue]) -> Resu
         match func._type {
             FuncType::Rust => func.as_rust()(this_value, args),
             FuncType::Script => {
-                let _ScriptFunc { code, captured } = func.as_script();
-                eval_script_closure(&func, code, captured.as_slice(), this_value, args)
+                let _ScriptFunc { code, captured: _ } = func.as_script();
+                if code.is_register() {
+                    return eval_register_function(&func, this_value, args);
+                }
+                let mut frame = ScriptFrame::new(&func, this_value, args)?;
+                if code.is_generator() {
+                    frame.detach()?;
+                    let generator = RGenerator::new(frame)?;
+                    return Ok(generator.cast_value());
+                }
+                eval_script_closure(frame)
             }
             FuncType::Native => func.as_native_mut().call(this_value, args),
         }
     }
 }
 
-fn eval_script_closure(
-    callee: &Ref<RFunction>,
-    callee_code: &Ref<RScriptCode>,
-    caps: &[RValue],
+/// 脚本函数一次调用所对应的帧。
+///
+/// 执行时帧的参数、局部变量与临时值位于 runtime 值栈上的一段窗口中。
+/// 执行到 Yield 挂起时，窗口中的值被移出到 saved 中，
+/// 恢复时再放回值栈的顶部，之后从挂起处继续执行。
+pub(crate) struct ScriptFrame {
+    callee: Ref<RFunction>,
+    this: RValue,
+    // 帧在值栈上时为窗口的起始位置。
+    base: Option<usize>,
+    saved: Array<RValue>,
+    ip: usize,
+    suspended: bool,
+    coroutine: bool,
+}
+
+/// 帧执行一次后的结果。
+pub(crate) enum FrameState {
+    Return(RValue),
+    Yield(RValue),
+    /// 仅在协程中出现，调用脚本函数时产生的新帧，
+    /// 其返回值会在当前帧恢复时压入栈顶。
+    Call(ScriptFrame),
+}
+
+impl ScriptFrame {
+    pub(crate) fn new(
+        callee: &Ref<RFunction>,
+        this_value: &RValue,
+        args: &[RValue],
+    ) -> Result<Self, Error> {
+        let code = callee
+            .get_code()
+            .ok_or_else(|| runtime_error_fmt!("frame can only be created from script function"))?;
+
+        let stack = runtime().stack_mut();
+        stack.ensure(args.len() + code.local_count() + code.max_stack())?;
+
+        let base = stack.len();
+        // TODO: 收集剩余参数到数组。
+        for v in args {
+            stack.push(v.clone())?;
+        }
+        Self::push_locals(stack, &code)?;
+
+        Ok(Self {
+            callee: callee.clone(),
+            this: this_value.clone(),
+            base: Some(base),
+            saved: Array::new(allocator()),
+            ip: 0,
+            suspended: false,
+            coroutine: false,
+        })
+    }
+
+    fn push_locals(stack: &mut VmStack, code: &Ref<RScriptCode>) -> Result<(), Error> {
+        let local_count = code.local_count();
+        let paramet_count = code.paramet_count() as usize;
+
+        let null_value = null().cast_value();
+        for _ in paramet_count..local_count {
+            stack.push(null_value.clone())?;
+        }
+        Ok(())
+    }
+
+    fn expect_base(&self) -> Result<usize, Error> {
+        self.base
+            .ok_or_else(|| runtime_error_fmt!("frame is not on the stack"))
+    }
+
+    /// 把帧移出值栈，之后该帧可以在值栈之外保存。
+    /// 在该帧之上的值栈中不能有其他帧。
+    pub(crate) fn detach(&mut self) -> Result<(), Error> {
+        if let Some(base) = self.base {
+            let stack = runtime().stack_mut();
+            let values = &stack.as_slice()[base..];
+            self.saved
+                .reserve(values.len())
+                .map_err(|_| Error::OutOfMemory)?;
+            for v in values {
+                self.saved.push(v.clone()).map_err(|_| Error::OutOfMemory)?;
+            }
+            stack.truncate(base);
+            self.base = None;
+        }
+        Ok(())
+    }
+
+    fn attach(&mut self) -> Result<(), Error> {
+        if self.base.is_none() {
+            let code = self.callee.get_code().ok_or_else(|| {
+                runtime_error_fmt!("frame can only be created from script function")
+            })?;
+            let stack = runtime().stack_mut();
+            stack.ensure(self.saved.len() + code.max_stack())?;
+
+            let base = stack.len();
+            for v in self.saved.as_slice() {
+                stack.push(v.clone())?;
+            }
+            while self.saved.pop().is_some() {}
+            self.base = Some(base);
+        }
+        Ok(())
+    }
+
+    /// 帧执行结束，丢弃窗口中的值。
+    fn leave(&mut self) {
+        if let Some(base) = self.base.take() {
+            runtime().stack_mut().truncate(base);
+        }
+    }
+
+    /// 以栈顶的 argc 个值为参数调用 callee，复用当前帧的栈，从头开始执行。
+    fn tail_call(&mut self, callee: Ref<RFunction>, argc: usize) -> Result<(), Error> {
+        let code = callee
+            .get_code()
+            .ok_or_else(|| runtime_error_fmt!("frame can only be created from script function"))?;
+
+        // 把参数移动到窗口底部，丢弃其余的值。
+        let base = self.expect_base()?;
+        let stack = runtime().stack_mut();
+        let args_start = stack.len() - argc;
+        let slice = stack.as_slice_mut();
+        for i in 0..argc {
+            slice.swap(base + i, args_start + i);
+        }
+        stack.truncate(base + argc);
+        stack.ensure(code.local_count() + code.max_stack())?;
+        Self::push_locals(stack, &code)?;
+
+        self.callee = callee;
+        self.this = null().cast_value();
+        self.ip = 0;
+        Ok(())
+    }
+
+    pub(crate) fn callee(&self) -> &Ref<RFunction> {
+        &self.callee
+    }
+
+    /// 在协程中执行的帧调用脚本函数时不会在 Rust 栈上递归，
+    /// 而是把新的帧交给协程，以便 Coroutine::yield 能挂起整个调用链。
+    pub(crate) fn set_coroutine(&mut self, coroutine: bool) {
+        self.coroutine = coroutine;
+    }
+
+    /// 开始或继续执行该帧。
+    /// 若帧在 Yield 处挂起，则 sent 作为该 yield 表达式的值。
+    pub(crate) fn resume(&mut self, sent: RValue) -> Result<FrameState, Error> {
+        self.attach()?;
+        if self.suspended {
+            self.suspended = false;
+            runtime().stack_mut().push(sent)?;
+        }
+
+        let res = self._run();
+        match &res {
+            Ok(FrameState::Yield(_)) | Ok(FrameState::Call(_)) => self.detach()?,
+            _ => self.leave(),
+        }
+        res
+    }
+
+    fn _run(&mut self) -> Result<FrameState, Error> {
+        loop {
+            // 返回 None 表示发生了尾调用，当前帧已被重置。
+            if let Some(state) = run_script_frame(self)? {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// 值栈上的值由值栈持有，这里只访问移出值栈后保存的值。
+    pub(crate) fn visit(&self, visitor: &mut dyn Visitor) {
+        visitor.visit_value(self.callee.cast_value_ref());
+        visitor.visit_value(&self.this);
+        for v in self.saved.as_slice() {
+            visitor.visit_value(v);
+        }
+    }
+}
+
+impl Drop for ScriptFrame {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+fn eval_script_closure(mut frame: ScriptFrame) -> Result<RValue, Error> {
+    match frame.resume(null().cast_value())? {
+        FrameState::Return(v) => Ok(v),
+        FrameState::Yield(_) => Err(runtime_error_fmt!("yield outside of generator")),
+        FrameState::Call(_) => Err(runtime_error_fmt!("invalid frame state")),
+    }
+}
+
+/// 若 value 是以栈指令执行的脚本函数且不是生成器函数，则返回该函数。
+pub(crate) fn as_plain_script_function(value: &RValue) -> Option<Ref<RFunction>> {
+    if !value.is_type(function_type()) {
+        return None;
+    }
+    let func = unsafe { value.cast_ref::<RFunction>() };
+    match func.get_code() {
+        Some(code) if !code.is_generator() && !code.is_register() => Some(func.clone()),
+        _ => None,
+    }
+}
+
+enum CallDispatch {
+    Value(RValue),
+    Frame(ScriptFrame),
+    Yield(RValue),
+}
+
+/// 在协程中，Coroutine::yield 与脚本函数的调用交给协程处理，其余情况直接调用。
+fn dispatch_call(
+    in_coroutine: bool,
+    callee: &RValue,
     this_value: &RValue,
     args: &[RValue],
+) -> Result<CallDispatch, Error> {
+    runtime().safepoint()?;
+    if in_coroutine {
+        if Ref::ptr_eq(callee, coroutine_yield_func().cast_value_ref()) {
+            let v = args.first().cloned().unwrap_or(null().cast_value());
+            return Ok(CallDispatch::Yield(v));
+        }
+        if let Some(func) = as_plain_script_function(callee) {
+            let mut frame = ScriptFrame::new(&func, this_value, args)?;
+            frame.set_coroutine(true);
+            frame.detach()?;
+            return Ok(CallDispatch::Frame(frame));
+        }
+    }
+    let v = value_call_with_this(callee, this_value, args)?;
+    Ok(CallDispatch::Value(v))
+}
+
+#[inline]
+pub(crate) fn get_const_str(code: &Ref<RScriptCode>, index: usize) -> Result<Ref<RString>, Error> {
+    code.get_const_string(index)
+        .ok_or_else(|| runtime_error_fmt!("invalid const string index"))
+}
+
+// 方法只取决于值的类型，以值的类型为键缓存。
+#[inline]
+pub(crate) fn cached_get_method(
+    code: &Ref<RScriptCode>,
+    ip: usize,
+    index: usize,
+    value: &RValue,
+) -> Result<RValue, Error> {
+    let cache = code.inline_cache(ip);
+    if let Some(method) = cache.and_then(|c| c.get(value.get_type())) {
+        return Ok(method);
+    }
+    let name = get_const_str(code, index)?;
+    let method = value_get_method(value, &name)?;
+    if let Some(cache) = cache {
+        cache.set(value.get_type(), &method);
+    }
+    Ok(method)
+}
+
+// 只有类型的属性（如 `Type::name`）可以按类型缓存，
+// 其他值的属性属于各个实例，每次都需要查找。
+// 脚本类型的实例（RDyn）把字段存放在各自的 StringMap 中，同一类型的实例没有统一的布局，
+// 因此实例字段的 GetAttr 不经过缓存，仍然每次进行一次哈希查找。
+#[inline]
+pub(crate) fn cached_get_attr(
+    code: &Ref<RScriptCode>,
+    ip: usize,
+    index: usize,
+    value: &RValue,
 ) -> Result<RValue, Error> {
+    if !value.is_type(type_type()) {
+        let name = get_const_str(code, index)?;
+        return value_get_attr(value, &name);
+    }
+    let tp = unsafe { value.cast_ref::<RType>() };
+    let cache = code.inline_cache(ip);
+    if let Some(attr) = cache.and_then(|c| c.get(tp)) {
+        return Ok(attr);
+    }
+    let name = get_const_str(code, index)?;
+    let attr = value_get_attr(value, &name)?;
+    if let Some(cache) = cache {
+        cache.set(tp, &attr);
+    }
+    Ok(attr)
+}
+
+fn run_script_frame(frame: &mut ScriptFrame) -> Result<Option<FrameState>, Error> {
     use opcode_funcs as opfunc;
     use Opcode::*;
 
     #[inline]
-    fn push(stack: &mut Array<RValue>, v: RValue) -> Result<(), Error> {
-        stack.push(v).map_err(|_| Error::OutOfMemory)
+    fn push(stack: &mut VmStack, v: RValue) -> Result<(), Error> {
+        stack.push(v)
     }
     #[inline]
-    fn pop(stack: &mut Array<RValue>) -> Result<RValue, Error> {
+    fn pop(stack: &mut VmStack) -> Result<RValue, Error> {
         stack
             .pop()
             .ok_or_else(|| runtime_error_fmt!("pop form empty stack"))
     }
     #[inline]
-    fn pop_n(stack: &mut Array<RValue>, n: usize) {
+    fn pop_n(stack: &mut VmStack, n: usize) {
         for _ in 0..n {
             stack.pop();
         }
     }
     #[inline]
-    fn top(stack: &mut Array<RValue>) -> Result<RValue, Error> {
+    fn top(stack: &mut VmStack) -> Result<RValue, Error> {
         if stack.len() != 0 {
             Ok(stack.get(stack.len() - 1).cloned().unwrap())
         } else {
@@ -340,7 +649,7 @@ fn eval_script_closure(
     }
 
     #[inline]
-    fn get_local(stack: &mut Array<RValue>, idx: usize) -> Result<RValue, Error> {
+    fn get_local(stack: &mut VmStack, idx: usize) -> Result<RValue, Error> {
         if let Some(v) = stack.get(idx) {
             Ok(v.clone())
         } else {
@@ -349,15 +658,12 @@ fn eval_script_closure(
     }
 
     #[inline]
-    #[allow(unused_must_use)]
-    fn set_local(stack: &mut Array<RValue>, idx: usize, val: RValue) {
-        if idx < stack.len() {
-            stack.set(idx, val);
-        }
+    fn set_local(stack: &mut VmStack, idx: usize, val: RValue) {
+        stack.set(idx, val);
     }
 
     #[inline]
-    fn lasts<'a>(stack: &'a mut Array<RValue>, n: usize) -> Result<&'a [RValue], Error> {
+    fn lasts<'a>(stack: &'a mut VmStack, n: usize) -> Result<&'a [RValue], Error> {
         if n > stack.len() {
             return Err(runtime_error_fmt!("stack error"));
         }
@@ -365,38 +671,44 @@ fn eval_script_closure(
         Ok(&stack.as_slice()[start..])
     }
 
-    #[inline]
-    fn get_const_str(code: &Ref<RScriptCode>, index: usize) -> Result<Ref<RString>, Error> {
-        code.get_const_string(index)
-            .ok_or_else(|| runtime_error_fmt!("invalid const string index"))
-    }
-
-    let local_count = callee_code.local_count();
-    let paramet_count = callee_code.paramet_count() as usize;
+    let callee = &frame.callee.clone();
+    let (callee_code, captured) = unsafe {
+        let _ScriptFunc { code, captured } = callee.as_script();
+        (code.clone(), captured.clone())
+    };
+    let callee_code = &callee_code;
+    let caps = captured.as_slice();
+    let this_value = &frame.this.clone();
     let ops = callee_code.opcode();
     let ret;
 
-    let local_stack_count = args.len() + local_count;
-
-    let mut stack_ = Array::new(allocator());
-    stack_
-        .reserve(local_stack_count)
-        .map_err(|_| Error::OutOfMemory)?;
-    let stack = &mut stack_;
+    let base = frame.expect_base()?;
+    let stack = runtime().stack_mut();
 
-    // TODO: 收集剩余参数到数组。
-    for v in args {
-        push(stack, v.clone())?;
-    }
+    let mut ip: usize = frame.ip;
+    let mut offset: i32 = 0;
 
-    let null_value = null().cast_value();
-    for _ in paramet_count..local_count {
-        push(stack, null_value.clone())?;
+    let in_coroutine = frame.coroutine;
+
+    // 把调用的结果压入栈顶，或者挂起当前帧，交由协程处理。
+    macro_rules! finish_call {
+        ($dispatch:expr) => {
+            match $dispatch {
+                CallDispatch::Value(ret) => push(stack, ret)?,
+                CallDispatch::Frame(new_frame) => {
+                    frame.ip = ip + 1;
+                    frame.suspended = true;
+                    return Ok(Some(FrameState::Call(new_frame)));
+                }
+                CallDispatch::Yield(v) => {
+                    frame.ip = ip + 1;
+                    frame.suspended = true;
+                    return Ok(Some(FrameState::Yield(v)));
+                }
+            }
+        };
     }
 
-    let mut ip: usize = 0;
-    let mut offset: i32 = 0;
-
     // println!("===== NEW Func =====");
 
     #[allow(unused_variables)]
@@ -473,6 +785,12 @@ fn eval_script_closure(
                 // value_call_with_this( &init_func, &new_type.cast_value(), &[])?;
                 push(stack, new_type.cast_value())?;
             }
+            NewEnum(count) => {
+                let vs = lasts(stack, count as usize * 2 + 1)?;
+                let v = opfunc::new_enum(&vs[0], &vs[1..])?;
+                pop_n(stack, count as usize * 2 + 1);
+                push(stack, v)?;
+            }
             SetOverload(oop) => {
                 let func = pop(stack)?;
                 let target = pop(stack)?;
@@ -508,23 +826,21 @@ fn eval_script_closure(
                 push(stack, value)?;
             }
             GetLocal(idx) => {
-                let v = get_local(stack, idx as usize)?;
+                let v = get_local(stack, base + idx as usize)?;
                 push(stack, v)?;
             }
             SetLocal(idx) => {
                 let v = pop(stack)?;
-                set_local(stack, idx as usize, v);
+                set_local(stack, base + idx as usize, v);
             }
             GetAttr(idx) => {
                 let target = pop(stack)?;
-                let name = get_const_str(callee_code, idx as usize)?;
-                let v = value_get_attr(&target, &name)?;
+                let v = cached_get_attr(callee_code, ip, idx as usize, &target)?;
                 push(stack, v)?;
             }
             GetAttrDup(idx) => {
                 let target = top(stack)?;
-                let name = get_const_str(callee_code, idx as usize)?;
-                let v = value_get_attr(&target, &name)?;
+                let v = cached_get_attr(callee_code, ip, idx as usize, &target)?;
                 push(stack, v)?;
             }
             SetAttr(idx) => {
@@ -715,39 +1031,66 @@ fn eval_script_closure(
             Call(count) => {
                 let l = lasts(stack, count as usize + 1)?;
                 let callee = l[0].clone();
-                let ret = value_call(&callee, &l[1..])?;
+                let null_value = null().cast_value();
+                let ret = dispatch_call(in_coroutine, &callee, &null_value, &l[1..])?;
                 pop_n(stack, count as usize + 1);
-                push(stack, ret)?;
+                finish_call!(ret);
             }
             CallThis(count) => {
                 let l = lasts(stack, count as usize + 2)?;
                 let this_value = l[0].clone();
                 let callee = l[1].clone();
-                let ret = value_call_with_this(&callee, &this_value, &l[2..])?;
+                let ret = dispatch_call(in_coroutine, &callee, &this_value, &l[2..])?;
                 pop_n(stack, count as usize + 2);
-                push(stack, ret)?;
+                finish_call!(ret);
             }
             CallMethod(idx, count) => {
                 let l = lasts(stack, count as usize + 1)?;
                 let this_value = l[0].clone();
-                let name = get_const_str(callee_code, idx as usize)?;
-                let ret = value_call_method(&this_value, &name, &l[1..])?;
+                let method = cached_get_method(callee_code, ip, idx as usize, &this_value)?;
+                let ret = dispatch_call(in_coroutine, &method, &this_value, &l[1..])?;
                 pop_n(stack, count as usize + 1);
-                push(stack, ret)?;
+                finish_call!(ret);
             }
             CallAttr(idx, count) => {
                 let l = lasts(stack, count as usize + 1)?;
                 let this_value = l[0].clone();
-                let name = get_const_str(callee_code, idx as usize)?;
-                let ret = value_call_attr(&this_value, &name, &l[1..])?;
+                let func = cached_get_attr(callee_code, ip, idx as usize, &this_value)?;
+                let ret = dispatch_call(in_coroutine, &func, &this_value, &l[1..])?;
                 pop_n(stack, count as usize + 1);
-                push(stack, ret)?;
+                finish_call!(ret);
+            }
+            TailCall(count) => {
+                let l = lasts(stack, count as usize + 1)?;
+                let callee = l[0].clone();
+                // 协程和生成器中的帧不在 runtime 的调用栈上，不做尾调用。
+                let tail_callee = if in_coroutine || callee_code.is_generator() {
+                    None
+                } else {
+                    as_plain_script_function(&callee)
+                };
+                if let Some(func) = tail_callee {
+                    runtime().safepoint()?;
+                    frame.tail_call(func, count as usize)?;
+                    runtime()._tail_call(&callee);
+                    return Ok(None);
+                }
+                let null_value = null().cast_value();
+                let ret = dispatch_call(in_coroutine, &callee, &null_value, &l[1..])?;
+                pop_n(stack, count as usize + 1);
+                finish_call!(ret);
             }
             Apply(_) => Err(runtime_error_fmt!("\"Apply\" instruction is reserved"))?,
             Return => {
                 ret = pop(stack)?;
                 break;
             }
+            Yield => {
+                let v = pop(stack)?;
+                frame.ip = ip + 1;
+                frame.suspended = true;
+                return Ok(Some(FrameState::Yield(v)));
+            }
             Pop => {
                 pop(stack)?;
             }
@@ -785,11 +1128,76 @@ fn eval_script_closure(
         if new_ip < 0 || new_ip >= ops.len() as isize {
             Err(runtime_error_fmt!("exit without a instruction"))?
         }
+        if new_ip <= ip as isize {
+            runtime().safepoint()?;
+        }
         offset = 0;
         ip = new_ip as usize;
     }
 
     // println!("===== EXIT Func =====");
 
-    Ok(ret)
+    frame.ip = ip;
+    Ok(Some(FrameState::Return(ret)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{allocator, loader};
+
+    fn eval_int(script: &str) -> Int {
+        let v = eval(script).unwrap();
+        unsafe { v.cast_ref::<RInt>().as_number() }
+    }
+
+    #[test]
+    fn test_frame_detach_attach() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 挂起的生成器把窗口移出值栈，恢复时再压回栈顶，
+        // 期间其他帧在值栈上的读写不会影响它的局部变量。
+        let script = "
+            function deep(n) { if (n == 0) return 0; return 1 + deep(n - 1); }
+            function gen(n) {
+                i = 0;
+                while (i < 3) {
+                    yield n * 10 + i + deep(10);
+                    i = i + 1;
+                }
+            }
+            s = 0;
+            for (x : gen(1))
+                for (y : gen(2))
+                    s = s + x * 100 + y + deep(20);
+            s
+        ";
+        let mut expect = 0;
+        for x in 20..23 {
+            for y in 30..33 {
+                expect += x * 100 + y + 20;
+            }
+        }
+        assert_eq!(eval_int(script), expect);
+        assert_eq!(runtime().stack_mut().len(), 0);
+
+        // 协程的帧在 yield 时移出值栈，在另一个 resume 中恢复。
+        let script = "
+            function f(n) {
+                a = n;
+                b = Coroutine::yield(a);
+                return a * 100 + b;
+            }
+            c1 = Coroutine(f);
+            c2 = Coroutine(f);
+            x = c1.resume(1) + c2.resume(2);
+            x * 10000 + c2.resume(4) + c1.resume(3)
+        ";
+        assert_eq!(eval_int(script), 3 * 10000 + 204 + 103);
+        assert_eq!(runtime().stack_mut().len(), 0);
+
+        // 出错时帧也会离开值栈。
+        assert!(eval("function g() { yield 1 + null; } for (x : g()) x;").is_err());
+        assert_eq!(runtime().stack_mut().len(), 0);
+    }
 }
@@ -0,0 +1,55 @@
+use crate::runtime::*;
+
+use crate::error::*;
+
+use crate::function::RFunction;
+use crate::map::RMap;
+use crate::module::RModule;
+use crate::number::{Float, Int, RFloat, RInt};
+use crate::string::RString;
+use crate::value::*;
+
+/// 脚本中的全局模块 gc：
+/// gc::collect() 进行一次完整的回收，返回释放的对象数量；
+/// gc::stats() 以 Map 返回 gc_stats 的各项，gc_time 的单位为秒。
+pub(crate) fn _new_gc_module() -> Result<Ref<RModule>, Error> {
+    let module = RModule::new(RString::new("gc")?, None)?;
+    let m = module.cast_value();
+
+    let collect = RFunction::from_rust_func(gc__collect)?;
+    value_set_attr(&m, &RString::new("collect")?, collect.cast_value_ref())?;
+
+    let stats = RFunction::from_rust_func(gc__stats)?;
+    value_set_attr(&m, &RString::new("stats")?, stats.cast_value_ref())?;
+
+    Ok(module)
+}
+
+#[allow(non_snake_case)]
+fn gc__collect(_this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    let freed = gc_stats().freed_objects;
+    run_gc()?;
+    let freed = gc_stats().freed_objects - freed;
+    Ok(RInt::new(freed as Int)?.cast_value())
+}
+
+#[allow(non_snake_case)]
+fn gc__stats(_this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    let stats = gc_stats();
+    let mut map = RMap::new()?;
+
+    let mut set_int = |name: &str, n: Int| -> Result<(), Error> {
+        map.set(RString::new(name)?.cast_value(), RInt::new(n)?.cast_value())?;
+        Ok(())
+    };
+    set_int("object_count", stats.object_count as Int)?;
+    set_int("heap_size", stats.heap_size as Int)?;
+    set_int("collections", stats.collections as Int)?;
+    set_int("freed_objects", stats.freed_objects as Int)?;
+    set_int("freed_bytes", stats.freed_bytes as Int)?;
+
+    let gc_time = RFloat::new(stats.gc_time.as_secs_f64() as Float)?;
+    map.set(RString::new("gc_time")?.cast_value(), gc_time.cast_value())?;
+
+    Ok(map.cast_value())
+}
@@ -0,0 +1,194 @@
+use core::mem::size_of;
+use core::ptr::addr_of_mut;
+use core::ptr::NonNull;
+
+use crate::runtime::*;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+use crate::function::{FrameState, ScriptFrame};
+use crate::option::ROption;
+use crate::type_::*;
+use crate::value::*;
+
+use crate::builtin::*;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GeneratorState {
+    Suspended,
+    Running,
+    Finished,
+}
+
+/// 调用包含 yield 的函数时返回该对象，
+/// 每次调用 resume 时从上次挂起的位置继续执行，直到下一个 yield 或函数返回。
+#[repr(C)]
+pub struct RGenerator {
+    _header: GcHeader,
+    _frame: ScriptFrame,
+    _state: GeneratorState,
+}
+
+impl RGenerator {
+    unsafe fn init(mut ptr: NonNull<Self>, frame: ScriptFrame) {
+        let r = ptr.as_mut();
+        addr_of_mut!(r._frame).write(frame);
+        addr_of_mut!(r._state).write(GeneratorState::Suspended);
+    }
+
+    unsafe fn _drop(&mut self) {
+        addr_of_mut!(self._frame).drop_in_place();
+    }
+
+    pub(crate) fn new(frame: ScriptFrame) -> Result<Ref<Self>, Error> {
+        unsafe {
+            let tp = generator_type().clone();
+            let v = new_gc_obj(size_of::<Self>(), tp)?.cast::<Self>();
+            Self::init(v.as_nonnull_ptr(), frame);
+            Ok(v)
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self._state == GeneratorState::Finished
+    }
+
+    /// 继续执行到下一个 yield，返回产出的值；函数已返回时返回 None。
+    pub fn resume(&mut self) -> Result<Option<RValue>, Error> {
+        match self._state {
+            GeneratorState::Finished => return Ok(None),
+            GeneratorState::Running => {
+                return Err(runtime_error_fmt!("generator already running"));
+            }
+            GeneratorState::Suspended => (),
+        }
+
+        self._state = GeneratorState::Running;
+        let res = self._frame.resume(null().cast_value());
+        match res {
+            Ok(FrameState::Yield(v)) => {
+                self._state = GeneratorState::Suspended;
+                Ok(Some(v))
+            }
+            Ok(FrameState::Return(_)) => {
+                self._state = GeneratorState::Finished;
+                Ok(None)
+            }
+            Ok(FrameState::Call(_)) => {
+                self._state = GeneratorState::Finished;
+                Err(runtime_error_fmt!("invalid frame state"))
+            }
+            Err(e) => {
+                self._state = GeneratorState::Finished;
+                Err(e)
+            }
+        }
+    }
+}
+
+pub(crate) fn _init_type_generator(mut tp: Ref<RType>) -> Result<(), Error> {
+    tp.with_visit(generator__visit);
+    tp.with_destory(generator__destory);
+
+    tp.with_str(default_value_str);
+    tp.with_hash(default_value_hash);
+    tp.with_eq(default_value_eq);
+
+    tp.with_iter(generator__iter);
+    tp.with_next(generator__next);
+
+    Ok(())
+}
+
+#[allow(non_snake_case)]
+fn generator__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let g = value_ptr.cast::<RGenerator>();
+        g.as_ref()._frame.visit(visitor);
+    }
+}
+
+#[allow(non_snake_case)]
+fn generator__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut g = value.expect_cast::<RGenerator>(generator_type())?;
+        g._drop();
+        Ok(())
+    }
+}
+
+#[allow(non_snake_case)]
+fn generator__iter(value: &RValue) -> Result<RValue, Error> {
+    Ok(value.clone())
+}
+
+#[allow(non_snake_case)]
+fn generator__next(value: &RValue) -> Result<Ref<ROption>, Error> {
+    let mut g = unsafe { value.expect_cast::<RGenerator>(generator_type())? };
+    let nv = g.resume()?;
+    ROption::new(nv)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::number::{Int, RInt};
+    use crate::runtime::{eval, initialize};
+    use crate::test_util::{allocator, loader};
+
+    fn eval_int(script: &str) -> Int {
+        let v = eval(script).unwrap();
+        unsafe { v.cast_ref::<RInt>().as_number() }
+    }
+
+    const RANGE: &str = "
+        function range(n) {
+            i = 0;
+            while (i < n) {
+                yield i;
+                i = i + 1;
+            }
+        }
+    ";
+
+    #[test]
+    fn test_generator() {
+        initialize(allocator(), loader()).unwrap();
+
+        let script = format!("{}s = 0; for (n : range(5)) s = s + n; s", RANGE);
+        assert_eq!(eval_int(&script), 10);
+
+        // 生成器之间互不影响，局部变量在挂起期间保持不变。
+        let script = format!(
+            "{}s = 0; for (a : range(3)) for (b : range(a + 1)) s = s + b; s",
+            RANGE
+        );
+        assert_eq!(eval_int(&script), 4);
+
+        // 空生成器不产出任何值。
+        let script = format!("{}s = 0; for (n : range(0)) s = s + 1; s", RANGE);
+        assert_eq!(eval_int(&script), 0);
+    }
+
+    #[test]
+    fn test_generator_finished() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 迭代完的生成器再次迭代时不再产出值。
+        let script = format!(
+            "{}g = range(3); s = 0; for (n : g) s = s + 1; for (n : g) s = s + 1; s",
+            RANGE
+        );
+        assert_eq!(eval_int(&script), 3);
+
+        // 生成器中的错误在迭代时抛出。
+        let script = "
+            function f() {
+                yield 1;
+                return 1 + null;
+            }
+            for (n : f()) n;
+        ";
+        assert!(eval(script).is_err());
+    }
+}
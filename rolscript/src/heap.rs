@@ -0,0 +1,197 @@
+//! 堆快照：存活的 gc 对象、按类型统计的数量与字节数，以及对象之间的引用。
+//!
+//! 对象以地址作为 id。对象到其类型的引用记录在 `type_id` 中，不作为边；
+//! 边只连接快照中的对象。快照不持有对象的引用，不会影响对象的存活。
+
+use core::fmt::{self, Write};
+use core::ptr::NonNull;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast_dump::write_str as write_json_str;
+use crate::runtime::Visitor;
+use crate::value::*;
+
+/// 快照中的一个对象。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeapObject {
+    pub id: usize,
+    pub type_id: usize,
+    /// 对象占用的字节数。
+    pub size: usize,
+    pub ref_count: usize,
+}
+
+/// 同一类型的对象的统计。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeapTypeStats {
+    pub type_id: usize,
+    pub name: String,
+    pub count: usize,
+    pub size: usize,
+}
+
+/// 见 Runtime::heap_snapshot 与 finalize_with_leak_report。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapSnapshot {
+    pub objects: Vec<HeapObject>,
+    /// 按对象数量从多到少排列。
+    pub types: Vec<HeapTypeStats>,
+    /// (引用者, 被引用者)。
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl HeapSnapshot {
+    pub(crate) fn from_objects(objs: &[NonNull<GcHeader>]) -> Self {
+        let ids: HashSet<usize> = objs.iter().map(|v| v.as_ptr() as usize).collect();
+
+        let mut snapshot = Self::default();
+        let mut type_index: HashMap<usize, usize> = HashMap::new();
+
+        for &value in objs {
+            let header = unsafe { value.as_ref() };
+            let tp = header.get_type();
+            let id = value.as_ptr() as usize;
+            let type_id = tp.as_ptr() as usize;
+            let size = header.block_size();
+
+            snapshot.objects.push(HeapObject {
+                id,
+                type_id,
+                size,
+                ref_count: header.ref_count(),
+            });
+
+            let index = *type_index.entry(type_id).or_insert_with(|| {
+                snapshot.types.push(HeapTypeStats {
+                    type_id,
+                    name: tp.name().as_str().to_string(),
+                    count: 0,
+                    size: 0,
+                });
+                snapshot.types.len() - 1
+            });
+            snapshot.types[index].count += 1;
+            snapshot.types[index].size += size;
+
+            let edges = &mut snapshot.edges;
+            let mut add_edge = |target: NonNull<GcHeader>| {
+                let target = target.as_ptr() as usize;
+                if target != type_id && ids.contains(&target) {
+                    edges.push((id, target));
+                }
+            };
+            value_visit_ptr(&mut add_edge as &mut dyn Visitor, value);
+        }
+
+        snapshot
+            .types
+            .sort_by(|a, b| b.count.cmp(&a.count).then(b.size.cmp(&a.size)));
+        snapshot
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.objects.iter().map(|o| o.size).sum()
+    }
+
+    pub fn type_name(&self, type_id: usize) -> Option<&str> {
+        self.types
+            .iter()
+            .find(|t| t.type_id == type_id)
+            .map(|t| t.name.as_str())
+    }
+
+    /// 以单行 JSON 写出：
+    ///
+    /// ```text
+    /// {"types":[{"id":..,"name":"Array","count":2,"size":160}],
+    ///  "objects":[{"id":..,"type":..,"size":80,"ref_count":1}],
+    ///  "edges":[[from,to]]}
+    /// ```
+    pub fn write_json(&self, out: &mut dyn Write) -> fmt::Result {
+        out.write_str("{\"types\":[")?;
+        for (i, t) in self.types.iter().enumerate() {
+            if i != 0 {
+                out.write_str(",")?;
+            }
+            write!(out, "{{\"id\":{},\"name\":", t.type_id)?;
+            write_json_str(out, &t.name)?;
+            write!(out, ",\"count\":{},\"size\":{}}}", t.count, t.size)?;
+        }
+
+        out.write_str("],\"objects\":[")?;
+        for (i, o) in self.objects.iter().enumerate() {
+            if i != 0 {
+                out.write_str(",")?;
+            }
+            write!(
+                out,
+                "{{\"id\":{},\"type\":{},\"size\":{},\"ref_count\":{}}}",
+                o.id, o.type_id, o.size, o.ref_count
+            )?;
+        }
+
+        out.write_str("],\"edges\":[")?;
+        for (i, (from, to)) in self.edges.iter().enumerate() {
+            if i != 0 {
+                out.write_str(",")?;
+            }
+            write!(out, "[{},{}]", from, to)?;
+        }
+        out.write_str("]}")
+    }
+
+    /// 以 Graphviz 的 DOT 格式写出对象图，节点标签为类型名与字节数。
+    pub fn write_dot(&self, out: &mut dyn Write) -> fmt::Result {
+        out.write_str("digraph heap {\n")?;
+        out.write_str("  node [shape=box];\n")?;
+        for o in &self.objects {
+            let name = self.type_name(o.type_id).unwrap_or("?");
+            write!(out, "  n{:x} [label=\"", o.id)?;
+            write_dot_label(out, name)?;
+            writeln!(out, "\\n{} B, rc {}\"];", o.size, o.ref_count)?;
+        }
+        for (from, to) in &self.edges {
+            writeln!(out, "  n{:x} -> n{:x};", from, to)?;
+        }
+        out.write_str("}\n")
+    }
+
+    /// 写出可读的报告：按类型的统计，之后逐个列出对象。
+    pub fn write_report(&self, out: &mut dyn Write) -> fmt::Result {
+        writeln!(
+            out,
+            "{} object(s), {} byte(s)",
+            self.object_count(),
+            self.total_size()
+        )?;
+        for t in &self.types {
+            writeln!(out, "  {:>8} {:>10} B  {}", t.count, t.size, t.name)?;
+        }
+        for o in &self.objects {
+            let name = self.type_name(o.type_id).unwrap_or("?");
+            writeln!(
+                out,
+                "  <{} at 0x{:x}> size {}, ref_count {}",
+                name, o.id, o.size, o.ref_count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn write_dot_label(out: &mut dyn Write, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            c => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
@@ -227,10 +227,14 @@ impl<'s> Lexical<'s> {
             tk.set_type(TokenType::For);
         } else if tk.source() == "return" {
             tk.set_type(TokenType::Return);
+        } else if tk.source() == "yield" {
+            tk.set_type(TokenType::Yield);
         } else if tk.source() == "function" {
             tk.set_type(TokenType::Function);
         } else if tk.source() == "type" {
             tk.set_type(TokenType::Type);
+        } else if tk.source() == "enum" {
+            tk.set_type(TokenType::Enum);
         } else if tk.source() == "public" {
             tk.set_type(TokenType::Public);
         }
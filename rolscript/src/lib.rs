@@ -4,12 +4,19 @@ mod util;
 
 mod alloc;
 mod runtime;
+mod stack;
 
 mod ast;
+mod ast_dump;
+mod bytecode;
+mod disasm;
 mod lexical;
 mod op;
+mod optimizer;
 mod parser;
+mod register;
 mod token;
+mod verifier;
 
 mod error;
 
@@ -17,20 +24,31 @@ mod type_;
 mod value;
 
 mod array;
+mod coroutine;
 mod dyn_;
+mod enum_;
 mod function;
+mod gc;
+mod generator;
+mod heap;
 mod map;
+mod message;
 mod module;
 mod number;
 mod option;
 mod script_code;
 mod string;
 mod tuple;
+mod weak;
 
 mod builtin;
 
+#[cfg(test)]
+mod test_util;
+
 pub use alloc::default_allocator;
 pub use alloc::Allocator;
+pub use alloc::LimitedAllocator;
 
 pub use lexical::Lexical;
 pub use parser::Parser;
@@ -47,17 +65,27 @@ pub use value::*;
 pub use type_::*;
 
 pub use array::{RArray, RArrayIter};
+pub use enum_::type_new_enum;
+pub use enum_::{REnumValue, REnumVariant};
 pub use map::RMap;
+pub use message::{channel, Message, MessageReceiver, MessageSender};
 pub use module::RModule;
 pub use number::value_to_bool;
 pub use number::{Float, Int};
 pub use number::{RBool, RFloat, RInt, RNull};
 pub use string::RString;
 pub use tuple::RTuple;
+pub use weak::{RWeakMap, RWeakRef};
 
 pub use function::RFunction;
 pub use function::RRustFunction;
+pub use generator::RGenerator;
+
+pub use coroutine::RCoroutine;
 
+pub use bytecode::{is_bytecode, BYTECODE_VERSION};
+pub use disasm::disassemble;
+pub use heap::{HeapObject, HeapSnapshot, HeapTypeStats};
 pub use script_code::RScriptCode;
 
 pub use option::ROption;
@@ -0,0 +1,335 @@
+//! 在 Runtime 之间传递数据。
+//!
+//! Ref 只属于创建它的 Runtime，不能跨线程传递。Message 是与 Runtime 无关的
+//! 普通 Rust 数据，由 Message::from_value 从当前 Runtime 的值深拷贝得到，
+//! 再由 Message::to_value 在另一个 Runtime 中重新创建。
+//! 只支持 Null、Bool、Int、Float、String，以及由它们组成的 Tuple、Array 与 Map；
+//! 共享的子结构会被复制为多份，含有循环引用的值无法转换。
+//! 复制后的节点数超过 MAX_MESSAGE_NODES 时转换失败，避免层层共享的值展开成
+//! 指数级大小；两个方向的转换都是递归的，嵌套超过 MAX_MESSAGE_DEPTH 层时也会失败。
+
+use std::collections::HashSet;
+use std::sync::mpsc;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+use crate::array::RArray;
+use crate::map::RMap;
+use crate::number::*;
+use crate::string::RString;
+use crate::tuple::RTuple;
+use crate::value::*;
+
+use crate::builtin::*;
+
+/// Message::from_value 最多创建的节点数，每个值（包括容器中的元素）计为一个节点。
+pub const MAX_MESSAGE_NODES: usize = 1 << 20;
+
+/// Message::from_value 与 Message::to_value 允许的最大容器嵌套层数。
+pub const MAX_MESSAGE_DEPTH: usize = 256;
+
+/// 可以在线程之间传递的值。
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Null,
+    Bool(bool),
+    Int(Int),
+    Float(Float),
+    String(String),
+    Tuple(Vec<Message>),
+    Array(Vec<Message>),
+    /// 按原 Map 的遍历顺序排列的键值对。
+    Map(Vec<(Message, Message)>),
+}
+
+impl Message {
+    /// 深拷贝当前 Runtime 中的 value。
+    pub fn from_value(value: &RValue) -> Result<Self, Error> {
+        let mut path = HashSet::new();
+        let mut nodes = 0;
+        Self::_from_value(value, &mut path, &mut nodes)
+    }
+
+    // path 为正在转换的容器，用于发现循环引用，其大小即嵌套层数；nodes 为已创建的节点数。
+    fn _from_value(
+        value: &RValue,
+        path: &mut HashSet<usize>,
+        nodes: &mut usize,
+    ) -> Result<Self, Error> {
+        *nodes += 1;
+        if *nodes > MAX_MESSAGE_NODES {
+            return Err(runtime_error_fmt!(
+                "value with more than {} nodes can not be sent to another runtime",
+                MAX_MESSAGE_NODES
+            ));
+        }
+
+        let tp = value.get_type();
+        unsafe {
+            if Ref::ptr_eq(tp, null_type()) {
+                return Ok(Message::Null);
+            } else if Ref::ptr_eq(tp, bool_type()) {
+                return Ok(Message::Bool(value.cast_ref::<RBool>().as_bool()));
+            } else if Ref::ptr_eq(tp, int_type()) {
+                return Ok(Message::Int(value.cast_ref::<RInt>().as_number()));
+            } else if Ref::ptr_eq(tp, float_type()) {
+                return Ok(Message::Float(value.cast_ref::<RFloat>().as_number()));
+            } else if Ref::ptr_eq(tp, string_type()) {
+                let s = value.cast_ref::<RString>().as_str();
+                return Ok(Message::String(s.to_string()));
+            }
+        }
+
+        let is_container = Ref::ptr_eq(tp, tuple_type())
+            || Ref::ptr_eq(tp, array_type())
+            || Ref::ptr_eq(tp, map_type());
+        if !is_container {
+            return Err(runtime_error_fmt!(
+                "\"{}\" can not be sent to another runtime",
+                tp.name().as_str()
+            ));
+        }
+
+        if path.len() >= MAX_MESSAGE_DEPTH {
+            return Err(Self::_too_deep());
+        }
+        let id = value.as_ptr() as usize;
+        if !path.insert(id) {
+            return Err(runtime_error_fmt!(
+                "cyclic \"{}\" can not be sent to another runtime",
+                tp.name().as_str()
+            ));
+        }
+
+        let mut items = |items: &[RValue]| -> Result<Vec<Self>, Error> {
+            items
+                .iter()
+                .map(|v| Self::_from_value(v, path, nodes))
+                .collect()
+        };
+        let msg = unsafe {
+            if Ref::ptr_eq(tp, tuple_type()) {
+                Message::Tuple(items(value.cast_ref::<RTuple>().as_slice())?)
+            } else if Ref::ptr_eq(tp, array_type()) {
+                Message::Array(items(value.cast_ref::<RArray>().as_slice())?)
+            } else {
+                let mut entries = Vec::new();
+                for (k, v) in value.cast_ref::<RMap>().iter() {
+                    let k = Self::_from_value(k, path, nodes)?;
+                    entries.push((k, Self::_from_value(v, path, nodes)?));
+                }
+                Message::Map(entries)
+            }
+        };
+
+        path.remove(&id);
+        Ok(msg)
+    }
+
+    fn _too_deep() -> Error {
+        runtime_error_fmt!(
+            "value nested deeper than {} levels can not be passed between runtimes",
+            MAX_MESSAGE_DEPTH
+        )
+    }
+
+    /// 在当前 Runtime 中创建对应的值。
+    pub fn to_value(&self) -> Result<RValue, Error> {
+        self._to_value(0)
+    }
+
+    // depth 为外层容器的个数。
+    fn _to_value(&self, depth: usize) -> Result<RValue, Error> {
+        let is_container = matches!(
+            self,
+            Message::Tuple(_) | Message::Array(_) | Message::Map(_)
+        );
+        if is_container && depth >= MAX_MESSAGE_DEPTH {
+            return Err(Self::_too_deep());
+        }
+        let value = match self {
+            Message::Null => null().cast_value(),
+            Message::Bool(b) => RBool::new(*b)?.cast_value(),
+            Message::Int(n) => RInt::new(*n)?.cast_value(),
+            Message::Float(n) => RFloat::new(*n)?.cast_value(),
+            Message::String(s) => RString::new(s)?.cast_value(),
+            Message::Tuple(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| item._to_value(depth + 1))
+                    .collect::<Result<Vec<_>, _>>()?;
+                RTuple::from_slice(&items)?.cast_value()
+            }
+            Message::Array(items) => {
+                let mut array = RArray::new()?;
+                for item in items {
+                    array.push(item._to_value(depth + 1)?)?;
+                }
+                array.cast_value()
+            }
+            Message::Map(entries) => {
+                let mut map = RMap::new()?;
+                for (k, v) in entries {
+                    map.set(k._to_value(depth + 1)?, v._to_value(depth + 1)?)?;
+                }
+                map.cast_value()
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// 创建一对用于在 Runtime 之间传递值的发送端与接收端。
+/// 发送端可以复制并移动到其它线程，每个线程使用自己的 Runtime 发送与接收。
+pub fn channel() -> (MessageSender, MessageReceiver) {
+    let (sender, receiver) = mpsc::channel();
+    (
+        MessageSender { _sender: sender },
+        MessageReceiver {
+            _receiver: receiver,
+        },
+    )
+}
+
+#[derive(Clone)]
+pub struct MessageSender {
+    _sender: mpsc::Sender<Message>,
+}
+
+impl MessageSender {
+    /// 深拷贝当前 Runtime 中的 value 并发送。接收端已被销毁时返回错误。
+    pub fn send(&self, value: &RValue) -> Result<(), Error> {
+        self.send_message(Message::from_value(value)?)
+    }
+
+    pub fn send_message(&self, msg: Message) -> Result<(), Error> {
+        self._sender
+            .send(msg)
+            .map_err(|_| runtime_error_fmt!("in send, the receiver has been dropped"))
+    }
+}
+
+pub struct MessageReceiver {
+    _receiver: mpsc::Receiver<Message>,
+}
+
+impl MessageReceiver {
+    /// 等待下一个值并在当前 Runtime 中创建它。全部发送端都已被销毁时返回错误。
+    pub fn recv(&self) -> Result<RValue, Error> {
+        self.recv_message()?.to_value()
+    }
+
+    /// 没有等待中的值时立即返回 None。
+    pub fn try_recv(&self) -> Result<Option<RValue>, Error> {
+        match self._receiver.try_recv() {
+            Ok(msg) => msg.to_value().map(Some),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(Self::_disconnected()),
+        }
+    }
+
+    pub fn recv_message(&self) -> Result<Message, Error> {
+        self._receiver.recv().map_err(|_| Self::_disconnected())
+    }
+
+    fn _disconnected() -> Error {
+        runtime_error_fmt!("in recv, all senders have been dropped")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::{eval, initialize, Runtime};
+    use crate::test_util::{allocator, loader};
+
+    #[test]
+    fn test_message() {
+        let (sender, receiver) = channel();
+        let (reply, replies) = channel();
+
+        let worker = std::thread::spawn(move || {
+            let mut rt = Runtime::new(allocator(), loader()).unwrap();
+            rt.with(|| {
+                let value =
+                    eval(r#"{"n": 1, "items": [1.5, true, null, ("a", 1152921504606846976)]}"#);
+                sender.send(&value.unwrap()).unwrap();
+
+                let cyclic = eval("a = [1]; a[0] = a; a").unwrap();
+                assert!(sender.send(&cyclic).is_err());
+                let tp = eval("Int").unwrap();
+                assert!(sender.send(&tp).is_err());
+
+                // 同一个数组出现两次不是循环引用。
+                let shared = eval("a = [1]; (a, a)").unwrap();
+                sender.send(&shared).unwrap();
+
+                // 每层共享上一层，展开后超过 2^40 个节点。
+                let nested =
+                    eval("a = [1]; i = 0; while (i < 40) { a = [a, a]; i = i + 1; } a").unwrap();
+                assert!(sender.send(&nested).is_err());
+
+                let msg = replies.recv_message().unwrap();
+                assert_eq!(msg, Message::String("done".to_string()));
+            });
+        });
+
+        let mut rt = Runtime::new(allocator(), loader()).unwrap();
+        rt.with(|| {
+            let value = receiver.recv().unwrap();
+            let msg = Message::from_value(&value).unwrap();
+            let expected = Message::Map(vec![
+                (Message::String("n".to_string()), Message::Int(1)),
+                (
+                    Message::String("items".to_string()),
+                    Message::Array(vec![
+                        Message::Float(1.5),
+                        Message::Bool(true),
+                        Message::Null,
+                        Message::Tuple(vec![
+                            Message::String("a".to_string()),
+                            Message::Int(1 << 60),
+                        ]),
+                    ]),
+                ),
+            ]);
+            assert_eq!(msg, expected);
+
+            let shared = Message::from_value(&receiver.recv().unwrap()).unwrap();
+            let a = Message::Array(vec![Message::Int(1)]);
+            assert_eq!(shared, Message::Tuple(vec![a.clone(), a]));
+
+            let done = RString::new("done").unwrap().cast_value();
+            reply.send(&done).unwrap();
+        });
+
+        worker.join().unwrap();
+        assert!(rt.with(|| receiver.recv()).is_err());
+    }
+
+    #[test]
+    fn test_message_depth() {
+        initialize(allocator(), loader()).unwrap();
+        let nested = |depth| {
+            let script = format!(
+                "a = []; i = 1; while (i < {}) {{ a = [a]; i = i + 1; }} a",
+                depth
+            );
+            eval(&script).unwrap()
+        };
+
+        let msg = Message::from_value(&nested(MAX_MESSAGE_DEPTH)).unwrap();
+        assert!(msg.to_value().is_ok());
+
+        // 过深的值返回错误，而不是耗尽 Rust 栈。
+        assert!(Message::from_value(&nested(MAX_MESSAGE_DEPTH + 1)).is_err());
+        assert!(Message::from_value(&nested(200000)).is_err());
+
+        let mut msg = Message::Null;
+        for _ in 0..=MAX_MESSAGE_DEPTH {
+            msg = Message::Array(vec![msg]);
+        }
+        assert!(msg.to_value().is_err());
+    }
+}
@@ -24,15 +24,15 @@ pub type Float = f32;
 #[cfg(target_pointer_width = "64")]
 pub type Float = f64;
 
-#[repr(C)]
+// Null、Bool 和 Float 总是直接存放在 Ref 中，不会分配内存，
+// 只有超出 48 位的 Int 才会分配 RInt 对象，见 value::immediate。
+
 pub struct RNull {
-    _header: GcHeader,
+    _private: (),
 }
 
-#[repr(C)]
 pub struct RBool {
-    _header: GcHeader,
-    _bool: bool,
+    _private: (),
 }
 
 #[repr(C)]
@@ -41,10 +41,8 @@ pub struct RInt {
     _number: Int,
 }
 
-#[repr(C)]
 pub struct RFloat {
-    _header: GcHeader,
-    _number: Float,
+    _private: (),
 }
 
 impl RBool {
@@ -56,60 +54,59 @@ impl RBool {
             Ok(false_().clone())
         }
     }
+}
 
+impl Ref<RBool> {
     #[inline]
     pub fn as_bool(&self) -> bool {
-        self._bool
+        self.word() == immediate::TRUE
     }
 }
 
 impl RInt {
     #[inline]
     pub fn new(n: Int) -> Result<Ref<RInt>, Error> {
-        runtime().integer_pool_get(n)
+        match immediate::from_int(n) {
+            Some(word) => unsafe { Ok(Ref::from_word(word)) },
+            None => _new_int_value(n),
+        }
     }
+}
 
+impl Ref<RInt> {
     #[inline]
     pub fn as_number(&self) -> Int {
-        self._number
+        if self.is_immediate() {
+            immediate::to_int(self.word())
+        } else {
+            self.as_ref()._number
+        }
     }
 }
 
 impl RFloat {
     #[inline]
     pub fn new(n: Float) -> Result<Ref<RFloat>, Error> {
-        let tp = float_type().clone();
-        unsafe {
-            let mut v = new_gc_obj(size_of::<Self>(), tp)?.cast::<Self>();
-            v._number = n;
-            Ok(v)
-        }
+        unsafe { Ok(Ref::from_word(immediate::from_float(n))) }
     }
+}
 
+impl Ref<RFloat> {
     #[inline]
     pub fn as_number(&self) -> Float {
-        self._number
+        immediate::to_float(self.word())
     }
 }
 
 pub(crate) fn _new_null_value() -> Result<Ref<RNull>, Error> {
-    unsafe {
-        let tp = null_type().clone();
-        let v = new_gc_obj(size_of::<RNull>(), tp)?.cast::<RNull>();
-        Ok(v)
-    }
+    unsafe { Ok(Ref::from_word(immediate::NULL)) }
 }
 
 pub(crate) fn _new_bool_value(b: bool) -> Result<Ref<RBool>, Error> {
-    unsafe {
-        let tp = bool_type().clone();
-        let mut v = new_gc_obj(size_of::<RBool>(), tp)?.cast::<RBool>();
-        v._bool = b;
-        Ok(v)
-    }
+    unsafe { Ok(Ref::from_word(immediate::from_bool(b))) }
 }
 
-pub(crate) fn _new_int_value(n: Int) -> Result<Ref<RInt>, Error> {
+fn _new_int_value(n: Int) -> Result<Ref<RInt>, Error> {
     unsafe {
         let tp = int_type().clone();
         let mut v = new_gc_obj(size_of::<RInt>(), tp)?.cast::<RInt>();
@@ -118,15 +115,6 @@ pub(crate) fn _new_int_value(n: Int) -> Result<Ref<RInt>, Error> {
     }
 }
 
-pub(crate) fn _new_float_value(n: Float) -> Result<Ref<RFloat>, Error> {
-    unsafe {
-        let tp = float_type().clone();
-        let mut v = new_gc_obj(size_of::<RFloat>(), tp)?.cast::<RFloat>();
-        v._number = n;
-        Ok(v)
-    }
-}
-
 pub(crate) fn _init_type_null(mut tp: Ref<RType>) -> Result<(), Error> {
     tp.with_eq(default_value_eq);
     tp.with_hash(|_| Ok(0));
@@ -204,6 +192,15 @@ fn unsupported_operand_error(op_name: &str, a: &RValue, b: &RValue) -> Error {
     )
 }
 
+// 除数为 0，或 Int::MIN 除以 -1 溢出。
+fn int_division_error(op_name: &str, a: Int, b: Int) -> Error {
+    if b == 0 {
+        runtime_error_fmt!("integer division by zero: {} {} {}", a, op_name, b)
+    } else {
+        runtime_error_fmt!("integer overflow: {} {} {}", a, op_name, b)
+    }
+}
+
 fn null__to_string(_: &RValue) -> Result<Ref<RString>, Error> {
     RString::new("null")
 }
@@ -359,7 +356,9 @@ fn int__idiv(instance: &RValue, right: &RValue) -> Result<RValue, Error> {
     let l = unsafe { instance.expect_cast::<RInt>(int_type())?.as_number() };
     if right.is_type(int_type()) {
         let r = unsafe { right.cast_ref::<RInt>().as_number() };
-        let res = l.div_euclid(r);
+        let res = l
+            .checked_div_euclid(r)
+            .ok_or_else(|| int_division_error("//", l, r))?;
         Ok(RInt::new(res)?.cast_value())
     } else if right.is_type(float_type()) {
         let r = unsafe { right.cast_ref::<RFloat>().as_number() };
@@ -374,7 +373,9 @@ fn int__mod(instance: &RValue, right: &RValue) -> Result<RValue, Error> {
     let l = unsafe { instance.expect_cast::<RInt>(int_type())?.as_number() };
     if right.is_type(int_type()) {
         let r = unsafe { right.cast_ref::<RInt>().as_number() };
-        let res = l.rem_euclid(r);
+        let res = l
+            .checked_rem_euclid(r)
+            .ok_or_else(|| int_division_error("%", l, r))?;
         Ok(RInt::new(res)?.cast_value())
     } else if right.is_type(float_type()) {
         let r = unsafe { right.cast_ref::<RFloat>().as_number() };
@@ -390,7 +391,15 @@ fn int__pow(instance: &RValue, right: &RValue) -> Result<RValue, Error> {
     if right.is_type(int_type()) {
         let r = unsafe { right.cast_ref::<RInt>().as_number() };
         if r >= 0 {
-            let res = l.pow(r as u32);
+            let exp = match u32::try_from(r) {
+                Ok(exp) => Some(exp),
+                // 底数为 0、1 或 -1 时结果只与指数的奇偶有关。
+                Err(_) if (-1..=1).contains(&l) => Some(2 + (r % 2) as u32),
+                Err(_) => None,
+            };
+            let res = exp
+                .and_then(|exp| l.checked_pow(exp))
+                .ok_or_else(|| runtime_error_fmt!("integer overflow: {} ** {}", l, r))?;
             Ok(RInt::new(res)?.cast_value())
         } else {
             let res = (l as Float).powi(r as i32);
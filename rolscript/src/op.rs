@@ -107,6 +107,8 @@ pub enum Opcode {
     NewMap(u32),
     NewClosure(u32),
     NewType,
+    /// 栈上依次为：类型名，(变体名, 字段名元组) * n。
+    NewEnum(u32),
     SetOverload(u8), // (overload_op)
     GetCapture(u32),
     SetCapture(u32),
@@ -156,14 +158,78 @@ pub enum Opcode {
     CallThis(u32),
     CallMethod(u16, u16), // (nmae index, arg count)
     CallAttr(u16, u16),
+    /// 用于 `return f(...)`，后面紧跟一个 Return。
+    /// 若被调用者是脚本函数，则复用当前帧执行，不会返回到该指令之后；
+    /// 否则与 Call 相同。
+    TailCall(u32),
     Apply(u32),
     Return,
+    /// 弹出栈顶值并挂起当前帧，把该值交给恢复者。
+    /// 恢复时传入的值会被压入栈顶，作为 yield 表达式的值。
+    Yield,
     Pop,
     Dup,
     Rot,
     Rot3,
     Rot4,
 }
+
+impl Opcode {
+    /// 指令执行时弹出与压入的值的个数 (pop, push)。
+    /// IterNext 跳转时只弹出不压入，这里给出的是不跳转时的情况。
+    pub(crate) fn stack_effect(&self) -> (usize, usize) {
+        use Opcode::*;
+        match *self {
+            Nop | Jmp(_) | JmpLabel(_) => (0, 0),
+            LoadNull | LoadTrue | LoadFalse | LoadInt(_) | LoadConstStr(_) | LoadConstNum(_)
+            | LoadThis | GetCapture(_) | GetLocal(_) | GetGlobal(_) => (0, 1),
+            NewTuple(n) | NewArray(n) => (n as usize, 1),
+            NewMap(n) => (n as usize * 2, 1),
+            NewClosure(_) | NewType => (1, 1),
+            NewEnum(n) => (n as usize * 2 + 1, 1),
+            SetOverload(_) | SetCapture(_) => (2, 0),
+            SetLocal(_) => (1, 0),
+            GetAttr(_) => (1, 1),
+            GetAttrDup(_) => (1, 2),
+            SetAttr(_) => (2, 0),
+            GetItem => (2, 1),
+            SetItem => (3, 0),
+            Add | Sub | Mul | Div | IDiv | Mod | Pow | And | Or | BitAnd | BitOr | BitXor | Shl
+            | Shr | Cmp | Eq | Ne | Lt | Le | Gt | Ge => (2, 1),
+            Not | BitNot | Iter => (1, 1),
+            IfFalse(_) | IfFalseLabel(_) => (1, 0),
+            IterNext(_) | IterNextLabel(_) => (1, 1),
+            Call(n) | TailCall(n) | Apply(n) => (n as usize + 1, 1),
+            CallThis(n) => (n as usize + 2, 1),
+            CallMethod(_, n) | CallAttr(_, n) => (n as usize + 1, 1),
+            Return => (1, 0),
+            Yield => (1, 1),
+            Pop => (1, 0),
+            Dup => (1, 2),
+            Rot => (2, 2),
+            Rot3 => (3, 3),
+            Rot4 => (4, 4),
+        }
+    }
+
+    /// 位于 ip、执行前栈深度为 depth 的指令执行后可能到达的位置及到达时的栈深度。
+    /// 不检查跳转目标是否越界，跳到开头之前时会回绕为很大的值。
+    pub(crate) fn successors(&self, ip: usize, depth: usize) -> [Option<(usize, usize)>; 2] {
+        use Opcode::*;
+        let (pop, push) = self.stack_effect();
+        let popped = depth.saturating_sub(pop);
+        let after = popped + push;
+        let target = |off: i32| (ip as isize + off as isize) as usize;
+        match *self {
+            Return => [None, None],
+            Jmp(off) => [Some((target(off), after)), None],
+            IfFalse(off) => [Some((ip + 1, after)), Some((target(off), after))],
+            IterNext(off) => [Some((ip + 1, after)), Some((target(off), popped))],
+            _ => [Some((ip + 1, after)), None],
+        }
+    }
+}
+
 pub mod opcode_funcs {
     use crate::op::*;
 
@@ -242,6 +308,30 @@ pub mod opcode_funcs {
         }
     }
 
+    #[inline]
+    pub(crate) fn new_enum(name: &RValue, variants: &[RValue]) -> Result<RValue, Error> {
+        use crate::collections::Array;
+        use crate::enum_::type_new_enum;
+        use crate::runtime::allocator;
+        use crate::string::RString;
+
+        let name = unsafe { name.expect_cast::<RString>(string_type())? };
+
+        let mut vs = Array::new(allocator());
+        for pair in variants.chunks(2) {
+            if let [vname, fields] = pair {
+                let vname = unsafe { vname.expect_cast::<RString>(string_type())? };
+                let fields = unsafe { fields.expect_cast::<RTuple>(tuple_type())? };
+                vs.push((vname, fields)).map_err(|_| Error::OutOfMemory)?;
+            } else {
+                return Err(runtime_error_fmt!("invalid enum variant"));
+            }
+        }
+
+        let tp = type_new_enum(&name, vs.as_slice())?;
+        Ok(tp.cast_value())
+    }
+
     #[inline]
     pub(crate) fn set_overload(
         oop: OverloadOp,
@@ -299,7 +389,7 @@ pub mod opcode_funcs {
         if b {
             Ok(true_().cast_value())
         } else {
-            Ok(true_().cast_value())
+            Ok(false_().cast_value())
         }
     }
     #[inline]
@@ -348,3 +438,42 @@ pub mod opcode_funcs {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::number::{Int, RBool, RInt};
+    use crate::runtime::{eval, initialize};
+    use crate::test_util::{allocator, loader};
+
+    fn eval_bool(script: &str) -> bool {
+        let v = eval(script).unwrap();
+        unsafe { v.cast_ref::<RBool>().as_bool() }
+    }
+
+    fn eval_int(script: &str) -> Int {
+        let v = eval(script).unwrap();
+        unsafe { v.cast_ref::<RInt>().as_number() }
+    }
+
+    #[test]
+    fn test_eq() {
+        initialize(allocator(), loader()).unwrap();
+
+        assert!(eval_bool("1 == 1"));
+        assert!(!eval_bool("1 == 2"));
+        assert!(!eval_bool("\"a\" == \"b\""));
+        assert!(!eval_bool("1 != 1"));
+        assert!(eval_bool("1 != 2"));
+    }
+
+    #[test]
+    fn test_unary() {
+        initialize(allocator(), loader()).unwrap();
+
+        assert!(!eval_bool("!true"));
+        assert!(eval_bool("!false"));
+        assert!(eval_bool("!(1 == 2)"));
+        assert_eq!(eval_int("~5"), -6);
+        assert_eq!(eval_int("~~5"), 5);
+    }
+}
@@ -0,0 +1,437 @@
+use crate::collections::Array;
+
+use crate::runtime::*;
+
+use crate::error::*;
+
+use crate::number::*;
+use crate::script_code::RScriptCode;
+use crate::value::*;
+
+use crate::op::opcode_funcs as opfunc;
+use crate::op::*;
+
+use crate::builtin::*;
+
+/// 对生成的字节码进行优化：
+/// - 折叠操作数均为内置类型常量的算术、比较运算。
+/// - 删除 Return 和 Jmp 之后不可达的指令。
+/// - 跳转到 Jmp 的跳转直接跳转到最终位置。
+/// - 删除 `LoadX; Pop`，把 `Dup; SetLocal; Pop` 合并为 `SetLocal`。
+///
+/// 优化过程中跳转指令的参数为绝对位置，被删除的指令先替换为 Nop，最后统一移除。
+pub(crate) fn optimize(code: &mut Ref<RScriptCode>) -> Result<(), Error> {
+    let mut ops = Array::new(allocator());
+    ops.append_slice(code.opcode())
+        .map_err(|_| Error::OutOfMemory)?;
+
+    to_absolute(ops.as_slice_mut());
+
+    let mut targets = Array::new(allocator());
+    loop {
+        jump_targets(ops.as_slice(), &mut targets)?;
+        let mut changed = fold_constant(code, ops.as_slice_mut(), targets.as_slice())?;
+        changed |= remove_unreachable(ops.as_slice_mut(), targets.as_slice());
+        changed |= thread_jumps(ops.as_slice_mut());
+        changed |= peephole(ops.as_slice_mut(), targets.as_slice());
+        if !changed {
+            break;
+        }
+    }
+
+    let code_ops = code.opcode_mut();
+    code_ops
+        .resize(0, Opcode::Nop)
+        .map_err(|_| Error::OutOfMemory)?;
+    compact(ops.as_slice(), code_ops)
+}
+
+fn jump_target(op: Opcode) -> Option<usize> {
+    match op {
+        Opcode::IfFalse(t) | Opcode::Jmp(t) | Opcode::IterNext(t) => Some(t as usize),
+        _ => None,
+    }
+}
+
+fn with_jump_target(op: Opcode, target: i32) -> Opcode {
+    match op {
+        Opcode::IfFalse(_) => Opcode::IfFalse(target),
+        Opcode::Jmp(_) => Opcode::Jmp(target),
+        Opcode::IterNext(_) => Opcode::IterNext(target),
+        op => op,
+    }
+}
+
+fn to_absolute(ops: &mut [Opcode]) {
+    for (i, op) in ops.iter_mut().enumerate() {
+        if let Opcode::IfFalse(t) | Opcode::Jmp(t) | Opcode::IterNext(t) = *op {
+            *op = with_jump_target(*op, i as i32 + t);
+        }
+    }
+}
+
+fn jump_targets(ops: &[Opcode], targets: &mut Array<bool>) -> Result<(), Error> {
+    targets
+        .resize(0, false)
+        .and_then(|_| targets.resize(ops.len() + 1, false))
+        .map_err(|_| Error::OutOfMemory)?;
+    let targets = targets.as_slice_mut();
+    for op in ops {
+        if let Some(t) = jump_target(*op) {
+            if t < targets.len() {
+                targets[t] = true;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn prev_op(ops: &[Opcode], pos: usize) -> Option<usize> {
+    (0..pos).rev().find(|i| !matches!(ops[*i], Opcode::Nop))
+}
+
+fn next_op(ops: &[Opcode], pos: usize) -> usize {
+    (pos..ops.len())
+        .find(|i| !matches!(ops[*i], Opcode::Nop))
+        .unwrap_or(ops.len())
+}
+
+/// 从 from 之后到 to 之间（包括 to）是否有跳转目标，有则不能合并这段指令。
+fn has_target(targets: &[bool], from: usize, to: usize) -> bool {
+    targets[from + 1..=to].iter().any(|t| *t)
+}
+
+fn const_value(code: &Ref<RScriptCode>, op: Opcode) -> Result<Option<RValue>, Error> {
+    let v = match op {
+        Opcode::LoadNull => null().cast_value(),
+        Opcode::LoadTrue => true_().cast_value(),
+        Opcode::LoadFalse => false_().cast_value(),
+        Opcode::LoadInt(n) => RInt::new(n as Int)?.cast_value(),
+        Opcode::LoadConstNum(idx) => match code.get_const_number(idx as usize) {
+            Some(v) => v,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(v))
+}
+
+fn is_builtin_value(v: &RValue) -> bool {
+    v.is_type(int_type())
+        || v.is_type(float_type())
+        || v.is_type(bool_type())
+        || v.is_type(null_type())
+}
+
+fn load_value(code: &mut Ref<RScriptCode>, v: &RValue) -> Result<Option<Opcode>, Error> {
+    let op = if v.is_type(null_type()) {
+        Opcode::LoadNull
+    } else if v.is_type(bool_type()) {
+        if unsafe { v.cast_ref::<RBool>().as_bool() } {
+            Opcode::LoadTrue
+        } else {
+            Opcode::LoadFalse
+        }
+    } else if v.is_type(int_type()) {
+        let n = unsafe { v.cast_ref::<RInt>().as_number() };
+        if n >= i32::MIN as Int && n <= i32::MAX as Int {
+            Opcode::LoadInt(n as i32)
+        } else {
+            Opcode::LoadConstNum(code.push_const_number(v.clone())? as u32)
+        }
+    } else if v.is_type(float_type()) {
+        Opcode::LoadConstNum(code.push_const_number(v.clone())? as u32)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(op))
+}
+
+fn eval_binary(op: Opcode, left: &RValue, right: &RValue) -> Option<Result<RValue, Error>> {
+    let arith = match op {
+        Opcode::Add => ArithOp::Add,
+        Opcode::Sub => ArithOp::Sub,
+        Opcode::Mul => ArithOp::Mul,
+        Opcode::Div => ArithOp::Div,
+        Opcode::IDiv => ArithOp::IDiv,
+        Opcode::Mod => ArithOp::Mod,
+        Opcode::Pow => ArithOp::Pow,
+        Opcode::And => ArithOp::And,
+        Opcode::Or => ArithOp::Or,
+        Opcode::BitAnd => ArithOp::BitAnd,
+        Opcode::BitOr => ArithOp::BitOr,
+        Opcode::BitXor => ArithOp::BitXor,
+        Opcode::Shl => ArithOp::Shl,
+        Opcode::Shr => ArithOp::Shr,
+        Opcode::Cmp => return Some(opfunc::cmp(left, right)),
+        Opcode::Eq => return Some(opfunc::eq(left, right)),
+        Opcode::Ne => return Some(opfunc::ne(left, right)),
+        Opcode::Lt => return Some(opfunc::lt(left, right)),
+        Opcode::Le => return Some(opfunc::le(left, right)),
+        Opcode::Gt => return Some(opfunc::gt(left, right)),
+        Opcode::Ge => return Some(opfunc::ge(left, right)),
+        _ => return None,
+    };
+    Some(value_binary_op(arith, left, right))
+}
+
+fn eval_unary(op: Opcode, value: &RValue) -> Option<Result<RValue, Error>> {
+    match op {
+        Opcode::Not => Some(value_unary_op(UnaryOp::Not, value)),
+        Opcode::BitNot => Some(value_unary_op(UnaryOp::BitNot, value)),
+        _ => None,
+    }
+}
+
+/// 常量折叠，直接调用内置类型的运算函数求值，保证结果与运行时一致。
+/// 运算出错时不折叠，把错误留到运行时。
+fn fold_constant(
+    code: &mut Ref<RScriptCode>,
+    ops: &mut [Opcode],
+    targets: &[bool],
+) -> Result<bool, Error> {
+    let mut changed = false;
+    for i in 0..ops.len() {
+        let Some(a) = prev_op(ops, i) else {
+            continue;
+        };
+        if has_target(targets, a, i) {
+            continue;
+        }
+        let Some(right) = const_value(code, ops[a])? else {
+            continue;
+        };
+        if !is_builtin_value(&right) {
+            continue;
+        }
+
+        if let Some(Ok(v)) = eval_unary(ops[i], &right) {
+            if let Some(load) = load_value(code, &v)? {
+                ops[a] = Opcode::Nop;
+                ops[i] = load;
+                changed = true;
+            }
+            continue;
+        }
+
+        let Some(b) = prev_op(ops, a) else {
+            continue;
+        };
+        if has_target(targets, b, a) {
+            continue;
+        }
+        let Some(left) = const_value(code, ops[b])? else {
+            continue;
+        };
+        if !is_builtin_value(&left) {
+            continue;
+        }
+        if let Some(Ok(v)) = eval_binary(ops[i], &left, &right) {
+            if let Some(load) = load_value(code, &v)? {
+                ops[b] = Opcode::Nop;
+                ops[a] = Opcode::Nop;
+                ops[i] = load;
+                changed = true;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Return 和 Jmp 之后，直到下一个跳转目标之前的指令不可达。
+fn remove_unreachable(ops: &mut [Opcode], targets: &[bool]) -> bool {
+    let mut changed = false;
+    let mut reachable = true;
+    for i in 0..ops.len() {
+        if targets[i] {
+            reachable = true;
+        }
+        if !reachable && !matches!(ops[i], Opcode::Nop) {
+            ops[i] = Opcode::Nop;
+            changed = true;
+        }
+        if matches!(ops[i], Opcode::Return | Opcode::Jmp(_)) {
+            reachable = false;
+        }
+    }
+    changed
+}
+
+fn thread_jumps(ops: &mut [Opcode]) -> bool {
+    let mut changed = false;
+    for i in 0..ops.len() {
+        let Some(mut target) = jump_target(ops[i]) else {
+            continue;
+        };
+
+        // 限制次数，避免 Jmp 互相跳转时死循环。
+        for _ in 0..ops.len() {
+            let t = next_op(ops, target);
+            match ops.get(t) {
+                Some(Opcode::Jmp(next)) if t != i => target = *next as usize,
+                _ => {
+                    target = t;
+                    break;
+                }
+            }
+        }
+
+        if matches!(ops[i], Opcode::Jmp(_)) && target == next_op(ops, i + 1) {
+            ops[i] = Opcode::Nop;
+            changed = true;
+        } else if Some(target) != jump_target(ops[i]) {
+            ops[i] = with_jump_target(ops[i], target as i32);
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn is_pure_load(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::LoadNull
+            | Opcode::LoadTrue
+            | Opcode::LoadFalse
+            | Opcode::LoadInt(_)
+            | Opcode::LoadConstStr(_)
+            | Opcode::LoadConstNum(_)
+            | Opcode::LoadThis
+            | Opcode::GetLocal(_)
+            | Opcode::GetCapture(_)
+            | Opcode::Dup
+    )
+}
+
+fn peephole(ops: &mut [Opcode], targets: &[bool]) -> bool {
+    let mut changed = false;
+    for i in 0..ops.len() {
+        if !matches!(ops[i], Opcode::Pop) {
+            continue;
+        }
+        let Some(a) = prev_op(ops, i) else {
+            continue;
+        };
+        if has_target(targets, a, i) {
+            continue;
+        }
+
+        // LoadX; Pop => (无)
+        if is_pure_load(ops[a]) {
+            ops[a] = Opcode::Nop;
+            ops[i] = Opcode::Nop;
+            changed = true;
+            continue;
+        }
+
+        // Dup; SetLocal(n); Pop => SetLocal(n)
+        if let Opcode::SetLocal(_) = ops[a] {
+            let Some(b) = prev_op(ops, a) else {
+                continue;
+            };
+            if matches!(ops[b], Opcode::Dup) && !has_target(targets, b, a) {
+                ops[b] = Opcode::Nop;
+                ops[i] = Opcode::Nop;
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// 移除 Nop，并把跳转指令的参数恢复为相对位置。
+fn compact(ops: &[Opcode], out: &mut Array<Opcode>) -> Result<(), Error> {
+    let mut new_pos = Array::new(allocator());
+    let mut n: usize = 0;
+    for op in ops {
+        new_pos.push(n).map_err(|_| Error::OutOfMemory)?;
+        if !matches!(op, Opcode::Nop) {
+            n += 1;
+        }
+    }
+    new_pos.push(n).map_err(|_| Error::OutOfMemory)?;
+    let new_pos = new_pos.as_slice();
+
+    for (i, op) in ops.iter().enumerate() {
+        let op = match *op {
+            Opcode::Nop => continue,
+            op => match jump_target(op) {
+                Some(t) => {
+                    let offset = new_pos[t] as i32 - new_pos[i] as i32;
+                    with_jump_target(op, offset)
+                }
+                None => op,
+            },
+        };
+        out.push(op).map_err(|_| Error::OutOfMemory)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::{eval, initialize, parse_to_code, set_optimize_enabled};
+    use crate::test_util::{allocator, loader};
+
+    fn eval_str(source: &str, optimize: bool) -> String {
+        set_optimize_enabled(optimize);
+        let v = eval(source).unwrap();
+        value_str(&v).unwrap().as_str().to_string()
+    }
+
+    #[test]
+    fn test_optimize() {
+        initialize(allocator(), loader()).unwrap();
+
+        let sources = [
+            "1 + 2 * 3 - 4 / 2",
+            "(1 << 40) * 1000 + 0.5",
+            "7 // 2 + 7 % 2 + 2 ** 10",
+            "1 < 2 && 3 >= 3 || !true",
+            "~5 ^ 3 | 8 & 12",
+            "a = 0; if (1 < 2) a = 1; else a = 2; a",
+            "i = 0; s = 0; while (i < 10) { s = s + i * 2; i = i + 1; } s",
+            "function f(n) { if (n <= 2) return 1; return f(n - 1) + f(n - 2); } f(10)",
+            "function g() { return 1; 2 + 3; } g()",
+            "s = 0; for (n : [1, 2, 3]) { if (n != 2) s = s + n; } s",
+            "x = { a = 1 + 1; a * 3 }; x",
+        ];
+
+        for source in sources {
+            assert_eq!(
+                eval_str(source, true),
+                eval_str(source, false),
+                "{}",
+                source
+            );
+        }
+
+        set_optimize_enabled(true);
+        let code = parse_to_code("return 1 + 2;", false).unwrap();
+        assert!(matches!(
+            code.opcode(),
+            [Opcode::LoadInt(3), Opcode::Return]
+        ));
+
+        // 运算出错时不折叠，错误留到运行时。
+        let code = parse_to_code("return 1 // 0;", false).unwrap();
+        assert!(code.opcode().iter().any(|op| matches!(op, Opcode::IDiv)));
+
+        // 整数乘方溢出时报错，不会回绕或截断指数。
+        let code = parse_to_code("return 2 ** 10;", false).unwrap();
+        assert!(matches!(
+            code.opcode(),
+            [Opcode::LoadInt(1024), Opcode::Return]
+        ));
+        let code = parse_to_code("return 10 ** 30;", false).unwrap();
+        assert!(code.opcode().iter().any(|op| matches!(op, Opcode::Pow)));
+        for optimize in [true, false] {
+            set_optimize_enabled(optimize);
+            assert!(eval("10 ** 30").is_err());
+            assert!(eval("2 ** 4294967297").is_err());
+            assert_eq!(eval_str("(-1) ** 4294967297", optimize), "-1");
+            assert_eq!(eval_str("1 ** 4294967296", optimize), "1");
+        }
+    }
+}
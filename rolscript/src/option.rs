@@ -67,6 +67,9 @@ impl ROption {
 }
 
 pub(crate) fn _init_type_option(mut tp: Ref<RType>) -> Result<(), Error> {
+    tp.with_visit(option__visit);
+    tp.with_destory(option__destory);
+
     tp.with_eq(option__eq);
     tp.with_hash(default_value_hash);
     tp.with_str(default_value_str);
@@ -90,6 +93,25 @@ pub(crate) fn _init_type_option(mut tp: Ref<RType>) -> Result<(), Error> {
     Ok(())
 }
 
+#[allow(non_snake_case)]
+fn option__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let op = value_ptr.cast::<ROption>();
+        if let Some(v) = &op.as_ref()._value {
+            visitor.visit_value(v);
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+fn option__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut op = value.expect_cast::<ROption>(option_type())?;
+        addr_of_mut!(op._value).drop_in_place();
+    }
+    Ok(())
+}
+
 #[allow(non_snake_case)]
 fn option__eq(value: &RValue, other: &RValue) -> Result<bool, Error> {
     let op = unsafe { value.expect_cast::<ROption>(option_type())? };
@@ -135,3 +157,35 @@ fn option__value(this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
         Ok(null().cast_value())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::array::RArray;
+    use crate::runtime::{initialize, run_gc};
+    use crate::string::RString;
+    use crate::test_util::{allocator, loader};
+
+    fn ref_count<T>(value: &Ref<T>) -> usize {
+        value.cast_value_ref().as_ref().ref_count()
+    }
+
+    #[test]
+    fn test_option_gc() {
+        initialize(allocator(), loader()).unwrap();
+
+        // array -> option -> array 的循环引用可以被回收。
+        let s = RString::new("item").unwrap();
+        let count = ref_count(&s);
+
+        let mut array = RArray::new().unwrap();
+        array.push(s.cast_value()).unwrap();
+        let op = ROption::new(Some(array.cast_value())).unwrap();
+        array.push(op.cast_value()).unwrap();
+        drop(op);
+        drop(array);
+
+        run_gc().unwrap();
+        assert_eq!(ref_count(&s), count);
+    }
+}
@@ -8,6 +8,7 @@ use crate::error::*;
 use crate::{parse_error_fmt, runtime_error_fmt};
 
 use crate::ast::{Ast, RAst};
+use crate::enum_::ENUM_METHODS;
 use crate::lexical::*;
 use crate::token::TokenType as TT;
 use crate::token::{Pos, Token};
@@ -93,6 +94,12 @@ impl<'s> Parser<'s> {
         }
     }
 
+    // 下一个 token 的起始位置，用于记录语法树节点的位置。
+    fn start_pos(&mut self) -> Pos {
+        let _ = self._peek_to_n(0);
+        self.current_pos()
+    }
+
     fn end(&self) -> bool {
         self._cur_idx >= self._tokens.len() && self._lexical.end()
     }
@@ -252,9 +259,12 @@ fn program(parser: &mut Parser, allow_last_expr: bool) -> Result<Ref<RAst>, Erro
     let mut stats = Array::new(allocator());
     let mut expr = None;
 
+    let program_start = parser.start_pos();
+
     comment(parser)?;
 
     while !parser.end() {
+        let start = parser.start_pos();
         let (desc, ast) = if parser.match_(TT::Public) {
             parser.next_token()?;
             let (desc, expr, name) = if parser.match_all(&[TT::Ident, TT::Assign]) {
@@ -263,10 +273,12 @@ fn program(parser: &mut Parser, allow_last_expr: bool) -> Result<Ref<RAst>, Erro
                 function_def(parser)?
             } else if parser.match_(TT::Type) {
                 type_def(parser)?
+            } else if parser.match_(TT::Enum) {
+                enum_def(parser)?
             } else {
                 return Err(parse_error_fmt!(
                     parser.current_pos(),
-                    "public only supports assignment statements, function definitions and type definitions"
+                    "public only supports assignment statements, function, type and enum definitions"
                 ));
             };
 
@@ -277,7 +289,7 @@ fn program(parser: &mut Parser, allow_last_expr: bool) -> Result<Ref<RAst>, Erro
                 parser.expect(TT::SemiColon)?;
             };
 
-            let ast = RAst::new(Ast::ProgramPublic { name, expr })?;
+            let ast = RAst::new_at(Ast::ProgramPublic { name, expr }, start)?;
             (Desc::Stat, ast)
         } else {
             _stat_or_expr(parser)?
@@ -297,7 +309,7 @@ fn program(parser: &mut Parser, allow_last_expr: bool) -> Result<Ref<RAst>, Erro
         }
     }
 
-    let ast = RAst::new(Ast::Program { stats, expr })?;
+    let ast = RAst::new_at(Ast::Program { stats, expr }, program_start)?;
 
     Ok(ast)
 }
@@ -308,12 +320,15 @@ fn ident_assign(parser: &mut Parser) -> ExPResult<Ref<RString>> {
     let (expr_desc, expr_ast) = expr(parser)?;
 
     let name = RString::new(name_tk.source())?;
-    let ident_ast = RAst::new(Ast::Ident { name: name.clone() })?;
+    let ident_ast = RAst::new_at(Ast::Ident { name: name.clone() }, name_tk.pos())?;
 
-    let ast = RAst::new(Ast::Assign {
-        target: ident_ast,
-        expr: expr_ast,
-    })?;
+    let ast = RAst::new_at(
+        Ast::Assign {
+            target: ident_ast,
+            expr: expr_ast,
+        },
+        name_tk.pos(),
+    )?;
 
     let desc = if expr_desc.is_var_expr() {
         Desc::Expr
@@ -330,9 +345,10 @@ fn comment(parser: &mut Parser) -> Result<(), Error> {
 }
 
 fn stat(parser: &mut Parser, allow_return: bool) -> PResult {
+    let start = parser.start_pos();
     let (desc, expr) = if parser.match_(TT::SemiColon) {
         parser.next_token()?;
-        let ast = RAst::new(Ast::Stat { expr: None })?;
+        let ast = RAst::new_at(Ast::Stat { expr: None }, start)?;
         Ok((Desc::Stat, ast))
     } else if parser.match_(TT::If) {
         if_(parser, false)
@@ -357,13 +373,13 @@ fn stat(parser: &mut Parser, allow_return: bool) -> PResult {
     let stat_ast = if desc.is_stat() {
         parser.expect(TT::SemiColon);
         if desc.is_expr() {
-            RAst::new(Ast::Stat { expr: Some(expr) })?
+            RAst::new_at(Ast::Stat { expr: Some(expr) }, start)?
         } else {
             expr
         }
     } else {
         parser.expect(TT::SemiColon)?;
-        RAst::new(Ast::Stat { expr: Some(expr) })?
+        RAst::new_at(Ast::Stat { expr: Some(expr) }, start)?
     };
 
     Ok((Desc::Stat, stat_ast))
@@ -372,6 +388,8 @@ fn stat(parser: &mut Parser, allow_return: bool) -> PResult {
 fn expr(parser: &mut Parser) -> PResult {
     if _match_lambda(parser) {
         lambda(parser)
+    } else if parser.match_(TT::Yield) {
+        yield_(parser)
     } else if parser.match_(TT::If) {
         if_(parser, true)
     } else if parser.match_(TT::While) {
@@ -382,11 +400,14 @@ fn expr(parser: &mut Parser) -> PResult {
         function_def(parser).map(|(a, b, _)| (a, b))
     } else if parser.match_(TT::Type) {
         type_def(parser).map(|(a, b, _)| (a, b))
+    } else if parser.match_(TT::Enum) {
+        enum_def(parser).map(|(a, b, _)| (a, b))
     } else {
         let (desc, ast) = binary_expr(parser, MAX_BINOP_LEVEL)?;
         if desc.is_var_expr() && parser.expect(TT::Assign).is_ok() {
             let (expr_desc, expr) = expr(parser)?;
-            let ast = RAst::new(Ast::Assign { target: ast, expr })?;
+            let pos = ast.pos();
+            let ast = RAst::new_at(Ast::Assign { target: ast, expr }, pos)?;
             if expr_desc.is_var_expr() {
                 Ok((Desc::Expr, ast))
             } else {
@@ -402,9 +423,10 @@ fn expr(parser: &mut Parser) -> PResult {
 /// 否则，返回的 desc.is_expr() == true || desc.is_expr() == false,
 /// 在must_expr为true的情况下，会尽可能的匹配语句。
 fn _stat_or_expr(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     if parser.match_(TT::SemiColon) {
         parser.next_token()?;
-        let ast = RAst::new(Ast::Stat { expr: None })?;
+        let ast = RAst::new_at(Ast::Stat { expr: None }, start)?;
         return Ok((Desc::Stat, ast));
     } else if parser.match_(TT::Return) {
         return return_(parser);
@@ -413,6 +435,8 @@ fn _stat_or_expr(parser: &mut Parser) -> PResult {
     let (desc, ast) = {
         if _match_lambda(parser) {
             lambda(parser)
+        } else if parser.match_(TT::Yield) {
+            yield_(parser)
         } else if parser.match_(TT::If) {
             if_(parser, false)
         } else if parser.match_(TT::While) {
@@ -423,11 +447,14 @@ fn _stat_or_expr(parser: &mut Parser) -> PResult {
             function_def(parser).map(|(a, b, _)| (a, b))
         } else if parser.match_(TT::Type) {
             type_def(parser).map(|(a, b, _)| (a, b))
+        } else if parser.match_(TT::Enum) {
+            enum_def(parser).map(|(a, b, _)| (a, b))
         } else {
             let (desc, ast) = binary_expr(parser, MAX_BINOP_LEVEL)?;
             if desc.is_var_expr() && parser.expect(TT::Assign).is_ok() {
                 let (expr_desc, expr) = expr(parser)?;
-                let ast = RAst::new(Ast::Assign { target: ast, expr })?;
+                let pos = ast.pos();
+                let ast = RAst::new_at(Ast::Assign { target: ast, expr }, pos)?;
                 if expr_desc.is_var_expr() {
                     Ok((Desc::Expr, ast))
                 } else {
@@ -454,10 +481,11 @@ fn _stat_or_expr(parser: &mut Parser) -> PResult {
 }
 
 fn return_(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     parser.expect(TT::Return)?;
     if parser.match_(TT::SemiColon) {
         parser.next_token()?;
-        let ast = RAst::new(Ast::Return { expr: None })?;
+        let ast = RAst::new_at(Ast::Return { expr: None }, start)?;
         Ok((Desc::Expr, ast))
     } else {
         let (expr_desc, expr_ast) = expr(parser)?;
@@ -469,13 +497,34 @@ fn return_(parser: &mut Parser) -> PResult {
             parser.expect(TT::SemiColon)?;
         }
 
-        let ast = RAst::new(Ast::Return {
-            expr: Some(expr_ast),
-        })?;
+        let ast = RAst::new_at(
+            Ast::Return {
+                expr: Some(expr_ast),
+            },
+            start,
+        )?;
         Ok((Desc::Stat, ast))
     }
 }
 
+/// yield 是表达式，其值为恢复执行时传入的值。
+fn yield_(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
+    parser.expect(TT::Yield)?;
+
+    let value = if parser.end()
+        || parser.match_any(&[TT::SemiColon, TT::RPar, TT::RBrace, TT::RBrack, TT::Comma])
+    {
+        None
+    } else {
+        let (_, value_ast) = expr(parser)?;
+        Some(value_ast)
+    };
+
+    let ast = RAst::new_at(Ast::Yield { expr: value }, start)?;
+    Ok((Desc::Expr, ast))
+}
+
 fn _match_lambda(parser: &mut Parser) -> bool {
     if parser.match_all(&[TT::Ident, TT::Arrow]) {
         return true;
@@ -525,6 +574,7 @@ fn _paramets_list(parser: &mut Parser) -> Result<Array<Ref<RString>>, Error> {
 }
 
 fn lambda(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     let paramets = if let Ok(name_tk) = parser.expect(TT::Ident) {
         let mut paramets = Array::new(allocator());
         let name = RString::new(name_tk.source())?;
@@ -546,12 +596,13 @@ fn lambda(parser: &mut Parser) -> PResult {
         body_desc
     };
 
-    let ast = RAst::new(Ast::Lambda { paramets, body })?;
+    let ast = RAst::new_at(Ast::Lambda { paramets, body }, start)?;
 
     Ok((desc, ast))
 }
 
 fn function_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
+    let start = parser.start_pos();
     parser.expect(TT::Function)?;
 
     let name_tk = parser.expect(TT::Ident)?;
@@ -563,11 +614,14 @@ fn function_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
 
     let (_, body) = block_expr(parser)?;
 
-    let ast = RAst::new(Ast::FunctionDef {
-        name: name.clone(),
-        paramets,
-        body,
-    })?;
+    let ast = RAst::new_at(
+        Ast::FunctionDef {
+            name: name.clone(),
+            paramets,
+            body,
+        },
+        start,
+    )?;
 
     Ok((Desc::StatExpr, ast, name))
 }
@@ -633,6 +687,7 @@ fn _expect_overload_operator_def(parser: &mut Parser) -> Result<Option<OverloadO
 }
 
 fn type_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
+    let start = parser.start_pos();
     parser.expect(TT::Type)?;
 
     let name_tk = parser.expect(TT::Ident)?;
@@ -645,6 +700,7 @@ fn type_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
     comment(parser)?;
 
     while !parser.match_(TT::RBrace) {
+        let stat_start = parser.start_pos();
         let (_, ast) = if parser.match_(TT::Public) {
             parser.next_token()?;
             let (desc, expr, name) = if parser.match_all(&[TT::Ident, TT::Assign]) {
@@ -667,7 +723,7 @@ fn type_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
                 parser.expect(TT::SemiColon)?;
             };
 
-            let ast = RAst::new(Ast::TypePublic { name, expr })?;
+            let ast = RAst::new_at(Ast::TypePublic { name, expr }, stat_start)?;
             (Desc::Stat, ast)
         } else if let Some(op) = _expect_overload_operator_def(parser)? {
             let paramets = _paramets_list(parser)?;
@@ -676,11 +732,14 @@ fn type_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
 
             let (_, body) = block_expr(parser)?;
 
-            let ast = RAst::new(Ast::OverloadDef {
-                op: op,
-                paramets,
-                body,
-            })?;
+            let ast = RAst::new_at(
+                Ast::OverloadDef {
+                    op: op,
+                    paramets,
+                    body,
+                },
+                stat_start,
+            )?;
             (Desc::StatExpr, ast)
         } else {
             stat(parser, false)?
@@ -693,10 +752,79 @@ fn type_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
 
     parser.expect(TT::RBrace)?;
 
-    let ast = RAst::new(Ast::TypeDef {
-        name: name.clone(),
-        stats,
-    })?;
+    let ast = RAst::new_at(
+        Ast::TypeDef {
+            name: name.clone(),
+            stats,
+        },
+        start,
+    )?;
+
+    Ok((Desc::StatExpr, ast, name))
+}
+
+fn enum_def(parser: &mut Parser) -> ExPResult<Ref<RString>> {
+    let start = parser.start_pos();
+    parser.expect(TT::Enum)?;
+
+    let name_tk = parser.expect(TT::Ident)?;
+    let name = RString::new(name_tk.source())?;
+
+    let mut variants = Array::new(allocator());
+
+    parser.expect(TT::LBrace)?;
+
+    comment(parser)?;
+
+    while !parser.match_(TT::RBrace) {
+        let variant_tk = parser.expect(TT::Ident)?;
+        let variant_name = RString::new(variant_tk.source())?;
+
+        for (n, _) in variants.as_slice() {
+            if Ref::ptr_eq(n, &variant_name) {
+                return Err(parse_error_fmt!(
+                    variant_tk.pos(),
+                    "duplicate enum variant \"{}\"",
+                    variant_name.as_str()
+                ));
+            }
+        }
+        if ENUM_METHODS.contains(&variant_name.as_str()) {
+            return Err(parse_error_fmt!(
+                variant_tk.pos(),
+                "enum variant can not be named \"{}\"",
+                variant_name.as_str()
+            ));
+        }
+
+        let fields = if parser.match_(TT::LPar) {
+            _paramets_list(parser)?
+        } else {
+            Array::new(allocator())
+        };
+
+        variants
+            .push((variant_name, fields))
+            .map_err(|_| Error::OutOfMemory)?;
+
+        comment(parser)?;
+
+        if parser.expect(TT::Comma).is_err() {
+            break;
+        }
+
+        comment(parser)?;
+    }
+
+    parser.expect(TT::RBrace)?;
+
+    let ast = RAst::new_at(
+        Ast::EnumDef {
+            name: name.clone(),
+            variants,
+        },
+        start,
+    )?;
 
     Ok((Desc::StatExpr, ast, name))
 }
@@ -750,16 +878,20 @@ fn if_(parser: &mut Parser, must_expr: bool) -> PResult {
         body_desc
     };
 
-    let ast = RAst::new(Ast::If {
-        is_expr: body_desc.is_expr(),
-        cond,
-        truebody,
-        falsebody,
-    })?;
+    let ast = RAst::new_at(
+        Ast::If {
+            is_expr: body_desc.is_expr(),
+            cond,
+            truebody,
+            falsebody,
+        },
+        if_pos,
+    )?;
     Ok((desc, ast))
 }
 
 fn while_(parser: &mut Parser, must_expr: bool) -> PResult {
+    let start = parser.start_pos();
     parser.expect(TT::While)?;
     parser.expect(TT::LPar)?;
 
@@ -786,16 +918,20 @@ fn while_(parser: &mut Parser, must_expr: bool) -> PResult {
         body_desc
     };
 
-    let ast = RAst::new(Ast::While {
-        is_expr: desc.is_expr(),
-        cond,
-        body,
-    })?;
+    let ast = RAst::new_at(
+        Ast::While {
+            is_expr: desc.is_expr(),
+            cond,
+            body,
+        },
+        start,
+    )?;
 
     Ok((desc, ast))
 }
 
 fn for_(parser: &mut Parser, must_expr: bool) -> PResult {
+    let start = parser.start_pos();
     parser.expect(TT::For)?;
     parser.expect(TT::LPar)?;
 
@@ -827,12 +963,15 @@ fn for_(parser: &mut Parser, must_expr: bool) -> PResult {
 
     let name = RString::new(name_tk.source())?;
 
-    let ast = RAst::new(Ast::For {
-        is_expr: desc.is_expr(),
-        name,
-        expr,
-        body,
-    })?;
+    let ast = RAst::new_at(
+        Ast::For {
+            is_expr: desc.is_expr(),
+            name,
+            expr,
+            body,
+        },
+        start,
+    )?;
 
     Ok((desc, ast))
 }
@@ -870,11 +1009,13 @@ fn binary_expr(parser: &mut Parser, level: usize) -> PResult {
     while let Ok(tk) = _expect_binop(parser, level) {
         let right_desc = if let Some(op) = _tk_to_arith_op(tk) {
             let (right_desc, right) = binary_expr(parser, level - 1)?;
-            left = RAst::new(Ast::ArithExpr { op, left, right })?;
+            let pos = left.pos();
+            left = RAst::new_at(Ast::ArithExpr { op, left, right }, pos)?;
             right_desc
         } else if let Some(op) = _tk_to_cmp_op(tk) {
             let (right_desc, right) = binary_expr(parser, level - 1)?;
-            left = RAst::new(Ast::CmpExpr { op, left, right })?;
+            let pos = left.pos();
+            left = RAst::new_at(Ast::CmpExpr { op, left, right }, pos)?;
             right_desc
         } else {
             return Err(parse_error_fmt!(
@@ -902,7 +1043,8 @@ fn pow_expr(parser: &mut Parser) -> PResult {
         let (right_desc, right) = pow_expr(parser)?;
 
         let op = ArithOp::Pow;
-        let ast = RAst::new(Ast::ArithExpr { op, left, right })?;
+        let pos = left.pos();
+        let ast = RAst::new_at(Ast::ArithExpr { op, left, right }, pos)?;
         let desc = if right_desc.is_var_expr() {
             Desc::Expr
         } else {
@@ -915,6 +1057,7 @@ fn pow_expr(parser: &mut Parser) -> PResult {
 }
 
 fn unary_expr(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     if parser.match_any(&[TT::Not, TT::BitNot]) {
         let op_tk = unsafe { parser.next_token()?.unwrap_unchecked() };
         let (expr_desc, expr) = unary_expr(parser)?;
@@ -930,7 +1073,7 @@ fn unary_expr(parser: &mut Parser) -> PResult {
         } else {
             expr_desc
         };
-        let ast = RAst::new(Ast::UnaryExpr { op, expr })?;
+        let ast = RAst::new_at(Ast::UnaryExpr { op, expr }, start)?;
 
         Ok((desc, ast))
     } else {
@@ -944,14 +1087,14 @@ fn atom(parser: &mut Parser) -> PResult {
         let n = tk
             .as_int()
             .ok_or_else(|| runtime_error_fmt!("invalid int literal: {}", tk.source()))?;
-        let ast = RAst::new(Ast::Int(n as Int))?;
+        let ast = RAst::new_at(Ast::Int(n as Int), tk.pos())?;
         (Desc::Expr, ast)
     } else if parser.match_(TT::Float) {
         let tk = parser.expect(TT::Float)?;
         let n = tk
             .as_float()
             .ok_or_else(|| runtime_error_fmt!("invalid float literal: {}", tk.source()))?;
-        let ast = RAst::new(Ast::Float(n as Float))?;
+        let ast = RAst::new_at(Ast::Float(n as Float), tk.pos())?;
         (Desc::Expr, ast)
     } else if parser.match_(TT::String) {
         string_literal(parser)?
@@ -993,35 +1136,55 @@ fn _args_list(parser: &mut Parser) -> Result<Array<Ref<RAst>>, Error> {
     }
 }
 
+/// 属性名，除了标识符之外还允许 yield，以支持 `Coroutine::yield(...)`。
+fn _expect_attr_name<'s>(parser: &mut Parser<'s>) -> Result<Token<'s>, Error> {
+    if parser.match_(TT::Yield) {
+        parser.expect(TT::Yield)
+    } else {
+        parser.expect(TT::Ident)
+    }
+}
+
 fn _prefix_expr(parser: &mut Parser, prefix_desc: Desc, prefix: Ref<RAst>) -> PResult {
+    let pos = prefix.pos();
     let (desc, ast) = if parser.match_(TT::LPar) {
         // 函数调用 => a(...)
         let args = _args_list(parser)?;
-        let ast = RAst::new(Ast::Call { func: prefix, args })?;
+        let ast = RAst::new_at(Ast::Call { func: prefix, args }, pos)?;
         (Desc::Expr, ast)
-    } else if parser.match_all(&[TT::Dot, TT::Ident, TT::LPar]) {
+    } else if parser.match_all(&[TT::Dot, TT::Ident, TT::LPar])
+        || parser.match_all(&[TT::Dot, TT::Yield, TT::LPar])
+    {
         // 调用对象的方法 => a.b(...)
         parser.expect(TT::Dot)?;
-        let name_tk = parser.expect(TT::Ident)?;
+        let name_tk = _expect_attr_name(parser)?;
         let name = RString::new(name_tk.source())?;
         let args = _args_list(parser)?;
-        let ast = RAst::new(Ast::MethodCall {
-            target: prefix,
-            name,
-            args,
-        })?;
+        let ast = RAst::new_at(
+            Ast::MethodCall {
+                target: prefix,
+                name,
+                args,
+            },
+            pos,
+        )?;
         (Desc::Expr, ast)
-    } else if parser.match_all(&[TT::DbColon, TT::Ident, TT::LPar]) {
+    } else if parser.match_all(&[TT::DbColon, TT::Ident, TT::LPar])
+        || parser.match_all(&[TT::DbColon, TT::Yield, TT::LPar])
+    {
         // 调用对象的属性 => a::b(...)
         parser.expect(TT::DbColon)?;
-        let name_tk = parser.expect(TT::Ident)?;
+        let name_tk = _expect_attr_name(parser)?;
         let name = RString::new(name_tk.source())?;
         let args = _args_list(parser)?;
-        let ast = RAst::new(Ast::AttrCall {
-            target: prefix,
-            name,
-            args,
-        })?;
+        let ast = RAst::new_at(
+            Ast::AttrCall {
+                target: prefix,
+                name,
+                args,
+            },
+            pos,
+        )?;
         (Desc::Expr, ast)
     } else if parser.match_(TT::LBrack) {
         // 获取对象的下标 => a[b]
@@ -1029,24 +1192,27 @@ fn _prefix_expr(parser: &mut Parser, prefix_desc: Desc, prefix: Ref<RAst>) -> PR
         let (_, idx_ast) = expr(parser)?;
         parser.expect(TT::RBrack)?;
 
-        let ast = RAst::new(Ast::Index {
-            expr: prefix,
-            index: idx_ast,
-        })?;
+        let ast = RAst::new_at(
+            Ast::Index {
+                expr: prefix,
+                index: idx_ast,
+            },
+            pos,
+        )?;
         (Desc::VarExpr, ast)
     } else if parser.match_(TT::Dot) {
         // 获取对象属性 => a.b
         parser.next_token()?;
-        let name_tk = parser.expect(TT::Ident)?;
+        let name_tk = _expect_attr_name(parser)?;
         let name = RString::new(name_tk.source())?;
-        let ast = RAst::new(Ast::Attr { expr: prefix, name })?;
+        let ast = RAst::new_at(Ast::Attr { expr: prefix, name }, pos)?;
         (Desc::VarExpr, ast)
     } else if parser.match_(TT::DbColon) {
         // 获取对象属性 => a::b
         parser.next_token()?;
-        let name_tk = parser.expect(TT::Ident)?;
+        let name_tk = _expect_attr_name(parser)?;
         let name = RString::new(name_tk.source())?;
-        let ast = RAst::new(Ast::Attr { expr: prefix, name })?;
+        let ast = RAst::new_at(Ast::Attr { expr: prefix, name }, pos)?;
         (Desc::VarExpr, ast)
     } else {
         return Ok((prefix_desc, prefix));
@@ -1064,7 +1230,7 @@ fn prefix_expr(parser: &mut Parser) -> PResult {
         (Desc::Expr, ast)
     } else if let Ok(name_tk) = parser.expect(TT::Ident) {
         let name = RString::new(name_tk.source())?;
-        let ast = RAst::new(Ast::Ident { name })?;
+        let ast = RAst::new_at(Ast::Ident { name }, name_tk.pos())?;
         (Desc::VarExpr, ast)
     } else {
         return Err(parse_error_fmt!(
@@ -1076,6 +1242,7 @@ fn prefix_expr(parser: &mut Parser) -> PResult {
 }
 
 fn block_expr(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     parser.expect(TT::LBrace)?;
 
     let mut stats = Array::new(allocator());
@@ -1098,7 +1265,7 @@ fn block_expr(parser: &mut Parser) -> PResult {
 
     parser.expect(TT::RBrace)?;
 
-    let ast = RAst::new(Ast::Block { stats, expr })?;
+    let ast = RAst::new_at(Ast::Block { stats, expr }, start)?;
     Ok((Desc::StatExpr, ast))
 }
 
@@ -1131,6 +1298,7 @@ fn _match_tuple_constructor(parser: &mut Parser) -> bool {
 }
 
 fn tuple_constructor(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     let mut expr_asts = Array::new(allocator());
 
     parser.expect(TT::LPar)?;
@@ -1155,12 +1323,13 @@ fn tuple_constructor(parser: &mut Parser) -> PResult {
 
     parser.expect(TT::RPar)?;
 
-    let ast = RAst::new(Ast::Tuple(expr_asts))?;
+    let ast = RAst::new_at(Ast::Tuple(expr_asts), start)?;
 
     Ok((Desc::Expr, ast))
 }
 
 fn array_constructor(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     parser.expect(TT::LBrack)?;
     let mut expr_asts = Array::new(allocator());
 
@@ -1176,7 +1345,7 @@ fn array_constructor(parser: &mut Parser) -> PResult {
     }
     parser.expect(TT::RBrack)?;
 
-    let ast = RAst::new(Ast::Array(expr_asts))?;
+    let ast = RAst::new_at(Ast::Array(expr_asts), start)?;
 
     Ok((Desc::Expr, ast))
 }
@@ -1213,7 +1382,7 @@ fn _map_field(parser: &mut Parser) -> Result<(Ref<RAst>, Ref<RAst>), Error> {
     let (_, key_ast) = if parser.match_(TT::Ident) {
         let tk = parser.expect(TT::Ident)?;
         let k_str = RString::new(tk.source())?;
-        (Desc::Expr, RAst::new(Ast::String(k_str))?)
+        (Desc::Expr, RAst::new_at(Ast::String(k_str), tk.pos())?)
     } else if parser.match_(TT::String) {
         string_literal(parser)?
     } else if parser.match_(TT::LBrack) {
@@ -1231,6 +1400,7 @@ fn _map_field(parser: &mut Parser) -> Result<(Ref<RAst>, Ref<RAst>), Error> {
     Ok((key_ast, value_ast))
 }
 fn map_constructor(parser: &mut Parser) -> PResult {
+    let start = parser.start_pos();
     parser.expect(TT::LBrace)?;
 
     let mut kv_asts = Array::new(allocator());
@@ -1252,7 +1422,7 @@ fn map_constructor(parser: &mut Parser) -> PResult {
 
     parser.expect(TT::RBrace)?;
 
-    let ast = RAst::new(Ast::Map(kv_asts))?;
+    let ast = RAst::new_at(Ast::Map(kv_asts), start)?;
 
     Ok((Desc::StatExpr, ast))
 }
@@ -1266,7 +1436,7 @@ fn string_literal(parser: &mut Parser) -> PResult {
 
         let buf = buf_arr.as_slice_mut();
         if let Some(s) = str_tk.as_string(buf) {
-            let ast = RAst::new(Ast::String(RString::new(s)?))?;
+            let ast = RAst::new_at(Ast::String(RString::new(s)?), str_tk.pos())?;
             Ok((Desc::Expr, ast))
         } else {
             Err(parse_error_fmt!(
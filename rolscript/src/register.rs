@@ -0,0 +1,1104 @@
+use crate::collections::Array;
+
+use crate::runtime::*;
+
+use crate::ast::*;
+use crate::function::*;
+use crate::number::*;
+use crate::script_code::*;
+use crate::string::RString;
+use crate::value::*;
+
+use crate::builtin::*;
+
+use crate::op::opcode_funcs as opfunc;
+use crate::op::*;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+pub(crate) type Reg = u16;
+
+/// 基于寄存器的指令。
+///
+/// 寄存器是帧在 runtime 值栈上的窗口中的下标，
+/// 前面依次是参数与局部变量，之后是临时值。
+/// 跳转指令的偏移与 Opcode 相同，相对于该指令自身。
+#[derive(Clone, Copy, Debug)]
+pub enum RegOp {
+    /// (dst, src)
+    Move(Reg, Reg),
+    LoadNull(Reg),
+    LoadInt(Reg, i32),
+    LoadConstStr(Reg, u32),
+    LoadConstNum(Reg, u32),
+    LoadThis(Reg),
+    /// (dst, start, count)，以 start 开始的 count 个寄存器为元素。
+    NewTuple(Reg, Reg, u16),
+    NewArray(Reg, Reg, u16),
+    /// (dst, start, count)，count 为键值对的个数。
+    NewMap(Reg, Reg, u16),
+    /// (dst, child, start, count)，以 start 开始的 count 个寄存器为捕获的变量。
+    NewClosure(Reg, u16, Reg, u16),
+    GetCapture(Reg, u32),
+    /// (closure, capture index, src)
+    SetCapture(Reg, u32, Reg),
+    GetGlobal(Reg, u32),
+    /// (dst, target, name)
+    GetAttr(Reg, Reg, u32),
+    /// (target, name, src)
+    SetAttr(Reg, u32, Reg),
+    /// (dst, target, index)
+    GetItem(Reg, Reg, Reg),
+    /// (target, index, src)
+    SetItem(Reg, Reg, Reg),
+    /// (op, dst, left, right)
+    Arith(ArithOp, Reg, Reg, Reg),
+    Cmp(CmpOp, Reg, Reg, Reg),
+    /// (op, dst, src)
+    Unary(UnaryOp, Reg, Reg),
+    /// (dst, src)
+    Iter(Reg, Reg),
+    IfFalse(Reg, i32),
+    Jmp(i32),
+    /// (iterator, dst, offset)，迭代结束时跳转，否则把得到的值存入 dst。
+    IterNext(Reg, Reg, i32),
+    /// (base, argc)，被调用者位于 base，参数紧随其后，返回值存入 base。
+    Call(Reg, u16),
+    /// (base, name, argc)，this 位于 base，参数紧随其后，返回值存入 base。
+    CallMethod(Reg, u16, u16),
+    CallAttr(Reg, u16, u16),
+    /// (base, argc)，用于 `return f(...)`，后面紧跟 Return(base)。
+    /// 若被调用者是寄存器指令的脚本函数，则复用当前窗口执行；否则与 Call 相同。
+    TailCall(Reg, u16),
+    Return(Reg),
+}
+
+impl RegOp {
+    /// 对指令中的每个寄存器应用 f。
+    pub(crate) fn map_regs(self, f: impl Fn(Reg) -> Reg) -> Self {
+        use RegOp::*;
+        match self {
+            Move(a, b) => Move(f(a), f(b)),
+            LoadNull(a) => LoadNull(f(a)),
+            LoadInt(a, n) => LoadInt(f(a), n),
+            LoadConstStr(a, n) => LoadConstStr(f(a), n),
+            LoadConstNum(a, n) => LoadConstNum(f(a), n),
+            LoadThis(a) => LoadThis(f(a)),
+            NewTuple(a, b, n) => NewTuple(f(a), f(b), n),
+            NewArray(a, b, n) => NewArray(f(a), f(b), n),
+            NewMap(a, b, n) => NewMap(f(a), f(b), n),
+            NewClosure(a, c, b, n) => NewClosure(f(a), c, f(b), n),
+            GetCapture(a, n) => GetCapture(f(a), n),
+            SetCapture(a, n, b) => SetCapture(f(a), n, f(b)),
+            GetGlobal(a, n) => GetGlobal(f(a), n),
+            GetAttr(a, b, n) => GetAttr(f(a), f(b), n),
+            SetAttr(a, n, b) => SetAttr(f(a), n, f(b)),
+            GetItem(a, b, c) => GetItem(f(a), f(b), f(c)),
+            SetItem(a, b, c) => SetItem(f(a), f(b), f(c)),
+            Arith(op, a, b, c) => Arith(op, f(a), f(b), f(c)),
+            Cmp(op, a, b, c) => Cmp(op, f(a), f(b), f(c)),
+            Unary(op, a, b) => Unary(op, f(a), f(b)),
+            Iter(a, b) => Iter(f(a), f(b)),
+            IfFalse(a, off) => IfFalse(f(a), off),
+            Jmp(off) => Jmp(off),
+            IterNext(a, b, off) => IterNext(f(a), f(b), off),
+            Call(a, n) => Call(f(a), n),
+            CallMethod(a, name, n) => CallMethod(f(a), name, n),
+            CallAttr(a, name, n) => CallAttr(f(a), name, n),
+            TailCall(a, n) => TailCall(f(a), n),
+            Return(a) => Return(f(a)),
+        }
+    }
+
+    fn with_offset(self, off: i32) -> Self {
+        match self {
+            RegOp::IfFalse(a, _) => RegOp::IfFalse(a, off),
+            RegOp::Jmp(_) => RegOp::Jmp(off),
+            RegOp::IterNext(a, b, _) => RegOp::IterNext(a, b, off),
+            op => op,
+        }
+    }
+}
+
+// 生成代码时临时值的寄存器带有该标记，
+// 局部变量的个数确定后再统一放到局部变量之后。
+const TEMP: Reg = 0x8000;
+
+/// 对 ast 的每个直接子节点调用 f，直到 f 返回 true。
+/// 函数与类型的定义体属于另一个函数，不在其中。
+fn any_child(ast: &Ast, f: &mut dyn FnMut(&Ref<RAst>) -> bool) -> bool {
+    use Ast::*;
+    match ast {
+        Int(_) | Float(_) | String(_) | Ident { name: _ } => false,
+        Tuple(arr) | Array(arr) => arr.as_slice().iter().any(&mut *f),
+        Map(map) => map.as_slice().iter().any(|(k, v)| f(k) || f(v)),
+        Program { stats, expr } | Block { stats, expr } => {
+            stats.as_slice().iter().any(&mut *f) || expr.as_ref().is_some_and(f)
+        }
+        ProgramPublic { name: _, expr } | TypePublic { name: _, expr } => f(expr),
+        ArithExpr { op: _, left, right } | CmpExpr { op: _, left, right } => f(left) || f(right),
+        UnaryExpr { op: _, expr } => f(expr),
+        Lambda { .. } | FunctionDef { .. } | OverloadDef { .. } => false,
+        TypeDef { .. } | EnumDef { .. } => false,
+        If {
+            is_expr: _,
+            cond,
+            truebody,
+            falsebody,
+        } => f(cond) || f(truebody) || falsebody.as_ref().is_some_and(f),
+        While {
+            is_expr: _,
+            cond,
+            body,
+        } => f(cond) || f(body),
+        For {
+            is_expr: _,
+            name: _,
+            expr,
+            body,
+        } => f(expr) || f(body),
+        Assign { target, expr } => f(target) || f(expr),
+        Attr { expr, name: _ } => f(expr),
+        Index { expr, index } => f(expr) || f(index),
+        Call { func, args } => f(func) || args.as_slice().iter().any(&mut *f),
+        MethodCall {
+            target,
+            name: _,
+            args,
+        }
+        | AttrCall {
+            target,
+            name: _,
+            args,
+        } => f(target) || args.as_slice().iter().any(&mut *f),
+        Return { expr } | Yield { expr } | Stat { expr } => expr.as_ref().is_some_and(f),
+    }
+}
+
+/// 函数体中是否只有寄存器指令能表示的语法。
+/// yield 需要挂起帧，类型与枚举的定义依赖栈指令，这些函数仍生成栈指令。
+pub(crate) fn is_supported(ast: &Ref<RAst>) -> bool {
+    match ast.as_ast() {
+        Ast::Yield { .. }
+        | Ast::TypeDef { .. }
+        | Ast::EnumDef { .. }
+        | Ast::OverloadDef { .. }
+        | Ast::TypePublic { .. } => false,
+        other => !any_child(other, &mut |v| !is_supported(v)),
+    }
+}
+
+/// 求值 ast 时是否可能给当前函数的局部变量赋值。
+fn assigns_local(ast: &Ref<RAst>) -> bool {
+    match ast.as_ast() {
+        Ast::Assign { target, expr: _ } if matches!(target.as_ast(), Ast::Ident { .. }) => true,
+        Ast::FunctionDef { .. } | Ast::For { .. } | Ast::TypeDef { .. } | Ast::EnumDef { .. } => {
+            true
+        }
+        other => any_child(other, &mut assigns_local),
+    }
+}
+
+fn jump_offset(from: usize, to: usize) -> i32 {
+    (to as isize - from as isize) as i32
+}
+
+struct RegCodegen {
+    builder: ScriptCodeBuilder,
+    ops: Array<RegOp>,
+    temps: usize,
+    max_temps: usize,
+}
+
+impl RegCodegen {
+    fn new(builder: ScriptCodeBuilder) -> Self {
+        Self {
+            builder,
+            ops: Array::new(allocator()),
+            temps: 0,
+            max_temps: 0,
+        }
+    }
+
+    fn emit(&mut self, op: RegOp) -> Result<usize, Error> {
+        self.ops.push(op).map_err(|_| Error::OutOfMemory)?;
+        Ok(self.ops.len() - 1)
+    }
+
+    fn pos(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// 把 at 处跳转指令的目标设置为 to。
+    fn patch(&mut self, at: usize, to: usize) {
+        let op = self.ops.as_slice()[at].with_offset(jump_offset(at, to));
+        self.ops.as_slice_mut()[at] = op;
+    }
+
+    fn push_temp(&mut self) -> Result<Reg, Error> {
+        if self.temps >= (TEMP - 1) as usize {
+            return Err(runtime_error_fmt!("too many temporary values"));
+        }
+        let r = TEMP | self.temps as Reg;
+        self.temps += 1;
+        self.max_temps = self.max_temps.max(self.temps);
+        Ok(r)
+    }
+
+    // 下一个将被分配的临时值。
+    fn next_temp(&self) -> Reg {
+        TEMP | self.temps as Reg
+    }
+
+    fn is_top_temp(&self, r: Reg) -> bool {
+        self.temps > 0 && r == TEMP | (self.temps - 1) as Reg
+    }
+
+    fn local(&mut self, name: &Ref<RString>) -> Result<Reg, Error> {
+        let idx = self.builder.with_local(name)?;
+        if idx >= TEMP as u32 {
+            return Err(runtime_error_fmt!("too many local variables"));
+        }
+        Ok(idx as Reg)
+    }
+
+    fn string(&mut self, s: &Ref<RString>) -> Result<u32, Error> {
+        let idx = self.builder.with_string(s)?;
+        Ok(idx as u32)
+    }
+
+    fn string_u16(&mut self, s: &Ref<RString>) -> Result<u16, Error> {
+        let idx = self.builder.with_string(s)?;
+        if idx > u16::MAX as usize {
+            return Err(runtime_error_fmt!("too many constant strings"));
+        }
+        Ok(idx as u16)
+    }
+
+    fn count_u16(n: usize) -> Result<u16, Error> {
+        if n > u16::MAX as usize {
+            Err(runtime_error_fmt!("too many items"))
+        } else {
+            Ok(n as u16)
+        }
+    }
+
+    fn move_(&mut self, dst: Reg, src: Reg) -> Result<(), Error> {
+        if dst != src {
+            self.emit(RegOp::Move(dst, src))?;
+        }
+        Ok(())
+    }
+
+    /// 求值 ast，返回存放结果的寄存器。局部变量直接返回其寄存器，不做复制。
+    fn expr(&mut self, ast: &Ref<RAst>) -> Result<Reg, Error> {
+        if let Ast::Ident { name } = ast.as_ast() {
+            if name.as_str() != "this" && self.builder.has_local(name) {
+                return self.local(name);
+            }
+        }
+        let t = self.push_temp()?;
+        self.expr_to(ast, t)?;
+        Ok(t)
+    }
+
+    /// 与 expr 相同，但之后求值 later 时若可能改变该局部变量，则先复制一份。
+    fn operand(&mut self, ast: &Ref<RAst>, later: &[&Ref<RAst>]) -> Result<Reg, Error> {
+        let r = self.expr(ast)?;
+        if r & TEMP == 0 && later.iter().any(|v| assigns_local(v)) {
+            let t = self.push_temp()?;
+            self.move_(t, r)?;
+            Ok(t)
+        } else {
+            Ok(r)
+        }
+    }
+
+    /// 把 asts 依次求值到连续的临时值中，返回第一个寄存器。
+    fn contiguous<'a>(&mut self, asts: impl Iterator<Item = &'a Ref<RAst>>) -> Result<Reg, Error> {
+        let start = self.next_temp();
+        for ast in asts {
+            let t = self.push_temp()?;
+            self.expr_to(ast, t)?;
+        }
+        Ok(start)
+    }
+
+    fn expr_to(&mut self, ast: &Ref<RAst>, dst: Reg) -> Result<(), Error> {
+        let mark = self.temps;
+        self._expr_to(ast, dst)?;
+        self.temps = mark;
+        Ok(())
+    }
+
+    fn _expr_to(&mut self, ast: &Ref<RAst>, dst: Reg) -> Result<(), Error> {
+        match ast.as_ast() {
+            Ast::Int(n) => {
+                if *n > (i32::MAX as Int) || *n < (i32::MIN as Int) {
+                    let idx = self.builder.with_integer(*n)?;
+                    self.emit(RegOp::LoadConstNum(dst, idx as u32))?;
+                } else {
+                    self.emit(RegOp::LoadInt(dst, *n as i32))?;
+                }
+            }
+            Ast::Float(n) => {
+                let idx = self.builder.with_float(*n)?;
+                self.emit(RegOp::LoadConstNum(dst, idx as u32))?;
+            }
+            Ast::String(s) => {
+                let idx = self.string(s)?;
+                self.emit(RegOp::LoadConstStr(dst, idx))?;
+            }
+            Ast::Tuple(arr) => {
+                let n = Self::count_u16(arr.len())?;
+                let start = self.contiguous(arr.as_slice().iter())?;
+                self.emit(RegOp::NewTuple(dst, start, n))?;
+            }
+            Ast::Array(arr) => {
+                let n = Self::count_u16(arr.len())?;
+                let start = self.contiguous(arr.as_slice().iter())?;
+                self.emit(RegOp::NewArray(dst, start, n))?;
+            }
+            Ast::Map(map) => {
+                let n = Self::count_u16(map.len())?;
+                let items = map.as_slice().iter().flat_map(|(k, v)| [k, v]);
+                let start = self.contiguous(items)?;
+                self.emit(RegOp::NewMap(dst, start, n))?;
+            }
+            Ast::Program { .. } => {
+                return Err(runtime_error_fmt!(
+                    "Ast::Program can only appear at the top level"
+                ))
+            }
+            Ast::Block { stats, expr } => {
+                for stat in stats.as_slice() {
+                    self.stat(stat)?;
+                }
+                if let Some(expr) = expr {
+                    self.expr_to(expr, dst)?;
+                } else {
+                    self.emit(RegOp::LoadNull(dst))?;
+                }
+            }
+            Ast::ArithExpr { op, left, right } => {
+                let a = self.operand(left, &[right])?;
+                let b = self.expr(right)?;
+                self.emit(RegOp::Arith(*op, dst, a, b))?;
+            }
+            Ast::CmpExpr { op, left, right } => {
+                let a = self.operand(left, &[right])?;
+                let b = self.expr(right)?;
+                self.emit(RegOp::Cmp(*op, dst, a, b))?;
+            }
+            Ast::UnaryExpr { op, expr } => {
+                let r = self.expr(expr)?;
+                self.emit(RegOp::Unary(*op, dst, r))?;
+            }
+            Ast::Lambda { paramets, body } => {
+                let (code, _) = _function_body_as_code(&mut self.builder, None, paramets, body)?;
+                self.closure(code, dst)?;
+            }
+            Ast::FunctionDef {
+                name,
+                paramets,
+                body,
+            } => {
+                let r = self.function_def(name, paramets, body)?;
+                self.move_(dst, r)?;
+            }
+            Ast::If {
+                is_expr,
+                cond,
+                truebody,
+                falsebody,
+            } => {
+                if *is_expr {
+                    self.if_(cond, truebody, falsebody, Some(dst))?;
+                } else {
+                    self.if_(cond, truebody, falsebody, None)?;
+                    self.emit(RegOp::LoadNull(dst))?;
+                }
+            }
+            Ast::While { .. } | Ast::For { .. } | Ast::Stat { .. } | Ast::ProgramPublic { .. } => {
+                self.stat(ast)?;
+                self.emit(RegOp::LoadNull(dst))?;
+            }
+            Ast::Ident { name } => {
+                if name.as_str() == "this" {
+                    self.emit(RegOp::LoadThis(dst))?;
+                } else if self.builder.has_local(name) {
+                    let r = self.local(name)?;
+                    self.move_(dst, r)?;
+                } else if let Some(idx) = self.builder.with_captured_parent(name)? {
+                    self.emit(RegOp::GetCapture(dst, idx))?;
+                } else {
+                    let idx = self.string(name)?;
+                    self.emit(RegOp::GetGlobal(dst, idx))?;
+                }
+            }
+            Ast::Assign { target, expr } => {
+                let r = self.assign(target, expr)?;
+                self.move_(dst, r)?;
+            }
+            Ast::Attr { expr, name } => {
+                let t = self.expr(expr)?;
+                let idx = self.string(name)?;
+                self.emit(RegOp::GetAttr(dst, t, idx))?;
+            }
+            Ast::Index { expr, index } => {
+                let t = self.operand(expr, &[index])?;
+                let i = self.expr(index)?;
+                self.emit(RegOp::GetItem(dst, t, i))?;
+            }
+            Ast::Call { func, args } => {
+                let argc = Self::count_u16(args.len())?;
+                let base = self.call_base(dst)?;
+                self.expr_to(func, base)?;
+                self.contiguous(args.as_slice().iter())?;
+                self.emit(RegOp::Call(base, argc))?;
+                self.move_(dst, base)?;
+            }
+            Ast::MethodCall { target, name, args } => {
+                let argc = Self::count_u16(args.len())?;
+                let idx = self.string_u16(name)?;
+                let base = self.call_base(dst)?;
+                self.expr_to(target, base)?;
+                self.contiguous(args.as_slice().iter())?;
+                self.emit(RegOp::CallMethod(base, idx, argc))?;
+                self.move_(dst, base)?;
+            }
+            Ast::AttrCall { target, name, args } => {
+                let argc = Self::count_u16(args.len())?;
+                let idx = self.string_u16(name)?;
+                let base = self.call_base(dst)?;
+                self.expr_to(target, base)?;
+                self.contiguous(args.as_slice().iter())?;
+                self.emit(RegOp::CallAttr(base, idx, argc))?;
+                self.move_(dst, base)?;
+            }
+            Ast::Return { expr } => self.return_(expr)?,
+            Ast::Yield { .. }
+            | Ast::TypeDef { .. }
+            | Ast::EnumDef { .. }
+            | Ast::OverloadDef { .. }
+            | Ast::TypePublic { .. } => {
+                return Err(runtime_error_fmt!(
+                    "unsupported syntax in register backend: {:?}",
+                    ast
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// 调用的参数需要紧跟在 base 之后，dst 位于临时值的顶部时可以直接用作 base。
+    fn call_base(&mut self, dst: Reg) -> Result<Reg, Error> {
+        if self.is_top_temp(dst) {
+            Ok(dst)
+        } else {
+            self.push_temp()
+        }
+    }
+
+    /// 求值 ast 并丢弃结果。
+    fn stat(&mut self, ast: &Ref<RAst>) -> Result<(), Error> {
+        let mark = self.temps;
+        match ast.as_ast() {
+            Ast::Block { stats, expr } => {
+                for stat in stats.as_slice() {
+                    self.stat(stat)?;
+                }
+                if let Some(expr) = expr {
+                    self.stat(expr)?;
+                }
+            }
+            Ast::ProgramPublic { name, expr } => {
+                let this = self.push_temp()?;
+                self.emit(RegOp::LoadThis(this))?;
+                let v = self.expr(expr)?;
+                let idx = self.string(name)?;
+                self.emit(RegOp::SetAttr(this, idx, v))?;
+            }
+            Ast::FunctionDef {
+                name,
+                paramets,
+                body,
+            } => {
+                self.function_def(name, paramets, body)?;
+            }
+            Ast::If {
+                is_expr: _,
+                cond,
+                truebody,
+                falsebody,
+            } => self.if_(cond, truebody, falsebody, None)?,
+            Ast::While {
+                is_expr: _,
+                cond,
+                body,
+            } => self.while_(cond, body)?,
+            Ast::For {
+                is_expr: _,
+                name,
+                expr,
+                body,
+            } => self.for_(name, expr, body)?,
+            Ast::Assign { target, expr } => {
+                self.assign(target, expr)?;
+            }
+            Ast::Return { expr } => self.return_(expr)?,
+            Ast::Stat { expr } => {
+                if let Some(expr) = expr {
+                    self.stat(expr)?;
+                }
+            }
+            _ => {
+                self.expr(ast)?;
+            }
+        }
+        self.temps = mark;
+        Ok(())
+    }
+
+    /// 返回存放所赋的值的寄存器。
+    fn assign(&mut self, target: &Ref<RAst>, expr: &Ref<RAst>) -> Result<Reg, Error> {
+        match target.as_ast() {
+            Ast::Ident { name } => {
+                if name.as_str() == "this" {
+                    return Err(runtime_error_fmt!("cannot assign a value to \"this\""));
+                }
+                let r = self.local(name)?;
+                self.expr_to(expr, r)?;
+                Ok(r)
+            }
+            Ast::Attr {
+                expr: target_expr,
+                name,
+            } => {
+                let t = self.operand(target_expr, &[expr])?;
+                let v = self.expr(expr)?;
+                let idx = self.string(name)?;
+                self.emit(RegOp::SetAttr(t, idx, v))?;
+                Ok(v)
+            }
+            Ast::Index {
+                expr: target_expr,
+                index,
+            } => {
+                let t = self.operand(target_expr, &[index, expr])?;
+                let i = self.operand(index, &[expr])?;
+                let v = self.expr(expr)?;
+                self.emit(RegOp::SetItem(t, i, v))?;
+                Ok(v)
+            }
+            _ => Err(runtime_error_fmt!(
+                "the left side of the assignor must be an assignable expression"
+            )),
+        }
+    }
+
+    fn if_(
+        &mut self,
+        cond: &Ref<RAst>,
+        truebody: &Ref<RAst>,
+        falsebody: &Option<Ref<RAst>>,
+        dst: Option<Reg>,
+    ) -> Result<(), Error> {
+        let mark = self.temps;
+        let c = self.expr(cond)?;
+        let if_pos = self.emit(RegOp::IfFalse(c, 0))?;
+        self.temps = mark;
+
+        self.branch(truebody, dst)?;
+        let jmp_pos = self.emit(RegOp::Jmp(0))?;
+
+        self.patch(if_pos, self.pos());
+        if let Some(falsebody) = falsebody {
+            self.branch(falsebody, dst)?;
+        } else if let Some(dst) = dst {
+            self.emit(RegOp::LoadNull(dst))?;
+        }
+        self.patch(jmp_pos, self.pos());
+        Ok(())
+    }
+
+    fn branch(&mut self, ast: &Ref<RAst>, dst: Option<Reg>) -> Result<(), Error> {
+        match dst {
+            Some(dst) => self.expr_to(ast, dst),
+            None => self.stat(ast),
+        }
+    }
+
+    fn while_(&mut self, cond: &Ref<RAst>, body: &Ref<RAst>) -> Result<(), Error> {
+        let start = self.pos();
+        let mark = self.temps;
+        let c = self.expr(cond)?;
+        let if_pos = self.emit(RegOp::IfFalse(c, 0))?;
+        self.temps = mark;
+
+        self.stat(body)?;
+        let jmp_pos = self.emit(RegOp::Jmp(0))?;
+        self.patch(jmp_pos, start);
+        self.patch(if_pos, self.pos());
+        Ok(())
+    }
+
+    fn for_(
+        &mut self,
+        name: &Ref<RString>,
+        expr: &Ref<RAst>,
+        body: &Ref<RAst>,
+    ) -> Result<(), Error> {
+        let var = self.local(name)?;
+
+        let iter = self.push_temp()?;
+        let mark = self.temps;
+        let r = self.expr(expr)?;
+        self.emit(RegOp::Iter(iter, r))?;
+        self.temps = mark;
+
+        let start = self.emit(RegOp::IterNext(iter, var, 0))?;
+        self.stat(body)?;
+        let jmp_pos = self.emit(RegOp::Jmp(0))?;
+        self.patch(jmp_pos, start);
+        self.patch(start, self.pos());
+        Ok(())
+    }
+
+    fn return_(&mut self, expr: &Option<Ref<RAst>>) -> Result<(), Error> {
+        // 尾调用 => return f(...)
+        if let Some(Ast::Call { func, args }) = expr.as_ref().map(|v| v.as_ast()) {
+            if self.builder.has_parent() {
+                let argc = Self::count_u16(args.len())?;
+                let base = self.push_temp()?;
+                self.expr_to(func, base)?;
+                self.contiguous(args.as_slice().iter())?;
+                self.emit(RegOp::TailCall(base, argc))?;
+                self.emit(RegOp::Return(base))?;
+                return Ok(());
+            }
+        }
+
+        let r = if let Some(expr) = expr {
+            self.expr(expr)?
+        } else {
+            let t = self.push_temp()?;
+            self.emit(RegOp::LoadNull(t))?;
+            t
+        };
+        self.emit(RegOp::Return(r))?;
+        Ok(())
+    }
+
+    fn function_def(
+        &mut self,
+        name: &Ref<RString>,
+        paramets: &Array<Ref<RString>>,
+        body: &Ref<RAst>,
+    ) -> Result<Reg, Error> {
+        let r = self.local(name)?;
+        let (code, capture_self_idx) =
+            _function_body_as_code(&mut self.builder, Some(name), paramets, body)?;
+        self.closure(code, r)?;
+        if let Some(idx) = capture_self_idx {
+            self.emit(RegOp::SetCapture(r, idx, r))?;
+        }
+        Ok(r)
+    }
+
+    fn closure(&mut self, code: Ref<RScriptCode>, dst: Reg) -> Result<(), Error> {
+        let child_idx = self.builder.with_child(code.clone())?;
+        if child_idx > u16::MAX as usize {
+            return Err(runtime_error_fmt!("too many child functions"));
+        }
+
+        let captureds = __capture_collect_sort(code.captured_iter(), Some(code.captured_count()))?;
+        let n = Self::count_u16(captureds.len())?;
+
+        let start = self.next_temp();
+        for (name, _idx) in captureds.as_slice() {
+            let t = self.push_temp()?;
+            if self.builder.has_local(name) {
+                let r = self.local(name)?;
+                self.move_(t, r)?;
+            } else if self.builder.has_captured(name) {
+                let idx = self.builder.with_captured(name)?;
+                self.emit(RegOp::GetCapture(t, idx))?;
+            } else {
+                return Err(runtime_error_fmt!(
+                    "invalid captured var: {}",
+                    name.as_str()
+                ));
+            }
+        }
+
+        self.emit(RegOp::NewClosure(dst, child_idx as u16, start, n))?;
+        Ok(())
+    }
+
+    /// 临时值放到局部变量之后，生成 RScriptCode。
+    fn finish(mut self) -> Result<Ref<RScriptCode>, Error> {
+        let local_count = self.builder.local_count();
+        let register_count = local_count + self.max_temps;
+        if register_count > Reg::MAX as usize {
+            return Err(runtime_error_fmt!("too many registers"));
+        }
+
+        let map = |r: Reg| {
+            if r & TEMP != 0 {
+                (local_count + (r & !TEMP) as usize) as Reg
+            } else {
+                r
+            }
+        };
+        for op in self.ops.as_slice_mut() {
+            *op = op.map_regs(map);
+        }
+
+        self.builder.build_register(self.ops, register_count)
+    }
+}
+
+/// 以寄存器指令生成函数体，返回值与 ast::_function_body_as_code 相同。
+pub(crate) fn function_as_code(
+    parent_builder: &mut ScriptCodeBuilder,
+    self_name: Option<&Ref<RString>>,
+    paramets: &Array<Ref<RString>>,
+    body: &Ref<RAst>,
+) -> Result<(Ref<RScriptCode>, Option<u32>), Error> {
+    let mut builder = ScriptCodeBuilder::new(Some(parent_builder))?;
+    for name in paramets.as_slice() {
+        builder.with_paramet(name)?;
+    }
+
+    let mut gen = RegCodegen::new(builder);
+    let r = gen.expr(body)?;
+    gen.emit(RegOp::Return(r))?;
+
+    let capture_self_idx = match self_name {
+        Some(name) if gen.builder.has_captured(name) => Some(gen.builder.with_captured(name)?),
+        _ => None,
+    };
+
+    Ok((gen.finish()?, capture_self_idx))
+}
+
+pub(crate) fn program_as_code(
+    stats: &Array<Ref<RAst>>,
+    expr: &Option<Ref<RAst>>,
+) -> Result<Ref<RScriptCode>, Error> {
+    let mut gen = RegCodegen::new(ScriptCodeBuilder::new(None)?);
+
+    for stat in stats.as_slice() {
+        gen.stat(stat)?;
+    }
+    let r = if let Some(expr) = expr {
+        gen.expr(expr)?
+    } else {
+        let t = gen.push_temp()?;
+        gen.emit(RegOp::LoadNull(t))?;
+        t
+    };
+    gen.emit(RegOp::Return(r))?;
+
+    gen.finish()
+}
+
+// 函数返回或出错时丢弃窗口中的值。
+struct Window(usize);
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        runtime().stack_mut().truncate(self.0);
+    }
+}
+
+/// 若 value 是寄存器指令的脚本函数，则返回该函数。
+fn as_register_function(value: &RValue) -> Option<Ref<RFunction>> {
+    if !value.is_type(function_type()) {
+        return None;
+    }
+    let func = unsafe { value.cast_ref::<RFunction>() };
+    match func.get_code() {
+        Some(code) if code.is_register() => Some(func.clone()),
+        _ => None,
+    }
+}
+
+/// 执行寄存器指令的脚本函数。
+///
+/// 与栈指令不同，调用其他脚本函数时总是在 Rust 栈上递归，
+/// 所以在协程中无法越过寄存器指令的函数挂起。
+pub(crate) fn eval_register_function(
+    callee: &Ref<RFunction>,
+    this_value: &RValue,
+    args: &[RValue],
+) -> Result<RValue, Error> {
+    let stack = runtime().stack_mut();
+    let base = stack.len();
+    let _window = Window(base);
+
+    stack.ensure(args.len())?;
+    for v in args {
+        stack.push(v.clone())?;
+    }
+
+    let mut callee = callee.clone();
+    let mut this_value = this_value.clone();
+    loop {
+        let code = callee
+            .get_code()
+            .ok_or_else(|| runtime_error_fmt!("invalid function type"))?;
+        let captured = callee
+            .get_captured()
+            .ok_or_else(|| runtime_error_fmt!("invalid function type"))?;
+
+        // 窗口底部是参数，其后补足局部变量与临时值。
+        // TODO: 收集剩余参数到数组。
+        let register_count = code.register_count();
+        let argc = (stack.len() - base).min(code.paramet_count() as usize);
+        stack.truncate(base + argc);
+        stack.ensure(register_count.saturating_sub(argc))?;
+        while stack.len() < base + register_count {
+            stack.push(null().cast_value())?;
+        }
+
+        match run(&callee, &code, captured.as_slice(), &this_value, base)? {
+            Some(ret) => return Ok(ret),
+            None => {
+                // 尾调用，新的被调用者与参数已放入窗口。
+                let func = as_register_function(&stack.as_slice()[base])
+                    .ok_or_else(|| runtime_error_fmt!("invalid tail call"))?;
+                let argc = stack.len() - base - 1;
+                let slice = stack.as_slice_mut();
+                for i in 0..argc {
+                    slice.swap(base + i, base + i + 1);
+                }
+                stack.truncate(base + argc);
+
+                runtime()._tail_call(func.cast_value_ref());
+                callee = func;
+                this_value = null().cast_value();
+            }
+        }
+    }
+}
+
+/// 执行到 Return 时返回 Some，执行到可以复用窗口的 TailCall 时返回 None，
+/// 此时窗口中依次是新的被调用者与参数。
+fn run(
+    callee: &Ref<RFunction>,
+    code: &Ref<RScriptCode>,
+    captured: &[RValue],
+    this_value: &RValue,
+    base: usize,
+) -> Result<Option<RValue>, Error> {
+    use RegOp::*;
+
+    let stack = runtime().stack_mut();
+    let null_value = null().cast_value();
+    let ops = code.reg_opcode();
+
+    macro_rules! reg {
+        ($r:expr) => {
+            &stack.as_slice()[base + $r as usize]
+        };
+    }
+    macro_rules! regs {
+        ($start:expr, $count:expr) => {
+            &stack.as_slice()[base + $start as usize..base + $start as usize + $count as usize]
+        };
+    }
+    macro_rules! set {
+        ($r:expr, $v:expr) => {{
+            let v = $v;
+            stack.set(base + $r as usize, v);
+        }};
+    }
+
+    let jump = |ip: usize, off: i32| (ip as isize + off as isize) as usize;
+
+    let mut ip: usize = 0;
+    loop {
+        let mut next = ip + 1;
+
+        match ops[ip] {
+            Move(dst, src) => set!(dst, reg!(src).clone()),
+            LoadNull(dst) => set!(dst, null_value.clone()),
+            LoadInt(dst, n) => set!(dst, RInt::new(n as Int)?.cast_value()),
+            LoadConstStr(dst, idx) => {
+                let s = get_const_str(code, idx as usize)?;
+                set!(dst, s.cast_value())
+            }
+            LoadConstNum(dst, idx) => {
+                let v = code
+                    .get_const_number(idx as usize)
+                    .ok_or_else(|| runtime_error_fmt!("invalid const number index"))?;
+                set!(dst, v)
+            }
+            LoadThis(dst) => set!(dst, this_value.clone()),
+            NewTuple(dst, start, count) => set!(dst, opfunc::new_tuple(regs!(start, count))?),
+            NewArray(dst, start, count) => set!(dst, opfunc::new_array(regs!(start, count))?),
+            NewMap(dst, start, count) => {
+                set!(dst, opfunc::new_map(regs!(start, count as usize * 2))?)
+            }
+            NewClosure(dst, child, start, count) => {
+                let caps = opfunc::new_array(regs!(start, count))?;
+                set!(dst, opfunc::new_closure(callee, child as u32, caps)?)
+            }
+            GetCapture(dst, idx) => {
+                let v = captured
+                    .get(idx as usize)
+                    .cloned()
+                    .unwrap_or(null_value.clone());
+                set!(dst, v)
+            }
+            SetCapture(closure, idx, src) => {
+                opfunc::set_capture(reg!(closure).clone(), idx, reg!(src).clone())?;
+            }
+            GetGlobal(dst, idx) => {
+                let name = get_const_str(code, idx as usize)?;
+                let value = get_global(&name).ok_or_else(|| {
+                    runtime_error_fmt!("field \"{}\" does not exist in Global", name.as_str(),)
+                })?;
+                set!(dst, value)
+            }
+            GetAttr(dst, target, idx) => {
+                let v = cached_get_attr(code, ip, idx as usize, reg!(target))?;
+                set!(dst, v)
+            }
+            SetAttr(target, idx, src) => {
+                let name = get_const_str(code, idx as usize)?;
+                value_set_attr(reg!(target), &name, reg!(src))?;
+            }
+            GetItem(dst, target, index) => {
+                let v = value_get_item(reg!(target), reg!(index))?;
+                set!(dst, v)
+            }
+            SetItem(target, index, src) => {
+                value_set_item(reg!(target), reg!(index), reg!(src))?;
+            }
+            Arith(op, dst, a, b) => {
+                let v = value_binary_op(op, reg!(a), reg!(b))?;
+                set!(dst, v)
+            }
+            Cmp(op, dst, a, b) => {
+                let (a, b) = (reg!(a), reg!(b));
+                let v = match op {
+                    CmpOp::Cmp => opfunc::cmp(a, b)?,
+                    CmpOp::Eq => opfunc::eq(a, b)?,
+                    CmpOp::Ne => opfunc::ne(a, b)?,
+                    CmpOp::Lt => opfunc::lt(a, b)?,
+                    CmpOp::Le => opfunc::le(a, b)?,
+                    CmpOp::Gt => opfunc::gt(a, b)?,
+                    CmpOp::Ge => opfunc::ge(a, b)?,
+                };
+                set!(dst, v)
+            }
+            Unary(op, dst, src) => {
+                let v = value_unary_op(op, reg!(src))?;
+                set!(dst, v)
+            }
+            Iter(dst, src) => {
+                let v = value_iter(reg!(src))?;
+                set!(dst, v)
+            }
+            IfFalse(src, off) => {
+                // 与栈指令的 IfFalse 相同。
+                let v = reg!(src);
+                let is_null = v.is_type(null_type());
+                let is_true = v.is_type(bool_type()) && unsafe { v.cast_ref::<RBool>().as_bool() };
+                if !(is_null || is_true) {
+                    next = jump(ip, off);
+                }
+            }
+            Jmp(off) => next = jump(ip, off),
+            IterNext(iter, dst, off) => {
+                let v = value_next(reg!(iter))?;
+                if let Some(inner_v) = v.value() {
+                    set!(dst, inner_v.clone())
+                } else {
+                    next = jump(ip, off);
+                }
+            }
+            Call(b, argc) => {
+                runtime().safepoint()?;
+                let l = regs!(b, argc as usize + 1);
+                let ret = value_call_with_this(&l[0], &null_value, &l[1..])?;
+                set!(b, ret)
+            }
+            CallMethod(b, idx, argc) => {
+                runtime().safepoint()?;
+                let l = regs!(b, argc as usize + 1);
+                let method = cached_get_method(code, ip, idx as usize, &l[0])?;
+                let ret = value_call_with_this(&method, &l[0], &l[1..])?;
+                set!(b, ret)
+            }
+            CallAttr(b, idx, argc) => {
+                runtime().safepoint()?;
+                let l = regs!(b, argc as usize + 1);
+                let func = cached_get_attr(code, ip, idx as usize, &l[0])?;
+                let ret = value_call_with_this(&func, &l[0], &l[1..])?;
+                set!(b, ret)
+            }
+            TailCall(b, argc) => {
+                runtime().safepoint()?;
+                if as_register_function(reg!(b)).is_some() {
+                    let start = base + b as usize;
+                    let slice = stack.as_slice_mut();
+                    for i in 0..argc as usize + 1 {
+                        slice.swap(base + i, start + i);
+                    }
+                    stack.truncate(base + argc as usize + 1);
+                    return Ok(None);
+                }
+                let l = regs!(b, argc as usize + 1);
+                let ret = value_call_with_this(&l[0], &null_value, &l[1..])?;
+                set!(b, ret)
+            }
+            Return(src) => return Ok(Some(reg!(src).clone())),
+        }
+
+        if next >= ops.len() {
+            Err(runtime_error_fmt!("exit without a instruction"))?
+        }
+        if next <= ip {
+            runtime().safepoint()?;
+        }
+        ip = next;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::{eval, initialize, set_backend, Backend};
+    use crate::test_util::{allocator, loader};
+    use crate::value::value_str;
+
+    const SCRIPTS: &[(&str, &str)] = &[
+        ("fib", include_str!("../benches/scripts/fib.rol")),
+        ("loops", include_str!("../benches/scripts/loops.rol")),
+        ("strings", include_str!("../benches/scripts/strings.rol")),
+        ("maps", include_str!("../benches/scripts/maps.rol")),
+    ];
+
+    fn eval_str(source: &str, backend: Backend) -> String {
+        set_backend(backend);
+        let v = eval(source).unwrap();
+        value_str(&v).unwrap().as_str().to_string()
+    }
+
+    #[test]
+    fn test_register_matches_stack() {
+        initialize(allocator(), loader()).unwrap();
+
+        for (name, source) in SCRIPTS {
+            let stack = eval_str(source, Backend::Stack);
+            let register = eval_str(source, Backend::Register);
+            assert_eq!(stack, register, "{}", name);
+        }
+        assert_eq!(eval_str(SCRIPTS[0].1, Backend::Register), "75025");
+        set_backend(Backend::Stack);
+    }
+}
@@ -1,24 +1,35 @@
+use core::cell::Cell;
+use core::fmt::Display;
+use core::fmt::{Formatter, Result as FmtResult};
+use core::marker::PhantomData;
 use core::mem::size_of;
-use core::mem::MaybeUninit;
-use core::ptr::NonNull;
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::alloc::Allocator;
 use crate::collections::*;
+use crate::stack::VmStack;
 
 use crate::error::*;
+use crate::runtime_error_fmt;
 
 use crate::ast::ast_as_code;
 use crate::lexical::Lexical;
 use crate::parser::Parser;
 
 use crate::array::*;
+use crate::dyn_::{_has_finalizer, _run_finalizer};
 use crate::function::*;
+use crate::heap::HeapSnapshot;
 use crate::module::*;
-use crate::number::*;
 use crate::script_code::*;
 use crate::string::*;
 use crate::type_::*;
 use crate::value::*;
+use crate::weak::*;
 
 use crate::builtin::*;
 
@@ -39,7 +50,9 @@ pub trait Visitor {
     fn visit(&mut self, value: NonNull<GcHeader>);
 
     fn visit_value(&mut self, value: &RValue) {
-        self.visit(value.as_nonnull_ptr())
+        if !value.is_immediate() {
+            self.visit(value.as_nonnull_ptr())
+        }
     }
 
     fn visit_ptr(&mut self, value_ptr: NonNull<GcHeader>) {
@@ -56,95 +69,156 @@ where
     }
 }
 
+/// Int 改为直接存放在 Ref 中之后不再需要小整数池，保留这两个常量只是为了兼容。
+#[deprecated(note = "Int values no longer allocate, there is no small integer pool")]
 pub const SMALL_INTEGER_BEG: isize = -256;
+#[deprecated(note = "Int values no longer allocate, there is no small integer pool")]
 pub const SMALL_INTEGER_END: isize = 512;
 
-pub(crate) static mut _RUNTIME_: MaybeUninit<Runtime> = MaybeUninit::uninit();
-pub(crate) static mut _RUNTIME_IS_INITIALIZED_: bool = false;
+/// 默认为脚本调用预留的 Rust 栈空间，从最外层的调用开始计算。
+/// std::thread 默认的栈为 2 MiB，余下的 512 KiB 留给宿主自身与最后一层调用。
+///
+/// 每层脚本调用都会在 Rust 栈上递归数次，实测栈式指令每层在优化构建下约 4 KiB，
+/// 未优化构建下约 52 KiB；寄存器指令分别约 1.3 KiB 与 16 KiB。
+/// 因此调用深度不按固定的层数限制栈，而是在每次调用时检查已经使用的栈。
+pub const DEFAULT_NATIVE_STACK_BUDGET: usize = 3 << 19;
+
+/// 默认的最大调用深度。
+/// 调用同时受 Rust 栈预算的限制，见 DEFAULT_NATIVE_STACK_BUDGET；
+/// 协程中的脚本调用不占用 Rust 栈，只受该值限制。
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// 值栈的容量（可容纳的值的个数），所有脚本帧的参数、局部变量与临时值都在其中。
+pub const DEFAULT_STACK_SIZE: usize = 1 << 16;
+
+/// 燃料耗尽时调用的钩子。
+/// 返回 Some(n) 时补充 n 单位燃料并继续执行，返回 None 或 Some(0) 时以 Error::OutOfFuel 停止。
+/// 钩子中不能执行脚本。
+pub type FuelHook = fn() -> Option<u64>;
+
+/// 用于从其他线程中断正在执行的脚本，由 Runtime::interrupt_handle 获得。
+#[derive(Clone)]
+pub struct InterruptHandle {
+    _flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// 请求中断。脚本在下一次调用函数或向回跳转时以 Error::Interrupted 停止。
+    /// 请求会一直保留到被响应为止：若此时没有脚本在执行（例如宿主正在编译下一段脚本），
+    /// 之后开始执行的脚本会被中断。不再需要的请求可以用 Runtime::clear_interrupt 丢弃。
+    pub fn interrupt(&self) {
+        self._flag.store(true, Ordering::Relaxed);
+    }
+}
+
+thread_local! {
+    // 当前线程正在使用的 Runtime，见 Runtime::enter。
+    static _CURRENT_RUNTIME_: Cell<*mut Runtime> = const { Cell::new(null_mut()) };
+    // initialize 为当前线程创建的 Runtime，由 finalize 销毁。
+    static _DEFAULT_RUNTIME_: Cell<*mut Runtime> = const { Cell::new(null_mut()) };
+}
 
+/// 当前线程的 Runtime。
+///
+/// # Panics
+///
+/// 当前线程没有 Runtime 时 panic，见 is_initialized。
 #[inline]
 pub fn runtime() -> &'static mut Runtime {
-    unsafe { _RUNTIME_.assume_init_mut() }
+    let ptr = _CURRENT_RUNTIME_.with(|current| current.get());
+    if ptr.is_null() {
+        _no_runtime();
+    }
+    unsafe { &mut *ptr }
+}
+
+#[cold]
+#[inline(never)]
+fn _no_runtime() -> ! {
+    panic!("no runtime on this thread, call initialize or Runtime::enter first")
 }
 
+/// 当前线程是否有 Runtime（由 initialize 创建，或由 Runtime::enter 设置）。
 #[inline]
 pub fn is_initialized() -> bool {
-    unsafe { _RUNTIME_IS_INITIALIZED_ }
+    !_CURRENT_RUNTIME_.with(|current| current.get()).is_null()
 }
 
+/// 为当前线程创建默认的 Runtime 并设为当前的 Runtime，已经创建过时什么都不做。
+/// 需要多个 Runtime 时使用 Runtime::new。
 pub fn initialize(
     allocator: &'static dyn Allocator,
     loader: &'static mut dyn Loader,
 ) -> Result<(), Error> {
-    unsafe {
-        if !_RUNTIME_IS_INITIALIZED_ {
-            _initialize(allocator, loader)?;
-        }
-        Ok(())
-    }
-}
-
-pub fn finalize() {
-    unsafe {
-        if _RUNTIME_IS_INITIALIZED_ {
-            _RUNTIME_.assume_init_drop();
-            _RUNTIME_IS_INITIALIZED_ = false;
-        }
-    }
+    initialize_with_gc_config(allocator, loader, GcConfig::default())
 }
 
-fn _initialize(
+pub fn initialize_with_gc_config(
     allocator: &'static dyn Allocator,
     loader: &'static mut dyn Loader,
+    gc_config: GcConfig,
 ) -> Result<(), Error> {
-    unsafe {
-        let runtime = Runtime::new(allocator, loader)?;
-        _RUNTIME_.write(runtime);
+    if _DEFAULT_RUNTIME_.with(|default| default.get()).is_null() {
+        let runtime = Box::into_raw(Runtime::new_with_gc_config(allocator, loader, gc_config)?);
+        _DEFAULT_RUNTIME_.with(|default| default.set(runtime));
+        _CURRENT_RUNTIME_.with(|current| current.set(runtime));
     }
+    Ok(())
+}
 
-    unsafe {
-        _create_type_and_string_type()?;
-        _create_builtin_types()?;
-
-        _init_builtin_types()?;
-
-        _init_builtin_values()?;
-
-        _init_integer_pool()?;
-
-        _init_builtin_global()?;
-
-        _RUNTIME_IS_INITIALIZED_ = true;
+/// 销毁 initialize 创建的 Runtime，释放它的全部对象，之后不能再使用其中的任何值。
+pub fn finalize() {
+    let runtime = _DEFAULT_RUNTIME_.with(|default| default.replace(null_mut()));
+    if !runtime.is_null() {
+        _CURRENT_RUNTIME_.with(|current| {
+            if current.get() == runtime {
+                current.set(null_mut());
+            }
+        });
+        drop(unsafe { Box::from_raw(runtime) });
     }
+}
 
-    Ok(())
+/// 与 finalize 相同，但在销毁 Runtime 之前报告仍然存活的对象。
+///
+/// 先丢弃全局变量与模块缓存并完整地回收一次，此时除了内建类型、内建值与字符串池
+/// 能到达的对象之外，剩下的对象都被 Rust 中的引用持有，通常意味着宿主或原生类型
+/// 没有释放引用。回收失败时仍然销毁 Runtime 并返回错误。
+pub fn finalize_with_leak_report() -> Result<HeapSnapshot, Error> {
+    let default = _DEFAULT_RUNTIME_.with(|default| default.get());
+    if default.is_null() {
+        return Ok(HeapSnapshot::default());
+    }
+    let report = unsafe { &mut *default }.with(|| runtime().leak_report());
+    finalize();
+    report
 }
 
-pub(crate) fn _init_integer_pool() -> Result<(), Error> {
-    let runtime = runtime();
-    runtime
-        ._small_integer_pool
-        .reserve((SMALL_INTEGER_END - SMALL_INTEGER_BEG).max(0) as usize)
-        .map_err(|_| Error::new_outofmemory())?;
+/// Runtime::enter 的返回值，drop 时恢复之前的 Runtime。
+pub struct RuntimeScope<'a> {
+    _prev: *mut Runtime,
+    _marker: PhantomData<&'a mut Runtime>,
+}
 
-    for n in SMALL_INTEGER_BEG..SMALL_INTEGER_END {
-        let v = _new_int_value(n)?;
-        runtime
-            ._small_integer_pool
-            .push(v)
-            .map_err(|_| Error::new_outofmemory())?;
+impl Drop for RuntimeScope<'_> {
+    fn drop(&mut self) {
+        _CURRENT_RUNTIME_.with(|current| current.set(self._prev));
     }
-    Ok(())
 }
 
 struct Frame {
     _callee: RValue,
+    // 因尾调用而被省略的帧数。
+    _elided: usize,
 }
 
 #[allow(dead_code)]
 impl Frame {
     pub(self) fn new(callee: RValue) -> Self {
-        Self { _callee: callee }
+        Self {
+            _callee: callee,
+            _elided: 0,
+        }
     }
 
     pub fn get_callee(&self) -> RValue {
@@ -152,15 +226,127 @@ impl Frame {
     }
 }
 
+#[inline(never)]
+fn _native_stack_pointer() -> usize {
+    let marker = 0u8;
+    core::hint::black_box(&marker) as *const u8 as usize
+}
+
+struct Traceback<'a>(&'a [Frame]);
+
+impl<'a> Display for Traceback<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("traceback (most recent call last):")?;
+        for frame in self.0 {
+            if frame._elided != 0 {
+                f.write_fmt(format_args!(
+                    "\n  ... {} frame(s) elided by tail call",
+                    frame._elided
+                ))?;
+            }
+            f.write_fmt(format_args!("\n  {:?}", frame._callee))?;
+        }
+        Ok(())
+    }
+}
+
+/// 自动回收的触发策略。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GcConfig {
+    /// gc 堆超过该字节数时进行第一次自动回收，默认 1 MiB。
+    pub initial_threshold: usize,
+    /// 之后 gc 堆超过上一次回收前大小的该倍数时再次回收，默认 8。
+    pub growth_factor: usize,
+    /// 为 true 时不自动回收，只在调用 run_gc 时回收。
+    /// 超过堆上限或分配器分配失败时仍会回收。
+    pub manual: bool,
+    /// 年轻代的对象在这么多次回收中存活后晋升到老年代，默认 2。
+    pub promote_after: usize,
+    /// 自动回收通常只扫描年轻代，每这么多次中有一次同时扫描老年代，默认 8。
+    /// 设为 1 时总是完整回收。
+    pub full_every: usize,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            initial_threshold: 1024 * 1024,
+            growth_factor: 8,
+            manual: false,
+            promote_after: 2,
+            full_every: 8,
+        }
+    }
+}
+
+/// gc 的统计信息，见 gc_stats。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// 当前 gc 对象的数量。
+    pub object_count: usize,
+    /// 当前 gc 堆的字节数。
+    pub heap_size: usize,
+    /// 当前老年代对象的数量。
+    pub old_object_count: usize,
+    /// 已进行的回收次数。
+    pub collections: u64,
+    /// 其中扫描了老年代的完整回收次数。
+    pub full_collections: u64,
+    /// 所有回收释放的对象数量。
+    pub freed_objects: u64,
+    /// 所有回收释放的字节数。
+    pub freed_bytes: u64,
+    /// 所有回收花费的时间。
+    pub gc_time: Duration,
+}
+
+/// 增量回收所处的阶段，见 Runtime::gc_step。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum GcPhase {
+    Idle,
+    // 记录候选对象的引用数。
+    Init,
+    // 扣除候选对象之间的引用。
+    Subtract,
+    // 从仍有外部引用的候选对象开始标记可达的对象。
+    Scan,
+    // 析构并释放确认不可达的对象。
+    Free,
+}
+
 struct GcInfo {
     pub(self) _current_obj_count: usize,
     pub(self) _curent_mem_size: usize,
     pub(self) _last_obj_count: usize,
     pub(self) _last_mem_size: usize,
+    pub(self) _collections: u64,
+    pub(self) _full_collections: u64,
+    // 上一次完整回收之后年轻代回收的次数。
+    pub(self) _young_collections: usize,
+    pub(self) _freed_objects: u64,
+    pub(self) _freed_bytes: u64,
+    pub(self) _gc_time: Duration,
+    // 年轻代，新分配的对象在这里。
     pub(self) _gc_objs: ListBase,
+    pub(self) _old_objs: ListBase,
     pub(self) _tmp_gc_objs: ListBase,
     pub(self) _to_be_released_objs: ListBase,
     pub(self) _has_been_released_objs: ListBase,
+    pub(self) _phase: GcPhase,
+    pub(self) _inc_objs: ListBase,
+    pub(self) _inc_reached: ListBase,
+    pub(self) _inc_unreached: ListBase,
+    // 当前阶段最后处理的节点。
+    pub(self) _inc_cursor: Option<NonNull<ListNodeBase>>,
+    pub(self) _inc_start_mem_size: usize,
+    pub(self) _inc_start_obj_count: usize,
+    // 全部 WeakRef 与 WeakMap，gc 在析构对象之前清理它们。
+    pub(self) _weak_refs: ListBase,
+    pub(self) _weak_maps: ListBase,
+    // 终结器复活的对象。
+    pub(self) _resurrected_objs: ListBase,
+    // 正在运行终结器，此时不进行回收。
+    pub(self) _finalizing: bool,
 }
 
 impl GcInfo {
@@ -170,21 +356,66 @@ impl GcInfo {
         addr_of_mut!(ptr.as_mut()._curent_mem_size).write(0);
         addr_of_mut!(ptr.as_mut()._last_obj_count).write(0);
         addr_of_mut!(ptr.as_mut()._last_mem_size).write(0);
+        addr_of_mut!(ptr.as_mut()._collections).write(0);
+        addr_of_mut!(ptr.as_mut()._full_collections).write(0);
+        addr_of_mut!(ptr.as_mut()._young_collections).write(0);
+        addr_of_mut!(ptr.as_mut()._freed_objects).write(0);
+        addr_of_mut!(ptr.as_mut()._freed_bytes).write(0);
+        addr_of_mut!(ptr.as_mut()._gc_time).write(Duration::ZERO);
         ListBase::init(nonnull_of!(ptr.as_mut()._gc_objs));
+        ListBase::init(nonnull_of!(ptr.as_mut()._old_objs));
         ListBase::init(nonnull_of!(ptr.as_mut()._tmp_gc_objs));
         ListBase::init(nonnull_of!(ptr.as_mut()._to_be_released_objs));
         ListBase::init(nonnull_of!(ptr.as_mut()._has_been_released_objs));
+        addr_of_mut!(ptr.as_mut()._phase).write(GcPhase::Idle);
+        ListBase::init(nonnull_of!(ptr.as_mut()._inc_objs));
+        ListBase::init(nonnull_of!(ptr.as_mut()._inc_reached));
+        ListBase::init(nonnull_of!(ptr.as_mut()._inc_unreached));
+        addr_of_mut!(ptr.as_mut()._inc_cursor).write(None);
+        addr_of_mut!(ptr.as_mut()._inc_start_mem_size).write(0);
+        addr_of_mut!(ptr.as_mut()._inc_start_obj_count).write(0);
+        ListBase::init(nonnull_of!(ptr.as_mut()._weak_refs));
+        ListBase::init(nonnull_of!(ptr.as_mut()._weak_maps));
+        ListBase::init(nonnull_of!(ptr.as_mut()._resurrected_objs));
+        addr_of_mut!(ptr.as_mut()._finalizing).write(false);
     }
 }
 
+/// 脚本函数所生成的指令。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// 基于栈的指令。
+    Stack,
+    /// 基于寄存器的指令。
+    /// 包含 yield 或类型、枚举定义的函数仍生成栈指令；
+    /// 寄存器指令的函数中无法挂起协程。
+    Register,
+}
+
 pub struct Runtime {
     _allocator: &'static dyn Allocator,
     _frames: Array<Frame>,
+    _stack: VmStack,
+    _max_call_depth: usize,
+    _native_stack_budget: usize,
+    // 最外层调用开始时的栈地址。
+    _native_stack_base: usize,
+    _fuel: Option<u64>,
+    _fuel_hook: Option<FuelHook>,
+    _interrupt: Arc<AtomicBool>,
+    _heap_limit: Option<usize>,
+    _gc_config: GcConfig,
+    _optimize: bool,
+    _backend: Backend,
+    _type_version: u64,
 
     _gc_info: NonNull<GcInfo>,
 
+    _builtins: Builtins,
+    // 内建类型与内建值都已创建。
+    _ready: bool,
+
     _string_pool: StringPool,
-    _small_integer_pool: Array<Ref<RInt>>,
 
     _loader: &'static mut dyn Loader,
     _modules: StringMap<Ref<RModule>>,
@@ -195,9 +426,58 @@ pub struct Runtime {
 type CResult<T> = Result<T, Error>;
 
 impl Runtime {
-    pub(crate) fn new(
+    /// 创建一个独立的 Runtime，它有自己的全局变量、模块缓存与内建类型。
+    ///
+    /// 不带 Runtime 参数的函数（eval、RString::new 等）都作用于当前线程的 Runtime，
+    /// 使用前需要通过 enter 或 with 把它设为当前的 Runtime。
+    /// 一个 Runtime 中创建的值不能在另一个 Runtime 中使用，也不能在它被销毁之后使用。
+    pub fn new(
+        allocator: &'static dyn Allocator,
+        loader: &'static mut dyn Loader,
+    ) -> CResult<Box<Self>> {
+        Self::new_with_gc_config(allocator, loader, GcConfig::default())
+    }
+
+    pub fn new_with_gc_config(
+        allocator: &'static dyn Allocator,
+        loader: &'static mut dyn Loader,
+        gc_config: GcConfig,
+    ) -> CResult<Box<Self>> {
+        let mut rt = Box::new(Self::_new(allocator, loader, gc_config)?);
+        rt.with(|| -> CResult<()> {
+            _create_type_and_string_type()?;
+            _create_builtin_types()?;
+
+            _init_builtin_types()?;
+
+            _init_builtin_values()?;
+
+            runtime()._ready = true;
+
+            _init_builtin_global()
+        })?;
+        Ok(rt)
+    }
+
+    /// 把 self 设为当前线程的 Runtime，直到返回的 RuntimeScope 被 drop。
+    pub fn enter(&mut self) -> RuntimeScope<'_> {
+        let prev = _CURRENT_RUNTIME_.with(|current| current.replace(self));
+        RuntimeScope {
+            _prev: prev,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 以 self 为当前的 Runtime 调用 f。
+    pub fn with<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let _scope = self.enter();
+        f()
+    }
+
+    fn _new(
         allocator: &'static dyn Allocator,
         loader: &'static mut dyn Loader,
+        gc_config: GcConfig,
     ) -> CResult<Self> {
         let gc_info = unsafe {
             let gc_info_ptr = allocator.alloc_block(size_of::<GcInfo>()).cast::<GcInfo>();
@@ -217,9 +497,23 @@ impl Runtime {
 
             _gc_info: gc_info,
 
+            _builtins: Builtins::new(),
+            _ready: false,
+
             _string_pool: StringPool::new(allocator),
-            _small_integer_pool: Array::new(allocator),
             _frames: Array::new(allocator),
+            _stack: VmStack::new(allocator, DEFAULT_STACK_SIZE)?,
+            _max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            _native_stack_budget: DEFAULT_NATIVE_STACK_BUDGET,
+            _native_stack_base: 0,
+            _fuel: None,
+            _fuel_hook: None,
+            _interrupt: Arc::new(AtomicBool::new(false)),
+            _heap_limit: None,
+            _gc_config: gc_config,
+            _optimize: true,
+            _backend: Backend::Stack,
+            _type_version: 0,
 
             _loader: loader,
             _modules: StringMap::new(allocator),
@@ -237,17 +531,223 @@ impl Runtime {
         this_value: &RValue,
         args: &[RValue],
     ) -> CResult<RValue> {
-        let frame = Frame::new(callee.clone());
-        if self._frames.push(frame).is_err() {
-            return Err(Error::OutOfMemory);
+        // 栈向低地址增长，所有支持的平台都是如此。
+        let sp = _native_stack_pointer();
+        if self._frames.len() == 0 {
+            self._native_stack_base = sp;
+        } else if self._native_stack_base.saturating_sub(sp) > self._native_stack_budget {
+            return Err(Error::new_recursion(self._frames.len()));
         }
 
+        self._push_frame(callee)?;
+
         let ret = _value_call_raw(callee, this_value, args);
 
-        self._frames.pop();
+        self._pop_frame();
 
         ret
     }
+
+    /// 记录一层调用，超过最大调用深度时返回 Error::Recursion。
+    /// 协程中的脚本调用不经过 _call，由协程直接记录。
+    pub(crate) fn _push_frame(&mut self, callee: &RValue) -> CResult<()> {
+        if self._frames.len() >= self._max_call_depth {
+            return Err(Error::new_recursion(self._max_call_depth));
+        }
+        self._frames
+            .push(Frame::new(callee.clone()))
+            .map_err(|_| Error::OutOfMemory)
+    }
+
+    #[inline]
+    pub(crate) fn _pop_frame(&mut self) {
+        self._frames.pop();
+    }
+
+    #[inline]
+    pub(crate) fn _call_depth(&self) -> usize {
+        self._frames.len()
+    }
+
+    /// 丢弃 depth 之上的调用记录。
+    pub(crate) fn _truncate_frames(&mut self, depth: usize) {
+        while self._frames.len() > depth {
+            self._frames.pop();
+        }
+    }
+
+    /// 尾调用复用了当前帧，把当前帧的被调用者替换为 callee，并记录被省略的帧。
+    pub(crate) fn _tail_call(&mut self, callee: &RValue) {
+        if let Some(frame) = self._frames.as_slice_mut().last_mut() {
+            frame._callee = callee.clone();
+            frame._elided += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn builtins(&self) -> &Builtins {
+        &self._builtins
+    }
+
+    pub(crate) fn builtins_mut(&mut self) -> &mut Builtins {
+        &mut self._builtins
+    }
+
+    #[inline]
+    pub(crate) fn stack_mut(&mut self) -> &mut VmStack {
+        &mut self._stack
+    }
+
+    pub fn max_call_depth(&self) -> usize {
+        self._max_call_depth
+    }
+
+    /// 设置最大调用深度，超过时调用会返回 Error::Recursion。
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self._max_call_depth = depth;
+    }
+
+    pub fn native_stack_budget(&self) -> usize {
+        self._native_stack_budget
+    }
+
+    /// 设置脚本调用可以使用的 Rust 栈的字节数，超过时调用会返回 Error::Recursion。
+    /// 应小于执行脚本的线程的栈大小，并留出宿主自身需要的空间。
+    pub fn set_native_stack_budget(&mut self, budget: usize) {
+        self._native_stack_budget = budget;
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self._fuel
+    }
+
+    /// 设置剩余的燃料，None 表示不限制（默认）。
+    /// 脚本每次调用函数以及每次向回跳转（循环的一次迭代）消耗 1 单位燃料，
+    /// 燃料耗尽时调用钩子，钩子不补充时返回 Error::OutOfFuel。
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self._fuel = fuel;
+    }
+
+    pub fn set_fuel_hook(&mut self, hook: Option<FuelHook>) {
+        self._fuel_hook = hook;
+    }
+
+    pub fn heap_limit(&self) -> Option<usize> {
+        self._heap_limit
+    }
+
+    /// 设置 gc 堆（所有 gc 对象）的字节数上限，None 表示不限制（默认）。
+    /// 分配会超过上限时先完整地回收一次，仍然超过则返回 Error::HeapLimit。
+    /// 集合的缓冲区等不属于 gc 堆，需要限制全部内存时使用 LimitedAllocator。
+    pub fn set_heap_limit(&mut self, limit: Option<usize>) {
+        self._heap_limit = limit;
+    }
+
+    /// gc 堆当前的字节数。
+    pub fn heap_size(&self) -> usize {
+        unsafe { self._gc_info.as_ref()._curent_mem_size }
+    }
+
+    pub fn gc_config(&self) -> GcConfig {
+        self._gc_config
+    }
+
+    pub fn set_gc_config(&mut self, config: GcConfig) {
+        self._gc_config = config;
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        let info = unsafe { self._gc_info.as_ref() };
+        GcStats {
+            object_count: info._current_obj_count,
+            heap_size: info._curent_mem_size,
+            old_object_count: info._old_objs.len(),
+            collections: info._collections,
+            full_collections: info._full_collections,
+            freed_objects: info._freed_objects,
+            freed_bytes: info._freed_bytes,
+            gc_time: info._gc_time,
+        }
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            _flag: self._interrupt.clone(),
+        }
+    }
+
+    /// 丢弃尚未响应的中断请求，例如宿主在一项任务结束后取消它的计时器时。
+    pub fn clear_interrupt(&mut self) {
+        self._interrupt.store(false, Ordering::Relaxed);
+    }
+
+    /// 脚本在调用函数与向回跳转时检查中断请求并消耗燃料。
+    #[inline]
+    pub(crate) fn safepoint(&mut self) -> CResult<()> {
+        if self._interrupt.load(Ordering::Relaxed) {
+            return self._interrupted();
+        }
+        self.consume_fuel()
+    }
+
+    // 响应之后清除请求，使 Runtime 可以继续使用。
+    #[cold]
+    fn _interrupted(&mut self) -> CResult<()> {
+        self._interrupt.store(false, Ordering::Relaxed);
+        Err(Error::new_interrupted())
+    }
+
+    #[inline]
+    fn consume_fuel(&mut self) -> CResult<()> {
+        match &mut self._fuel {
+            None => Ok(()),
+            Some(0) => self._refuel(),
+            Some(n) => {
+                *n -= 1;
+                Ok(())
+            }
+        }
+    }
+
+    #[cold]
+    fn _refuel(&mut self) -> CResult<()> {
+        match self._fuel_hook.and_then(|hook| hook()) {
+            Some(n) if n > 0 => {
+                self._fuel = Some(n - 1);
+                Ok(())
+            }
+            _ => Err(Error::new_outoffuel()),
+        }
+    }
+
+    pub fn optimize_enabled(&self) -> bool {
+        self._optimize
+    }
+
+    /// 是否在生成字节码后进行优化，默认开启。
+    pub fn set_optimize_enabled(&mut self, enabled: bool) {
+        self._optimize = enabled;
+    }
+
+    pub fn backend(&self) -> Backend {
+        self._backend
+    }
+
+    /// 设置之后编译的脚本所生成的指令，默认为 Backend::Stack。
+    pub fn set_backend(&mut self, backend: Backend) {
+        self._backend = backend;
+    }
+
+    /// 类型的属性每次改变都会得到一个新的版本号，用于使内联缓存失效。
+    /// 版本号全局唯一，类型被回收后即便地址被复用，版本号也不会相同。
+    pub(crate) fn next_type_version(&mut self) -> u64 {
+        self._type_version += 1;
+        self._type_version
+    }
+
+    pub fn traceback(&self) -> Result<Ref<RString>, Error> {
+        RString::format(format_args!("{}", Traceback(self._frames.as_slice())))
+    }
 }
 
 // 对象分配方法与gc
@@ -256,75 +756,213 @@ impl Runtime {
         unsafe { self._gc_info.as_mut() }
     }
 
-    fn _dec_ref(&mut self) {
-        fn _dec_ref_mark(mut value: NonNull<GcHeader>) {
+    // 在 _gc_objs 上进行试验删除，不可达的对象移动到 _tmp_gc_objs。
+    // 只扣除 _gc_objs 中对象之间的引用，其余对象（老年代）与 Rust 中的引用都视为外部引用，
+    // 因此只扫描年轻代时，被老年代引用的对象总是存活。
+    fn _find_cycles(&mut self) {
+        fn _subtract_mark(mut value: NonNull<GcHeader>) {
             unsafe {
                 let value_ref = value.as_mut();
-                debug_assert!(value_ref.ref_count() > 0);
-
-                value_ref.dec_ref();
+                if value_ref.gc_state() == GcState::Candidate {
+                    debug_assert!(value_ref.gc_refs() > 0);
+                    value_ref.set_gc_refs(value_ref.gc_refs() - 1);
+                }
+            }
+        }
 
-                if value_ref.mark() && value_ref.ref_count() == 0 {
-                    runtime().gc_info_mut()._gc_objs.remove(value);
-                    runtime().gc_info_mut()._tmp_gc_objs.insert_last(value);
+        fn _restore_mark(mut value: NonNull<GcHeader>) {
+            unsafe {
+                let value_ref = value.as_mut();
+                if value_ref.gc_state() == GcState::Unreached {
+                    value_ref.set_gc_state(GcState::Candidate);
+                    runtime().gc_info_mut()._tmp_gc_objs.remove(value);
+                    runtime().gc_info_mut()._gc_objs.insert_last(value);
                 }
             }
         }
 
+        let info = self.gc_info_mut();
         unsafe {
-            for node in self.gc_info_mut()._gc_objs.iter() {
+            for node in info._gc_objs.iter() {
                 let mut value = node.cast::<GcHeader>();
+                let value_ref = value.as_mut();
+                value_ref.set_gc_refs(value_ref.ref_count());
+                value_ref.set_gc_state(GcState::Candidate);
+            }
+
+            for node in info._gc_objs.iter() {
+                value_visit_ptr(&mut _subtract_mark, node.cast::<GcHeader>());
+            }
 
-                debug_assert!(!value.as_mut().mark());
+            for node in info._gc_objs.iter() {
+                let mut value = node.cast::<GcHeader>();
+                if value.as_ref().gc_refs() == 0 {
+                    value.as_mut().set_gc_state(GcState::Unreached);
+                    info._gc_objs.remove(value);
+                    info._tmp_gc_objs.insert_last(value);
+                }
+            }
 
-                value_visit_ptr(&mut _dec_ref_mark, value);
-                value.as_mut().set_mark(true);
+            // 剩下的对象仍有外部引用，恢复它们引用的对象。
+            // 被恢复的对象插入在 _gc_objs 的末尾，也需要遍历。
+            // WeakMap 只在键被恢复后才恢复对应的值，所以之后再补上这些值，直到没有新的对象被恢复。
+            let mut last = None;
+            loop {
+                let info = self.gc_info_mut();
+                let mut next = match last {
+                    None => info._gc_objs.first(),
+                    Some(node) => info._gc_objs.next_of(node),
+                };
+                while let Some(node) = next {
+                    let mut value = node.cast::<GcHeader>();
+                    value_visit_ptr(&mut _restore_mark, value);
+                    value.as_mut().set_gc_state(GcState::None);
+
+                    last = Some(node);
+                    next = self.gc_info_mut()._gc_objs.next_of(node);
+                }
 
-                if value.as_ref().ref_count() == 0 {
-                    self.gc_info_mut()._gc_objs.remove(value);
-                    self.gc_info_mut()._tmp_gc_objs.insert_last(value);
+                let info = self.gc_info_mut();
+                let len = info._gc_objs.len();
+                _visit_weak_map_values(&info._weak_maps, &mut _restore_mark);
+                if self.gc_info_mut()._gc_objs.len() == len {
+                    break;
                 }
             }
         }
     }
-    fn _scan(&mut self) {
-        fn _scan_1_mark(mut value: NonNull<GcHeader>) {
-            unsafe {
+
+    // 清理目标为垃圾的 WeakRef 与键为垃圾的 WeakMap 项，必须在析构任何对象之前进行。
+    fn _clear_weak(&mut self) -> Result<(), Error> {
+        let info = self.gc_info_mut();
+        _clear_weak_refs(&info._weak_refs);
+        _clear_weak_maps(&info._weak_maps)
+    }
+
+    pub(crate) fn add_weak_ref(&mut self, node: NonNull<ListNodeBase>) {
+        self.gc_info_mut()._weak_refs.insert_last(node);
+    }
+
+    pub(crate) fn remove_weak_ref(&mut self, node: NonNull<ListNodeBase>) {
+        self.gc_info_mut()._weak_refs.remove(node);
+    }
+
+    pub(crate) fn add_weak_map(&mut self, node: NonNull<ListNodeBase>) {
+        self.gc_info_mut()._weak_maps.insert_last(node);
+    }
+
+    pub(crate) fn remove_weak_map(&mut self, node: NonNull<ListNodeBase>) {
+        self.gc_info_mut()._weak_maps.remove(node);
+    }
+
+    // 年轻代中存活的对象年龄加一，达到 promote_after 的晋升到老年代。
+    fn _promote(&mut self) {
+        let promote_after = self._gc_config.promote_after;
+        let info = self.gc_info_mut();
+        unsafe {
+            for node in info._gc_objs.iter() {
+                let mut value = node.cast::<GcHeader>();
                 let value_ref = value.as_mut();
+                let age = value_ref.gc_age().saturating_add(1);
+                value_ref.set_gc_age(age);
+                if age as usize >= promote_after {
+                    info._gc_objs.remove(value);
+                    info._old_objs.insert_last(value);
+                }
+            }
+        }
+    }
 
-                value_ref.inc_ref();
+    // 对 _tmp_gc_objs 中有终结器且还没有运行过的对象调用终结器，此时这些对象都还没有被析构，
+    // 终结器可以访问同一个循环中的其它对象。每个对象的终结器最多运行一次，
+    // 出错时仍然运行其余的终结器，返回第一个错误。
+    // 终结器可能使垃圾对象重新可达，这些对象以及它们引用的对象会被移回年轻代而不被释放。
+    // 指向垃圾对象的弱引用在终结器运行之前已被清空，复活的对象也不会恢复。
+    fn _finalize(&mut self) -> Result<(), Error> {
+        let mut ret = Ok(());
+        let mut finalized = false;
+
+        self.gc_info_mut()._finalizing = true;
+        let mut next = self.gc_info_mut()._tmp_gc_objs.first();
+        while let Some(node) = next {
+            let mut value = node.cast::<GcHeader>();
+            unsafe {
+                if !value.as_ref().finalized() && _has_finalizer(value.as_ref()) {
+                    value.as_mut().set_finalized(true);
+                    finalized = true;
+
+                    let v = Ref::from_raw(value);
+                    let r = _run_finalizer(&v);
+                    if ret.is_ok() {
+                        ret = r;
+                    }
+                }
+                // 回收被禁止，终结器无法从 _tmp_gc_objs 中移除对象。
+                next = self.gc_info_mut()._tmp_gc_objs.next_of(node);
+            }
+        }
+        self.gc_info_mut()._finalizing = false;
 
-                if value_ref.ref_count() == 1 {
-                    runtime().gc_info_mut()._tmp_gc_objs.remove(value);
-                    runtime().gc_info_mut()._gc_objs.insert_last(value);
-                    value_ref.set_mark(false);
+        if finalized {
+            self._find_resurrected();
+        }
+        ret
+    }
+
+    // 在 _tmp_gc_objs 上重新进行试验删除，仍有外部引用的对象被复活。
+    fn _find_resurrected(&mut self) {
+        fn _subtract_mark(mut value: NonNull<GcHeader>) {
+            unsafe {
+                let value_ref = value.as_mut();
+                if value_ref.gc_state() == GcState::Unreached {
+                    debug_assert!(value_ref.gc_refs() > 0);
+                    value_ref.set_gc_refs(value_ref.gc_refs() - 1);
                 }
             }
         }
 
-        fn _scan_2_mark(mut value: NonNull<GcHeader>) {
+        fn _resurrect_mark(mut value: NonNull<GcHeader>) {
             unsafe {
+                let info = runtime().gc_info_mut();
                 let value_ref = value.as_mut();
-                value_ref.inc_ref();
+                if value_ref.gc_state() == GcState::Unreached {
+                    value_ref.set_gc_state(GcState::None);
+                    info._tmp_gc_objs.remove(value);
+                    info._resurrected_objs.insert_last(value);
+                }
             }
         }
 
+        let info = self.gc_info_mut();
         unsafe {
-            for node in self.gc_info_mut()._gc_objs.iter() {
+            for node in info._tmp_gc_objs.iter() {
                 let mut value = node.cast::<GcHeader>();
+                let value_ref = value.as_mut();
+                value_ref.set_gc_refs(value_ref.ref_count());
+            }
 
-                debug_assert!(value.as_mut().ref_count() > 0);
-
-                value.as_mut().set_mark(false);
+            for node in info._tmp_gc_objs.iter() {
+                value_visit_ptr(&mut _subtract_mark, node.cast::<GcHeader>());
+            }
 
-                value_visit_ptr(&mut _scan_1_mark, value);
+            for node in info._tmp_gc_objs.iter() {
+                let mut value = node.cast::<GcHeader>();
+                if value.as_ref().gc_refs() > 0 {
+                    value.as_mut().set_gc_state(GcState::None);
+                    info._tmp_gc_objs.remove(value);
+                    info._resurrected_objs.insert_last(value);
+                }
             }
 
-            for node in self.gc_info_mut()._tmp_gc_objs.iter() {
-                let value = node.cast::<GcHeader>();
-                value_visit_ptr(&mut _scan_2_mark, value);
+            let mut next = info._resurrected_objs.first();
+            while let Some(node) = next {
+                value_visit_ptr(&mut _resurrect_mark, node.cast::<GcHeader>());
+                next = self.gc_info_mut()._resurrected_objs.next_of(node);
             }
         }
+
+        let info = self.gc_info_mut();
+        info._gc_objs.append(&mut info._resurrected_objs);
     }
 
     fn _free_cycles(&mut self) -> Result<(), Error> {
@@ -358,60 +996,477 @@ impl Runtime {
     }
 
     fn run_gc(&mut self) -> Result<(), Error> {
-        #[cfg(debug_assertions)]
-        {
-            dbg!(self.gc_info_mut()._curent_mem_size);
-            dbg!(self.gc_info_mut()._current_obj_count);
-            dbg!(self.gc_info_mut()._last_mem_size);
-            dbg!(self.gc_info_mut()._last_obj_count);
+        let start = Instant::now();
+        let ret = self._run_gc(true);
+        self.gc_info_mut()._gc_time += start.elapsed();
+        ret
+    }
+
+    /// 只回收年轻代。有增量回收周期正在进行时改为完整回收。
+    fn run_young_gc(&mut self) -> Result<(), Error> {
+        if self.gc_info_mut()._phase != GcPhase::Idle {
+            return self.run_gc();
+        }
+
+        let start = Instant::now();
+        let ret = self._run_gc(false);
+        self.gc_info_mut()._gc_time += start.elapsed();
+        ret
+    }
+
+    // 自动回收，每 full_every 次中有一次完整回收。
+    fn _run_auto_gc(&mut self) -> Result<(), Error> {
+        let full_every = self._gc_config.full_every;
+        let info = self.gc_info_mut();
+        if info._young_collections + 1 >= full_every {
+            self.run_gc()
+        } else {
+            self.run_young_gc()
+        }
+    }
+
+    fn _run_gc(&mut self, full: bool) -> Result<(), Error> {
+        if self.gc_info_mut()._finalizing {
+            return Ok(());
         }
+        self._inc_abort()?;
 
         let last_mem_size = self.gc_info_mut()._curent_mem_size;
         let last_obj_ount = self.gc_info_mut()._current_obj_count;
 
-        self._dec_ref();
-        self._scan();
-        self._free_cycles()?;
+        if full {
+            let info = self.gc_info_mut();
+            info._gc_objs.append(&mut info._old_objs);
+        }
 
-        self.gc_info_mut()._last_mem_size = last_mem_size;
-        self.gc_info_mut()._last_obj_count = last_obj_ount;
+        self._find_cycles();
 
-        Ok(())
-    }
+        let info = self.gc_info_mut();
+        if full {
+            info._old_objs.append(&mut info._gc_objs);
+            info._young_collections = 0;
+            info._full_collections += 1;
+        } else {
+            self._promote();
+            self.gc_info_mut()._young_collections += 1;
+        }
 
-    fn free_gc_obj(&mut self, value: NonNull<GcHeader>) {
-        unsafe {
-            let align = size_of::<usize>();
-            let size = value.as_ref().block_size();
+        // 终结器可能为垃圾对象建立新的弱引用，运行之后再清理一次。
+        let ret = self._clear_weak().and_then(|_| {
+            let finalized = self._finalize();
+            self._clear_weak()?;
+            self._free_cycles().and(finalized)
+        });
 
-            GcHeader::drop_head(value);
+        let info = self.gc_info_mut();
+        info._last_mem_size = last_mem_size;
+        info._last_obj_count = last_obj_ount;
+        info._collections += 1;
 
-            self._allocator.free(value.as_ptr() as _, size, align);
+        ret
+    }
 
-            self.gc_info_mut()._curent_mem_size -= size;
-            self.gc_info_mut()._current_obj_count -= 1;
-        }
+    /// 进行一步增量回收，处理不超过 max_objects 个对象（至少为 1），
+    /// 没有正在进行的回收周期时开始一个新的周期。返回周期是否已在本次调用中结束。
+    ///
+    /// 周期开始时已存在的对象是候选对象，之后分配的对象留到下一个周期。
+    /// 两步之间脚本可以任意修改对象：遍历整个堆的阶段只用于排除大部分可达对象，
+    /// 最后一步在暂时认为不可达的对象上重新进行一次完整的试验删除，
+    /// 只有此时确实没有外部引用的对象才会被释放，因此这一步的开销与垃圾的数量成正比。
+    /// 周期同时覆盖年轻代与老年代，存活的对象都进入老年代。期间调用 run_gc 会放弃当前周期。
+    fn gc_step(&mut self, max_objects: usize) -> Result<bool, Error> {
+        let start = Instant::now();
+        let ret = self._gc_step(max_objects.max(1));
+        self.gc_info_mut()._gc_time += start.elapsed();
+        ret
     }
 
-    fn new_gc_obj(&mut self, size: usize, type_: Ref<RType>) -> CResult<Ref<GcHeader>> {
-        let size = size.max(size_of::<GcHeader>());
+    fn _gc_step(&mut self, mut budget: usize) -> Result<bool, Error> {
+        if self.gc_info_mut()._finalizing {
+            return Ok(false);
+        }
+        if self.gc_info_mut()._phase == GcPhase::Idle {
+            self._inc_begin();
+        }
 
-        {
-            if self.gc_info_mut()._last_mem_size == 0 {
-                if self.gc_info_mut()._curent_mem_size > 1024 * 1024 {
-                    self.run_gc()?;
+        let mut finalized = Ok(());
+        while budget > 0 {
+            let done = match self.gc_info_mut()._phase {
+                GcPhase::Idle => unreachable!(),
+                GcPhase::Init => self._inc_init(&mut budget),
+                GcPhase::Subtract => self._inc_subtract(&mut budget),
+                GcPhase::Scan => {
+                    let done = self._inc_scan(&mut budget);
+                    if done {
+                        self._inc_verify();
+                        self._clear_weak()?;
+                        finalized = self._finalize();
+                        self._clear_weak()?;
+                    }
+                    done
                 }
-            } else if self.gc_info_mut()._curent_mem_size > self.gc_info_mut()._last_mem_size * 8 {
-                self.run_gc()?;
+                GcPhase::Free => {
+                    if self._inc_free(&mut budget)? {
+                        self._inc_finish();
+                        return finalized.map(|_| true);
+                    }
+                    false
+                }
+            };
+
+            if done {
+                let info = self.gc_info_mut();
+                info._inc_cursor = None;
+                info._phase = match info._phase {
+                    GcPhase::Init => GcPhase::Subtract,
+                    GcPhase::Subtract => GcPhase::Scan,
+                    _ => GcPhase::Free,
+                };
             }
         }
 
-        unsafe {
-            let align = size_of::<usize>();
-            let ptr = self._allocator.alloc(size, align) as *mut GcHeader;
-            if ptr.is_null() {
-                return Err(Error::OutOfMemory);
-            }
+        finalized.map(|_| false)
+    }
+
+    fn _inc_begin(&mut self) {
+        let info = self.gc_info_mut();
+        info._inc_start_mem_size = info._curent_mem_size;
+        info._inc_start_obj_count = info._current_obj_count;
+        info._inc_objs.append(&mut info._gc_objs);
+        info._inc_objs.append(&mut info._old_objs);
+        info._inc_cursor = None;
+        info._phase = GcPhase::Init;
+    }
+
+    fn _inc_finish(&mut self) {
+        let info = self.gc_info_mut();
+        info._last_mem_size = info._inc_start_mem_size;
+        info._last_obj_count = info._inc_start_obj_count;
+        info._collections += 1;
+        info._full_collections += 1;
+        info._young_collections = 0;
+        info._inc_cursor = None;
+        info._phase = GcPhase::Idle;
+    }
+
+    // 放弃当前周期，候选对象全部视为可达；已确认的垃圾仍然释放。
+    fn _inc_abort(&mut self) -> Result<(), Error> {
+        let info = self.gc_info_mut();
+        match info._phase {
+            GcPhase::Idle => return Ok(()),
+            GcPhase::Free => {
+                info._phase = GcPhase::Idle;
+                self._free_cycles()?;
+            }
+            _ => {
+                info._gc_objs.append(&mut info._inc_objs);
+                info._gc_objs.append(&mut info._inc_reached);
+                info._gc_objs.append(&mut info._inc_unreached);
+                info._phase = GcPhase::Idle;
+            }
+        }
+        self.gc_info_mut()._inc_cursor = None;
+        Ok(())
+    }
+
+    // 按游标遍历 list，不会遗漏遍历期间插入在末尾的节点。
+    fn _inc_next(&mut self, list: fn(&mut GcInfo) -> &mut ListBase) -> Option<NonNull<GcHeader>> {
+        let info = self.gc_info_mut();
+        let next = match info._inc_cursor {
+            None => list(info).first(),
+            Some(node) => unsafe { list(info).next_of(node) },
+        };
+        info._inc_cursor = next.or(info._inc_cursor);
+        next.map(|node| node.cast::<GcHeader>())
+    }
+
+    fn _inc_init(&mut self, budget: &mut usize) -> bool {
+        while *budget > 0 {
+            let Some(mut value) = self._inc_next(|info| &mut info._inc_objs) else {
+                return true;
+            };
+            unsafe {
+                let value_ref = value.as_mut();
+                value_ref.set_gc_refs(value_ref.ref_count());
+                value_ref.set_gc_state(GcState::Candidate);
+            }
+            *budget -= 1;
+        }
+        false
+    }
+
+    fn _inc_subtract(&mut self, budget: &mut usize) -> bool {
+        fn _subtract_mark(mut value: NonNull<GcHeader>) {
+            unsafe {
+                let value_ref = value.as_mut();
+                // 两步之间引用可能已经改变，gc_refs 只是估计值。
+                if value_ref.gc_state() == GcState::Candidate {
+                    value_ref.set_gc_refs(value_ref.gc_refs().saturating_sub(1));
+                }
+            }
+        }
+
+        while *budget > 0 {
+            let Some(value) = self._inc_next(|info| &mut info._inc_objs) else {
+                return true;
+            };
+            value_visit_ptr(&mut _subtract_mark, value);
+            *budget -= 1;
+        }
+        false
+    }
+
+    fn _inc_scan(&mut self, budget: &mut usize) -> bool {
+        fn _reach_mark(mut value: NonNull<GcHeader>) {
+            unsafe {
+                let info = runtime().gc_info_mut();
+                let value_ref = value.as_mut();
+                match value_ref.gc_state() {
+                    GcState::Candidate => info._inc_objs.remove(value),
+                    GcState::Unreached => info._inc_unreached.remove(value),
+                    _ => return,
+                }
+                value_ref.set_gc_state(GcState::Reached);
+                info._inc_reached.insert_last(value);
+            }
+        }
+
+        while *budget > 0 {
+            *budget -= 1;
+
+            // 先处理已标记为可达的对象，再从候选对象中取出下一个。
+            if let Some(value) = self._inc_next(|info| &mut info._inc_reached) {
+                value_visit_ptr(&mut _reach_mark, value);
+                continue;
+            }
+
+            let info = self.gc_info_mut();
+            let Some(node) = info._inc_objs.pop_front() else {
+                return true;
+            };
+            unsafe {
+                let mut value = node.cast::<GcHeader>();
+                let value_ref = value.as_mut();
+                if value_ref.gc_refs() > 0 {
+                    value_ref.set_gc_state(GcState::Reached);
+                    info._inc_reached.insert_last(value);
+                } else {
+                    value_ref.set_gc_state(GcState::Unreached);
+                    info._inc_unreached.insert_last(value);
+                }
+            }
+        }
+        false
+    }
+
+    // 在暂时不可达的对象上重新进行试验删除，扣除的只有它们之间的引用，
+    // 其余对象与 Rust 中的引用都视为外部引用，所以结果与之前各步的估计无关。
+    fn _inc_verify(&mut self) {
+        fn _verify_mark(mut value: NonNull<GcHeader>) {
+            unsafe {
+                let value_ref = value.as_mut();
+                if value_ref.gc_state() == GcState::Unreached {
+                    debug_assert!(value_ref.gc_refs() > 0);
+                    value_ref.set_gc_refs(value_ref.gc_refs() - 1);
+                }
+            }
+        }
+
+        fn _restore_mark(mut value: NonNull<GcHeader>) {
+            unsafe {
+                let info = runtime().gc_info_mut();
+                let value_ref = value.as_mut();
+                if value_ref.gc_state() == GcState::Unreached {
+                    value_ref.set_gc_state(GcState::Reached);
+                    info._inc_unreached.remove(value);
+                    info._inc_reached.insert_last(value);
+                }
+            }
+        }
+
+        let info = self.gc_info_mut();
+        info._old_objs.append(&mut info._inc_reached);
+
+        unsafe {
+            for node in info._inc_unreached.iter() {
+                let mut value = node.cast::<GcHeader>();
+                let value_ref = value.as_mut();
+                value_ref.set_gc_refs(value_ref.ref_count());
+            }
+
+            for node in info._inc_unreached.iter() {
+                value_visit_ptr(&mut _verify_mark, node.cast::<GcHeader>());
+            }
+
+            for node in info._inc_unreached.iter() {
+                let mut value = node.cast::<GcHeader>();
+                if value.as_ref().gc_refs() > 0 {
+                    value.as_mut().set_gc_state(GcState::Reached);
+                    info._inc_unreached.remove(value);
+                    info._inc_reached.insert_last(value);
+                }
+            }
+        }
+
+        self.gc_info_mut()._inc_cursor = None;
+        while let Some(value) = self._inc_next(|info| &mut info._inc_reached) {
+            value_visit_ptr(&mut _restore_mark, value);
+        }
+
+        let info = self.gc_info_mut();
+        info._old_objs.append(&mut info._inc_reached);
+        info._tmp_gc_objs.append(&mut info._inc_unreached);
+    }
+
+    // 与 _free_cycles 相同，先析构全部对象再释放。
+    fn _inc_free(&mut self, budget: &mut usize) -> Result<bool, Error> {
+        while *budget > 0 {
+            *budget -= 1;
+
+            let info = self.gc_info_mut();
+            let node = match info._to_be_released_objs.pop_front() {
+                Some(node) => Some(node),
+                None => info._tmp_gc_objs.pop_front(),
+            };
+            if let Some(node) = node {
+                let value = node.cast::<GcHeader>();
+                info._has_been_released_objs.insert_last(value);
+                unsafe {
+                    let v = Ref::from_raw(value);
+                    value_destory(&v)?;
+                }
+                continue;
+            }
+
+            let Some(node) = info._has_been_released_objs.pop_front() else {
+                return Ok(true);
+            };
+            self.free_gc_obj(node.cast::<GcHeader>());
+        }
+        Ok(false)
+    }
+
+    fn _should_run_gc(&mut self) -> bool {
+        let config = self._gc_config;
+        let info = self.gc_info_mut();
+        if config.manual {
+            false
+        } else if info._last_mem_size == 0 {
+            info._curent_mem_size > config.initial_threshold
+        } else {
+            info._curent_mem_size > info._last_mem_size.saturating_mul(config.growth_factor)
+        }
+    }
+
+    // 全部存活的对象，包括增量回收周期中的候选对象，不包括已确认的垃圾。
+    fn _live_objs(&mut self) -> Vec<NonNull<GcHeader>> {
+        let info = self.gc_info_mut();
+        let lists = [
+            &info._gc_objs,
+            &info._old_objs,
+            &info._inc_objs,
+            &info._inc_reached,
+            &info._inc_unreached,
+        ];
+        let mut objs = Vec::with_capacity(info._current_obj_count);
+        for list in lists {
+            objs.extend(list.iter().map(|node| node.cast::<GcHeader>()));
+        }
+        objs
+    }
+
+    /// 当前所有存活对象的快照，不会触发回收。
+    pub fn heap_snapshot(&mut self) -> HeapSnapshot {
+        HeapSnapshot::from_objects(&self._live_objs())
+    }
+
+    fn leak_report(&mut self) -> CResult<HeapSnapshot> {
+        self._global = StringMap::new(self._allocator);
+        self._modules = StringMap::new(self._allocator);
+        // 析构对象会释放它持有的引用，使更多对象成为垃圾。
+        loop {
+            let freed = self.gc_info_mut()._freed_objects;
+            self.run_gc()?;
+            if self.gc_info_mut()._freed_objects == freed {
+                break;
+            }
+        }
+
+        let mut pending = Vec::new();
+        let mut push = |value: NonNull<GcHeader>| pending.push(value);
+        _visit_builtin(&mut push);
+        for s in self._string_pool.iter() {
+            push.visit_value(s.cast_value_ref());
+        }
+
+        while let Some(mut value) = pending.pop() {
+            unsafe {
+                if !value.as_ref().mark() {
+                    value.as_mut().set_mark(true);
+                    let mut push = |value: NonNull<GcHeader>| pending.push(value);
+                    value_visit_ptr(&mut push, value);
+                }
+            }
+        }
+
+        let objs = self._live_objs();
+        let mut leaked = Vec::new();
+        for mut value in objs {
+            unsafe {
+                if !value.as_ref().mark() {
+                    leaked.push(value);
+                }
+                value.as_mut().set_mark(false);
+            }
+        }
+
+        Ok(HeapSnapshot::from_objects(&leaked))
+    }
+
+    fn free_gc_obj(&mut self, value: NonNull<GcHeader>) {
+        unsafe {
+            let align = size_of::<usize>();
+            let size = value.as_ref().block_size();
+
+            GcHeader::drop_head(value);
+
+            self._allocator.free(value.as_ptr() as _, size, align);
+
+            let info = self.gc_info_mut();
+            info._curent_mem_size -= size;
+            info._current_obj_count -= 1;
+            info._freed_bytes += size as u64;
+            info._freed_objects += 1;
+        }
+    }
+
+    fn new_gc_obj(&mut self, size: usize, type_: Ref<RType>) -> CResult<Ref<GcHeader>> {
+        let size = size.max(size_of::<GcHeader>());
+
+        if let Some(limit) = self._heap_limit {
+            if self.heap_size() + size > limit {
+                self.run_gc()?;
+                if self.heap_size() + size > limit {
+                    return Err(Error::new_heaplimit(limit));
+                }
+            }
+        }
+
+        if self._should_run_gc() {
+            self._run_auto_gc()?;
+        }
+
+        unsafe {
+            let align = size_of::<usize>();
+            let mut ptr = self._allocator.alloc(size, align) as *mut GcHeader;
+            if ptr.is_null() {
+                // 分配器的内存可能是有限的，回收之后再尝试一次。
+                self.run_gc()?;
+                ptr = self._allocator.alloc(size, align) as *mut GcHeader;
+                if ptr.is_null() {
+                    return Err(Error::OutOfMemory);
+                }
+            }
 
             let ptr = NonNull::new_unchecked(ptr);
             GcHeader::init(ptr, size, Some(type_));
@@ -500,17 +1555,60 @@ impl Runtime {
             Ok(tp_uninit)
         }
     }
+}
 
-    pub(crate) fn integer_pool_get(&mut self, n: Int) -> Result<Ref<RInt>, Error> {
-        if n >= SMALL_INTEGER_BEG && n < SMALL_INTEGER_END {
-            unsafe {
-                let slice = self._small_integer_pool.as_slice();
-                Ok(slice
-                    .get_unchecked((n - SMALL_INTEGER_BEG) as usize)
-                    .clone())
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        let this: *mut Runtime = self;
+        let prev = _CURRENT_RUNTIME_.with(|current| current.replace(this));
+
+        self._teardown();
+
+        let prev = if prev == this { null_mut() } else { prev };
+        _CURRENT_RUNTIME_.with(|current| current.set(prev));
+    }
+}
+
+impl Runtime {
+    // 释放全部对象：先析构全部对象（不运行终结器），再统一释放内存，
+    // 因此析构时引用的其它对象总是有效的。
+    fn _teardown(&mut self) {
+        self._stack.truncate(0);
+        self._frames = Array::new(self._allocator);
+        self._global = StringMap::new(self._allocator);
+        self._modules = StringMap::new(self._allocator);
+        self._string_pool = StringPool::new(self._allocator);
+
+        // 全部对象集中到 _gc_objs 中。
+        let info = self.gc_info_mut();
+        info._gc_objs.append(&mut info._old_objs);
+        info._gc_objs.append(&mut info._inc_objs);
+        info._gc_objs.append(&mut info._inc_reached);
+        info._gc_objs.append(&mut info._inc_unreached);
+        info._gc_objs.append(&mut info._resurrected_objs);
+        info._gc_objs.append(&mut info._tmp_gc_objs);
+        info._gc_objs.append(&mut info._to_be_released_objs);
+
+        // 内建类型没有创建完成时，部分对象的类型还没有初始化，只释放内存。
+        if self._ready {
+            for node in self.gc_info_mut()._gc_objs.iter() {
+                unsafe {
+                    let v = Ref::from_raw(node.cast::<GcHeader>());
+                    let _ = value_destory(&v);
+                }
             }
-        } else {
-            _new_int_value(n)
+        }
+
+        // 增量回收中已经析构过的对象。
+        let info = self.gc_info_mut();
+        info._gc_objs.append(&mut info._has_been_released_objs);
+        while let Some(node) = self.gc_info_mut()._gc_objs.pop_front() {
+            self.free_gc_obj(node.cast::<GcHeader>());
+        }
+
+        unsafe {
+            self._allocator
+                .free_block(self._gc_info.as_ptr() as *mut u8, size_of::<GcInfo>());
         }
     }
 }
@@ -523,10 +1621,102 @@ pub fn loader() -> &'static mut dyn Loader {
     runtime()._loader
 }
 
+pub fn max_call_depth() -> usize {
+    runtime().max_call_depth()
+}
+
+pub fn set_max_call_depth(depth: usize) {
+    runtime().set_max_call_depth(depth)
+}
+
+pub fn native_stack_budget() -> usize {
+    runtime().native_stack_budget()
+}
+
+pub fn set_native_stack_budget(budget: usize) {
+    runtime().set_native_stack_budget(budget)
+}
+
+pub fn fuel() -> Option<u64> {
+    runtime().fuel()
+}
+
+pub fn set_fuel(fuel: Option<u64>) {
+    runtime().set_fuel(fuel)
+}
+
+pub fn set_fuel_hook(hook: Option<FuelHook>) {
+    runtime().set_fuel_hook(hook)
+}
+
+pub fn heap_limit() -> Option<usize> {
+    runtime().heap_limit()
+}
+
+pub fn set_heap_limit(limit: Option<usize>) {
+    runtime().set_heap_limit(limit)
+}
+
+pub fn heap_size() -> usize {
+    runtime().heap_size()
+}
+
+pub fn interrupt_handle() -> InterruptHandle {
+    runtime().interrupt_handle()
+}
+
+pub fn clear_interrupt() {
+    runtime().clear_interrupt()
+}
+
+pub fn optimize_enabled() -> bool {
+    runtime().optimize_enabled()
+}
+
+pub fn set_optimize_enabled(enabled: bool) {
+    runtime().set_optimize_enabled(enabled)
+}
+
+pub fn backend() -> Backend {
+    runtime().backend()
+}
+
+pub fn set_backend(backend: Backend) {
+    runtime().set_backend(backend)
+}
+
+pub fn traceback() -> Result<Ref<RString>, Error> {
+    runtime().traceback()
+}
+
 pub fn run_gc() -> Result<(), Error> {
     runtime().run_gc()
 }
 
+pub fn run_young_gc() -> Result<(), Error> {
+    runtime().run_young_gc()
+}
+
+pub fn gc_step(max_objects: usize) -> Result<bool, Error> {
+    runtime().gc_step(max_objects)
+}
+
+pub fn gc_config() -> GcConfig {
+    runtime().gc_config()
+}
+
+pub fn set_gc_config(config: GcConfig) {
+    runtime().set_gc_config(config)
+}
+
+pub fn gc_stats() -> GcStats {
+    runtime().gc_stats()
+}
+
+pub fn heap_snapshot() -> HeapSnapshot {
+    runtime().heap_snapshot()
+}
+
 pub fn new_gc_obj(size: usize, type_: Ref<RType>) -> Result<Ref<GcHeader>, Error> {
     runtime().new_gc_obj(size, type_)
 }
@@ -571,6 +1761,17 @@ pub fn parse_to_code(script_code: &str, allow_last_expr: bool) -> Result<Ref<RSc
     ast_as_code(ast_node, false)
 }
 
+/// 解析源码，把语法树以 JSON 写入 out，格式见 ast_dump 模块。
+pub fn parse_to_ast_json(script_code: &str, out: &mut dyn core::fmt::Write) -> Result<(), Error> {
+    let ast_node = {
+        let lexical = Lexical::new(script_code);
+        let mut parser = Parser::new(lexical);
+        parser.parse(true)?
+    };
+    crate::ast_dump::write_json(&ast_node, out)
+        .map_err(|_| runtime_error_fmt!("in parse_to_ast_json, failed to write"))
+}
+
 pub fn parse_to_function(script_code: &str) -> Result<Ref<RFunction>, Error> {
     let code = parse_to_code(script_code, true)?;
     let caps = RArray::new()?;
@@ -578,6 +1779,14 @@ pub fn parse_to_function(script_code: &str) -> Result<Ref<RFunction>, Error> {
     Ok(func)
 }
 
+/// 由 RScriptCode::serialize 写出的 `.rolc` 内容创建函数。
+pub fn bytecode_to_function(data: &[u8]) -> Result<Ref<RFunction>, Error> {
+    let code = RScriptCode::deserialize(data)?;
+    let caps = RArray::new()?;
+    let func = RFunction::from_script_code(code, caps)?;
+    Ok(func)
+}
+
 pub fn eval(script_code: &str) -> Result<RValue, Error> {
     let func = parse_to_function(script_code)?;
     let null_v = null().cast_value();
@@ -589,3 +1798,683 @@ pub fn eval_with_module(module: &Ref<RModule>, script_code: &str) -> Result<RVal
     let module = module.cast_value();
     runtime()._call(&func.cast_value(), &module, &[])
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::alloc::LimitedAllocator;
+    use crate::array::RArray;
+    use crate::map::RMap;
+    use crate::number::{Int, RInt};
+    use crate::test_util::{allocator, loader};
+    use crate::weak::{RWeakMap, RWeakRef};
+    use core::ptr::addr_of;
+
+    // 被回收的对象释放引用之后，其它对象的引用计数可能变为 0，
+    // 它们在下一次回收时才被释放，回收到对象数量不再变化为止。
+    fn collect() -> usize {
+        let mut count = runtime().gc_info_mut()._current_obj_count;
+        loop {
+            run_gc().unwrap();
+            let n = runtime().gc_info_mut()._current_obj_count;
+            if n == count {
+                return count;
+            }
+            count = n;
+        }
+    }
+
+    #[test]
+    fn test_gc_closure_cycle() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 函数捕获了自身，与捕获数组构成循环引用。
+        let source = "function f(n) { if (n <= 0) return 0; return f(n - 1); } f(3)";
+        eval(source).unwrap();
+        let count = collect();
+        eval(source).unwrap();
+        assert_eq!(collect(), count);
+    }
+
+    #[test]
+    fn test_gc_keeps_live_objects() {
+        initialize(allocator(), loader()).unwrap();
+        collect();
+
+        // b 与 c 只被 gc 对象引用，a 是最后分配的对象，
+        // 扫描到 a 时才恢复 b，之后还需要经过 b 恢复 c。
+        let c = RArray::new().unwrap();
+        let mut b = RArray::new().unwrap();
+        b.push(c.cast_value()).unwrap();
+        drop(c);
+        let mut a = RArray::new().unwrap();
+        a.push(b.cast_value()).unwrap();
+        drop(b);
+
+        let count = runtime().gc_info_mut()._current_obj_count;
+        run_gc().unwrap();
+        assert_eq!(runtime().gc_info_mut()._current_obj_count, count);
+        drop(a);
+    }
+
+    static mut REFUELS: usize = 0;
+
+    fn refuel_twice() -> Option<u64> {
+        unsafe {
+            REFUELS += 1;
+            if REFUELS <= 2 {
+                Some(100)
+            } else {
+                None
+            }
+        }
+    }
+
+    fn as_int(v: RValue) -> Int {
+        unsafe { v.cast_ref::<RInt>().as_number() }
+    }
+
+    #[test]
+    fn test_tail_call() {
+        initialize(allocator(), loader()).unwrap();
+
+        let sum = "function sum(n, acc) { if (n == 0) return acc; return sum(n - 1, acc + n); }";
+        for backend in [Backend::Stack, Backend::Register] {
+            set_backend(backend);
+
+            // 尾调用不增加调用深度，远超最大调用深度的尾递归也能完成。
+            let script = format!("{} sum(100000, 0)", sum);
+            assert_eq!(as_int(eval(&script).unwrap()), 5000050000);
+        }
+        set_backend(Backend::Stack);
+    }
+
+    static mut TRACEBACK: Option<String> = None;
+
+    #[test]
+    fn test_tail_call_traceback() {
+        initialize(allocator(), loader()).unwrap();
+
+        let tb = RFunction::from_rust_func(|_, _| {
+            let tb = traceback()?;
+            unsafe { TRACEBACK = Some(tb.as_str().to_string()) };
+            Ok(null().cast_value())
+        })
+        .unwrap();
+        set_global_with_str("tb", tb.cast_value()).unwrap();
+
+        let script = "
+            function f(n) { if (n == 0) { tb(); return 0; } return f(n - 1); }
+            f(1000)
+        ";
+        eval(script).unwrap();
+        let tb = unsafe { (*addr_of!(TRACEBACK)).clone().unwrap() };
+
+        // 1000 次尾调用复用了同一帧，回溯中只剩脚本、f 与 tb 三帧。
+        assert!(tb.contains("... 1000 frame(s) elided by tail call"));
+        assert_eq!(tb.lines().count(), 5);
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        // 在 std::thread 默认大小的栈上，无限递归也只会返回 Error::Recursion。
+        let t = std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(|| {
+                initialize(allocator(), loader()).unwrap();
+                assert_eq!(max_call_depth(), DEFAULT_MAX_CALL_DEPTH);
+                assert_eq!(native_stack_budget(), DEFAULT_NATIVE_STACK_BUDGET);
+
+                let f = "function f(n) { if (n == 0) return 0; return 1 + f(n - 1); }";
+                for backend in [Backend::Stack, Backend::Register] {
+                    set_backend(backend);
+
+                    let script = format!("{} f(1000000)", f);
+                    assert!(matches!(eval(&script), Err(Error::Recursion { .. })));
+
+                    // 出错后调用栈已经恢复，运行时仍然可用。
+                    let script = format!("{} f(10)", f);
+                    assert_eq!(as_int(eval(&script).unwrap()), 10);
+
+                    // 优化构建下每层占用的栈很少，常见的递归深度不受影响。
+                    #[cfg(not(debug_assertions))]
+                    {
+                        let script = format!("{} f(300)", f);
+                        assert_eq!(as_int(eval(&script).unwrap()), 300);
+                    }
+                }
+
+                // 脚本本身占一层。
+                set_max_call_depth(10);
+                let script = format!("{} f(8)", f);
+                assert_eq!(as_int(eval(&script).unwrap()), 8);
+                let script = format!("{} f(9)", f);
+                assert!(matches!(
+                    eval(&script),
+                    Err(Error::Recursion { max_depth: 10 })
+                ));
+                finalize();
+            })
+            .unwrap();
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn test_fuel() {
+        initialize(allocator(), loader()).unwrap();
+
+        let fib = "function f(n) { if (n <= 2) return 1; return f(n - 1) + f(n - 2); } f(15)";
+        for backend in [Backend::Stack, Backend::Register] {
+            set_backend(backend);
+
+            set_fuel(Some(1000));
+            assert!(matches!(eval("while (true) 1;"), Err(Error::OutOfFuel)));
+            assert!(matches!(eval(fib), Err(Error::OutOfFuel)));
+            assert_eq!(fuel(), Some(0));
+
+            // 耗尽之后的运行时仍然可用。
+            set_fuel(Some(100000));
+            assert_eq!(as_int(eval(fib).unwrap()), 610);
+            assert!(fuel().unwrap() < 100000);
+
+            set_fuel(None);
+            assert_eq!(
+                as_int(eval("i = 0; while (i < 5000) i = i + 1; i").unwrap()),
+                5000
+            );
+
+            // 钩子补充两次燃料之后停止。
+            unsafe { REFUELS = 0 };
+            set_fuel(Some(0));
+            set_fuel_hook(Some(refuel_twice));
+            assert!(matches!(eval("while (true) 1;"), Err(Error::OutOfFuel)));
+            assert_eq!(unsafe { REFUELS }, 3);
+            set_fuel_hook(None);
+            set_fuel(None);
+        }
+    }
+
+    #[test]
+    fn test_interrupt() {
+        initialize(allocator(), loader()).unwrap();
+
+        for backend in [Backend::Stack, Backend::Register] {
+            set_backend(backend);
+
+            let handle = interrupt_handle();
+            let watchdog = std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                handle.interrupt();
+            });
+            let ret = eval("function f() { while (true) 1; } f()");
+            assert!(matches!(ret, Err(Error::Interrupted)));
+            watchdog.join().unwrap();
+
+            // 中断请求已被响应，之后的脚本正常执行。
+            assert_eq!(
+                as_int(eval("function g(n) { return n + 1; } g(1)").unwrap()),
+                2
+            );
+        }
+
+        // 没有脚本执行时收到的请求会保留，中断之后开始的脚本。
+        let script = "function g(n) { return n + 1; } g(1)";
+        interrupt_handle().interrupt();
+        assert!(matches!(eval(script), Err(Error::Interrupted)));
+        assert_eq!(as_int(eval(script).unwrap()), 2);
+
+        // 宿主可以主动丢弃不再需要的请求。
+        interrupt_handle().interrupt();
+        clear_interrupt();
+        assert_eq!(as_int(eval(script).unwrap()), 2);
+    }
+
+    #[test]
+    fn test_heap_limit() {
+        initialize(allocator(), loader()).unwrap();
+
+        let chain = "i = 0; a = null; while (i < 5000) { a = (a, i); i = i + 1; } i";
+        let garbage = "i = 0; while (i < 5000) { a = (i, (i, i)); i = i + 1; } i";
+        run_gc().unwrap();
+        set_heap_limit(Some(heap_size() + 100000));
+
+        // 存活的数据超过上限。
+        assert!(matches!(eval(chain), Err(Error::HeapLimit { .. })));
+
+        // 垃圾可以被回收，不会触发上限。
+        run_gc().unwrap();
+        assert_eq!(as_int(eval(garbage).unwrap()), 5000);
+        assert!(heap_size() <= heap_limit().unwrap());
+
+        set_heap_limit(None);
+        assert_eq!(as_int(eval(chain).unwrap()), 5000);
+    }
+
+    #[test]
+    fn test_gc_config() {
+        initialize(allocator(), loader()).unwrap();
+
+        let garbage = "i = 0; while (i < 20000) { x = [i]; x[0] = x; i = i + 1; } i";
+
+        set_gc_config(GcConfig {
+            manual: true,
+            ..GcConfig::default()
+        });
+        let before = gc_stats();
+        assert_eq!(as_int(eval(garbage).unwrap()), 20000);
+        let after = gc_stats();
+        assert_eq!(after.collections, before.collections);
+        assert!(after.object_count >= before.object_count + 20000);
+
+        run_gc().unwrap();
+        let collected = gc_stats();
+        assert_eq!(collected.collections, after.collections + 1);
+        assert!(collected.freed_objects >= before.freed_objects + 20000);
+        assert!(collected.heap_size < after.heap_size);
+
+        set_gc_config(GcConfig {
+            initial_threshold: 64 * 1024,
+            growth_factor: 1,
+            ..GcConfig::default()
+        });
+        // 阈值基于上一次回收前的堆大小。
+        for _ in 0..2 {
+            assert_eq!(as_int(eval(garbage).unwrap()), 20000);
+        }
+        assert!(gc_stats().collections > collected.collections);
+
+        set_gc_config(GcConfig::default());
+        let freed = eval("x = null; gc::collect()").unwrap();
+        assert!(as_int(freed) > 0);
+    }
+
+    fn new_array(items: &[RValue]) -> Ref<RArray> {
+        let mut arr = RArray::new().unwrap();
+        for item in items {
+            arr.push(item.clone()).unwrap();
+        }
+        arr
+    }
+
+    // n 个只引用自身的数组。
+    fn new_cycles(n: usize) {
+        for _ in 0..n {
+            let mut x = new_array(&[]);
+            let v = x.cast_value();
+            x.push(v).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_gc_step() {
+        initialize(allocator(), loader()).unwrap();
+        set_gc_config(GcConfig {
+            manual: true,
+            ..GcConfig::default()
+        });
+
+        // b 与 a 之间隔着循环垃圾，扫描先经过 b 再经过 a，两步之间把 x 唯一的引用
+        // 在 a 与 b 之间移动，x 可能在两处都没有被标记到。间隔的奇偶两种都要试。
+        for n in [100, 101] {
+            collect();
+            let mut b = new_array(&[null().cast_value()]);
+            new_cycles(n);
+            let x = new_array(&[RInt::new(7).unwrap().cast_value()]);
+            let mut a = new_array(&[x.cast_value()]);
+            drop(x);
+
+            let before = gc_stats();
+            let mut steps = 0;
+            while !gc_step(1).unwrap() {
+                let old = a.set(0, null().cast_value()).unwrap();
+                let old = b.set(0, old).unwrap();
+                a.set(0, old).unwrap();
+                steps += 1;
+            }
+            assert!(steps > n);
+
+            let after = gc_stats();
+            assert_eq!(after.collections, before.collections + 1);
+            assert_eq!(after.freed_objects, before.freed_objects + n as u64);
+
+            // 复用被释放的内存，使错误释放的对象能被发现。
+            new_cycles(n);
+            let x = if a.get(0).unwrap().is_immediate() {
+                b.get(0).unwrap().clone()
+            } else {
+                a.get(0).unwrap().clone()
+            };
+            let x = unsafe { x.cast::<RArray>() };
+            assert_eq!(as_int(x.get(0).unwrap().clone()), 7);
+        }
+
+        // 步骤之间脚本产生的循环垃圾在下一个周期回收。
+        let garbage = "i = 0; while (i < 100) { x = [i]; x[0] = x; i = i + 1; } x = null;";
+        let mut ran = false;
+        while !gc_step(64).unwrap() {
+            if !ran {
+                eval(garbage).unwrap();
+                ran = true;
+            }
+        }
+        let freed = gc_stats().freed_objects;
+        while !gc_step(64).unwrap() {}
+        assert!(gc_stats().freed_objects >= freed + 100);
+
+        // run_gc 放弃未完成的周期。
+        assert!(!gc_step(1).unwrap());
+        eval(garbage).unwrap();
+        let freed = gc_stats().freed_objects;
+        run_gc().unwrap();
+        assert!(gc_stats().freed_objects >= freed + 100);
+        assert!(gc_step(usize::MAX).unwrap());
+
+        set_gc_config(GcConfig::default());
+    }
+
+    #[test]
+    fn test_generational_gc() {
+        initialize(allocator(), loader()).unwrap();
+        set_gc_config(GcConfig {
+            manual: true,
+            promote_after: 2,
+            ..GcConfig::default()
+        });
+
+        let mut old = new_array(&[]);
+        run_gc().unwrap();
+        let stats = gc_stats();
+        assert_eq!(stats.old_object_count, stats.object_count);
+
+        // 年轻代的循环垃圾只需年轻代回收。
+        let mut young = new_array(&[]);
+        let v = young.cast_value();
+        young.push(v).unwrap();
+        old.push(young.cast_value()).unwrap();
+        drop(young);
+        new_cycles(100);
+        let before = gc_stats();
+        run_young_gc().unwrap();
+        let after = gc_stats();
+        assert_eq!(after.freed_objects, before.freed_objects + 100);
+        assert_eq!(after.full_collections, before.full_collections);
+        assert_eq!(after.old_object_count, before.old_object_count);
+
+        // 被老年代引用的对象存活两次回收后晋升。
+        run_young_gc().unwrap();
+        let promoted = gc_stats();
+        assert_eq!(promoted.old_object_count, after.old_object_count + 1);
+        assert_eq!(promoted.freed_objects, after.freed_objects);
+
+        // 跨越两代的循环只能由完整回收释放。
+        let mut young = new_array(&[old.cast_value()]);
+        old.push(young.cast_value()).unwrap();
+        let v = young.cast_value();
+        young.push(v).unwrap();
+        drop(young);
+        drop(old);
+        run_young_gc().unwrap();
+        assert_eq!(gc_stats().freed_objects, promoted.freed_objects);
+        run_gc().unwrap();
+        let full = gc_stats();
+        assert_eq!(full.freed_objects, promoted.freed_objects + 3);
+        assert_eq!(full.full_collections, promoted.full_collections + 1);
+        assert_eq!(full.old_object_count, full.object_count);
+
+        // 自动回收按 full_every 穿插完整回收。
+        set_gc_config(GcConfig {
+            initial_threshold: 64 * 1024,
+            growth_factor: 1,
+            full_every: 3,
+            ..GcConfig::default()
+        });
+        let garbage = "i = 0; while (i < 20000) { x = [i]; x[0] = x; i = i + 1; } i";
+        for _ in 0..3 {
+            assert_eq!(as_int(eval(garbage).unwrap()), 20000);
+        }
+        let auto = gc_stats();
+        assert!(auto.collections - full.collections >= 3);
+        assert!(auto.full_collections > full.full_collections);
+        assert!(
+            auto.full_collections - full.full_collections < auto.collections - full.collections
+        );
+
+        set_gc_config(GcConfig::default());
+    }
+
+    #[test]
+    fn test_weak() {
+        initialize(allocator(), loader()).unwrap();
+        set_gc_config(GcConfig {
+            manual: true,
+            ..GcConfig::default()
+        });
+
+        let mut x = new_array(&[]);
+        let v = x.cast_value();
+        x.push(v).unwrap();
+        let dead = RWeakRef::new(&x.cast_value()).unwrap();
+        let live = new_array(&[]);
+        let alive = RWeakRef::new(&live.cast_value()).unwrap();
+        drop(x);
+        run_young_gc().unwrap();
+        assert!(dead.get().is_none());
+        assert_eq!(alive.get().unwrap().as_ptr(), live.cast_value().as_ptr());
+
+        let mut map = RWeakMap::new().unwrap();
+        let int = |n| RInt::new(n).unwrap().cast_value();
+        assert!(map.is_empty());
+        let k1 = new_array(&[]);
+        map.set(&k1.cast_value(), int(1)).unwrap();
+        // 值引用自身的键，键仍然可以被回收。
+        let k2 = new_array(&[]);
+        let v2 = new_array(&[k2.cast_value()]);
+        map.set(&k2.cast_value(), v2.cast_value()).unwrap();
+        let weak_v2 = RWeakRef::new(&v2.cast_value()).unwrap();
+        drop(k2);
+        drop(v2);
+        // k4 只被 k3 的值引用，k4 的值也要存活。
+        let k3 = new_array(&[]);
+        let k4 = new_array(&[]);
+        map.set(&k3.cast_value(), k4.cast_value()).unwrap();
+        map.set(&k4.cast_value(), new_array(&[int(4)]).cast_value())
+            .unwrap();
+        drop(k4);
+        assert!(map.set(&int(5), int(5)).is_err());
+
+        run_gc().unwrap();
+        assert!(weak_v2.get().is_none());
+        assert_eq!(map.len(), 3);
+        assert_eq!(as_int(map.get(&k1.cast_value()).unwrap().clone()), 1);
+        let k4 = map.get(&k3.cast_value()).unwrap().clone();
+        let v4 = unsafe { map.get(&k4).unwrap().clone().cast::<RArray>() };
+        assert_eq!(as_int(v4.get(0).unwrap().clone()), 4);
+
+        // 复用被释放的内存，使错误释放的对象能被发现。
+        new_cycles(100);
+        assert_eq!(as_int(v4.get(0).unwrap().clone()), 4);
+        drop((k4, v4));
+
+        // 增量回收同样在析构之前清理，但 k4 要等到 k3 的项被移除后的下一个周期。
+        drop(k3);
+        while !gc_step(16).unwrap() {}
+        assert_eq!(map.len(), 2);
+        while !gc_step(16).unwrap() {}
+        assert_eq!(map.len(), 1);
+
+        let script = "k = [1]; m = WeakMap(); m[k] = 2; w = WeakRef(k); \
+                      m[k] + m.len() + w.get().value()[0]";
+        assert_eq!(as_int(eval(script).unwrap()), 4);
+
+        set_gc_config(GcConfig::default());
+    }
+
+    #[test]
+    fn test_finalizer() {
+        initialize(allocator(), loader()).unwrap();
+        set_gc_config(GcConfig {
+            manual: true,
+            ..GcConfig::default()
+        });
+
+        let seen = RMap::new().unwrap();
+        set_global_with_str("seen", seen.cast_value()).unwrap();
+        set_global_with_str("keep", RMap::new().unwrap().cast_value()).unwrap();
+        let node = eval(
+            r#"
+            type Node {
+                function [new](name) {
+                    this.name = name;
+                    this.peer = null;
+                }
+                function [destory]() {
+                    seen[this.name] = this.peer.name;
+                    if (this.name == "c") {
+                        keep[0] = this;
+                    }
+                }
+            }
+            Node"#,
+        )
+        .unwrap();
+        set_global_with_str("Node", node).unwrap();
+
+        let pair = |a: &str, b: &str| {
+            let script =
+                format!(r#"a = Node("{a}"); b = Node("{b}"); a.peer = b; b.peer = a; null"#);
+            eval(&script).unwrap();
+        };
+        let peer_of = |seen: &Ref<RMap>, name: &str| {
+            let key = RString::new(name).unwrap().cast_value();
+            let peer = seen.get(&key)?.clone();
+            Some(unsafe { peer.cast::<RString>() }.as_str().to_string())
+        };
+
+        // 终结器运行时同一个循环中的对象都还没有被析构。
+        pair("a", "b");
+        pair("c", "d");
+        collect();
+        assert_eq!(peer_of(&seen, "a").as_deref(), Some("b"));
+        assert_eq!(peer_of(&seen, "b").as_deref(), Some("a"));
+        assert_eq!(peer_of(&seen, "c").as_deref(), Some("d"));
+        assert_eq!(peer_of(&seen, "d").as_deref(), Some("c"));
+
+        // c 复活了自己，d 被 c 引用，也不会被释放。
+        new_cycles(100);
+        let peer = eval("keep[0].peer.name").unwrap();
+        assert_eq!(unsafe { peer.cast::<RString>() }.as_str(), "d");
+
+        // 复活的对象再次成为垃圾时不再运行终结器。
+        let seen = RMap::new().unwrap();
+        set_global_with_str("seen", seen.cast_value()).unwrap();
+        let before = gc_stats();
+        eval("keep[0] = null").unwrap();
+        collect();
+        assert_eq!(seen.len(), 0);
+        assert!(gc_stats().freed_objects >= before.freed_objects + 2);
+
+        // 增量回收同样在析构之前运行终结器。
+        pair("e", "f");
+        while !gc_step(16).unwrap() {}
+        assert_eq!(peer_of(&seen, "e").as_deref(), Some("f"));
+        assert_eq!(peer_of(&seen, "f").as_deref(), Some("e"));
+
+        set_gc_config(GcConfig::default());
+    }
+
+    #[test]
+    fn test_heap_snapshot() {
+        initialize(allocator(), loader()).unwrap();
+
+        let inner = new_array(&[]);
+        let outer = new_array(&[inner.cast_value()]);
+        let inner_id = inner.as_ptr() as usize;
+        let outer_id = outer.as_ptr() as usize;
+
+        let snapshot = heap_snapshot();
+        assert_eq!(snapshot.object_count(), gc_stats().object_count);
+        assert!(snapshot.edges.contains(&(outer_id, inner_id)));
+
+        let obj = snapshot.objects.iter().find(|o| o.id == inner_id).unwrap();
+        assert_eq!(snapshot.type_name(obj.type_id), Some("Array"));
+        assert_eq!(obj.ref_count, 2);
+
+        let arrays = snapshot.types.iter().find(|t| t.name == "Array").unwrap();
+        assert!(arrays.count >= 2);
+
+        let mut json = String::new();
+        snapshot.write_json(&mut json).unwrap();
+        assert!(json.starts_with("{\"types\":[{"));
+        assert!(json.contains(&format!("[{},{}]", outer_id, inner_id)));
+
+        let mut dot = String::new();
+        snapshot.write_dot(&mut dot).unwrap();
+        assert!(dot.starts_with("digraph heap {"));
+        assert!(dot.contains(&format!("n{:x} -> n{:x};", outer_id, inner_id)));
+    }
+
+    #[test]
+    fn test_multiple_runtimes() {
+        let mut a = Runtime::new(allocator(), loader()).unwrap();
+        let mut b = Runtime::new(allocator(), loader()).unwrap();
+
+        a.with(|| set_global_with_str("x", RInt::new(1).unwrap().cast_value()).unwrap());
+        b.with(|| set_global_with_str("x", RInt::new(2).unwrap().cast_value()).unwrap());
+        assert_eq!(a.with(|| as_int(eval("x").unwrap())), 1);
+        assert_eq!(b.with(|| as_int(eval("x").unwrap())), 2);
+
+        // 每个 Runtime 有自己的内建类型；离开作用域后恢复之前的 Runtime。
+        a.with(|| {
+            let int_a = int_type().as_ptr();
+            let int_b = b.with(|| int_type().as_ptr());
+            assert_ne!(int_a, int_b);
+            assert_eq!(int_type().as_ptr(), int_a);
+        });
+
+        drop(b);
+        assert_eq!(a.with(|| as_int(eval("x + 1").unwrap())), 2);
+    }
+
+    #[test]
+    fn test_runtime_drop() {
+        let limited: &'static LimitedAllocator =
+            Box::leak(Box::new(LimitedAllocator::new(usize::MAX)));
+
+        let mut rt = Runtime::new(limited, loader()).unwrap();
+        rt.with(|| {
+            eval("a = [1, (2, 3), {\"k\": \"v\"}]; a[0] = a; null").unwrap();
+        });
+        assert!(limited.used() > 0);
+
+        // 销毁时释放全部对象，包括仍然存活的与循环引用的对象。
+        drop(rt);
+        assert_eq!(limited.used(), 0);
+    }
+
+    #[test]
+    fn test_runtime_threads() {
+        let threads: Vec<_> = (0..4)
+            .map(|n| {
+                std::thread::spawn(move || {
+                    let mut rt = Runtime::new(allocator(), loader()).unwrap();
+                    rt.with(|| {
+                        let script = format!(
+                            "i = 0; s = 0; while (i < 1000) {{ s = s + {n}; i = i + 1; }} s"
+                        );
+                        as_int(eval(&script).unwrap())
+                    })
+                })
+            })
+            .collect();
+        for (n, t) in threads.into_iter().enumerate() {
+            assert_eq!(t.join().unwrap(), 1000 * n as Int);
+        }
+
+        // 没有 Runtime 的线程调用 API 时 panic。
+        let t = std::thread::spawn(|| {
+            assert!(!is_initialized());
+            eval("1").is_ok()
+        });
+        assert!(t.join().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use core::cell::{Cell, UnsafeCell};
 use core::mem::size_of;
 use core::ptr::addr_of_mut;
 use core::ptr::NonNull;
@@ -11,7 +12,11 @@ use crate::string::*;
 use crate::type_::*;
 use crate::value::*;
 
+use crate::bytecode::{Reader, Writer};
 use crate::op::*;
+use crate::optimizer::optimize;
+use crate::register::RegOp;
+use crate::verifier::verify;
 
 use crate::error::*;
 use crate::runtime_error_fmt;
@@ -20,18 +25,65 @@ use crate::builtin::*;
 
 use crate::util::StringMap;
 
+/// GetAttr、CallMethod 等指令的内联缓存，以类型为键。
+/// 类型的属性改变后版本号随之改变，缓存自然失效。
+/// 只缓存方法与类型属性，实例字段不缓存，见 function::cached_get_attr。
+pub(crate) struct InlineCache {
+    _type: Cell<u64>,
+    _version: Cell<u64>,
+    _value: UnsafeCell<Option<RValue>>,
+}
+
+impl InlineCache {
+    fn new() -> Self {
+        Self {
+            _type: Cell::new(0),
+            _version: Cell::new(0),
+            _value: UnsafeCell::new(None),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, tp: &Ref<RType>) -> Option<RValue> {
+        if self._type.get() == tp.word().get() && self._version.get() == tp.version() {
+            unsafe { (*self._value.get()).clone() }
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set(&self, tp: &Ref<RType>, value: &RValue) {
+        self._type.set(tp.word().get());
+        self._version.set(tp.version());
+        unsafe { *self._value.get() = Some(value.clone()) };
+    }
+
+    fn value(&self) -> Option<&RValue> {
+        unsafe { (*self._value.get()).as_ref() }
+    }
+}
+
 #[repr(C)]
 pub struct RScriptCode {
     _header: GcHeader,
     _parent: Option<Ref<RScriptCode>>,
     _paramet_count: u32,
     _variable: bool,
+    _generator: bool,
+    // 执行时栈上临时值的最大个数，不包括参数与局部变量。
+    _max_stack: usize,
     _opcodes: Array<Opcode>,
+    // 以寄存器指令生成时不为空，此时 _opcodes 为空。
+    _reg_opcodes: Array<RegOp>,
+    _register_count: usize,
     _chlidren: Array<Ref<RScriptCode>>,
     _strings: Array<Ref<RString>>,
     _numbers: Array<RValue>,
     _captured_vars: StringMap<u32>,
     _local_vars: StringMap<u32>,
+    // 与 _opcodes 或 _reg_opcodes 一一对应。
+    _inline_caches: Array<InlineCache>,
 }
 
 impl RScriptCode {
@@ -41,22 +93,29 @@ impl RScriptCode {
         addr_of_mut!(r._parent).write(None);
         addr_of_mut!(r._paramet_count).write(0);
         addr_of_mut!(r._variable).write(false);
+        addr_of_mut!(r._generator).write(false);
+        addr_of_mut!(r._max_stack).write(0);
         addr_of_mut!(r._opcodes).write(Array::new(allocator));
+        addr_of_mut!(r._reg_opcodes).write(Array::new(allocator));
+        addr_of_mut!(r._register_count).write(0);
         addr_of_mut!(r._chlidren).write(Array::new(allocator));
         addr_of_mut!(r._strings).write(Array::new(allocator));
         addr_of_mut!(r._numbers).write(Array::new(allocator));
         addr_of_mut!(r._captured_vars).write(StringMap::new(allocator));
         addr_of_mut!(r._local_vars).write(StringMap::new(allocator));
+        addr_of_mut!(r._inline_caches).write(Array::new(allocator));
     }
 
     unsafe fn _drop(&mut self) {
         addr_of_mut!(self._parent).drop_in_place();
         addr_of_mut!(self._opcodes).drop_in_place();
+        addr_of_mut!(self._reg_opcodes).drop_in_place();
         addr_of_mut!(self._chlidren).drop_in_place();
         addr_of_mut!(self._strings).drop_in_place();
         addr_of_mut!(self._numbers).drop_in_place();
         addr_of_mut!(self._captured_vars).drop_in_place();
         addr_of_mut!(self._local_vars).drop_in_place();
+        addr_of_mut!(self._inline_caches).drop_in_place();
     }
 
     pub(self) fn new() -> Result<Ref<Self>, Error> {
@@ -80,10 +139,33 @@ impl RScriptCode {
         self._variable
     }
 
+    /// 包含 yield 的函数为生成器函数，调用时返回生成器而不是直接执行。
+    pub fn is_generator(&self) -> bool {
+        self._generator
+    }
+
     pub fn opcode(&self) -> &[Opcode] {
         self._opcodes.as_slice()
     }
 
+    pub fn max_stack(&self) -> usize {
+        self._max_stack
+    }
+
+    /// 是否以寄存器指令生成，见 Backend。
+    pub fn is_register(&self) -> bool {
+        self._reg_opcodes.len() != 0
+    }
+
+    pub(crate) fn reg_opcode(&self) -> &[RegOp] {
+        self._reg_opcodes.as_slice()
+    }
+
+    /// 寄存器的个数，包括参数与局部变量。
+    pub fn register_count(&self) -> usize {
+        self._register_count
+    }
+
     pub fn children_count(&self) -> usize {
         self._chlidren.len()
     }
@@ -114,6 +196,185 @@ impl RScriptCode {
     pub fn get_const_number(&self, idx: usize) -> Option<RValue> {
         self._numbers.get(idx).cloned()
     }
+
+    #[inline]
+    pub(crate) fn inline_cache(&self, ip: usize) -> Option<&InlineCache> {
+        self._inline_caches.get(ip)
+    }
+
+    /// 把该代码及其子代码写成 `.rolc` 格式，见 bytecode 模块。
+    /// 内联缓存与 max_stack 不会被写入，读取时重新生成。
+    pub fn serialize(&self) -> Result<Array<u8>, Error> {
+        let mut w = Writer::new()?;
+        self._serialize(&mut w)?;
+        w.finish()
+    }
+
+    fn _serialize(&self, w: &mut Writer) -> Result<(), Error> {
+        w.u32(self._paramet_count)?;
+        w.u8(self._variable as u8)?;
+        w.u8(self._generator as u8)?;
+        w.len(self._register_count)?;
+
+        w.len(self._strings.len())?;
+        for s in self._strings.as_slice() {
+            w.str(s.as_str())?;
+        }
+        w.len(self._numbers.len())?;
+        for n in self._numbers.as_slice() {
+            if n.is_type(int_type()) {
+                w.u8(0)?;
+                w.i64(unsafe { n.cast_ref::<RInt>().as_number() } as i64)?;
+            } else if n.is_type(float_type()) {
+                w.u8(1)?;
+                let n = unsafe { n.cast_ref::<RFloat>().as_number() };
+                // 32 位平台上 Float 是 f32，文件中总是存为 f64。
+                #[cfg(target_pointer_width = "32")]
+                let n = n as f64;
+                w.f64(n)?;
+            } else {
+                return Err(runtime_error_fmt!("in serialize, constant is not a number"));
+            }
+        }
+        for vars in [&self._captured_vars, &self._local_vars] {
+            w.len(vars.len())?;
+            for (k, v) in vars.iter() {
+                w.str(k.as_str())?;
+                w.u32(*v)?;
+            }
+        }
+
+        w.len(self._opcodes.len())?;
+        for op in self._opcodes.as_slice() {
+            w.opcode(*op)?;
+        }
+        w.len(self._reg_opcodes.len())?;
+        for op in self._reg_opcodes.as_slice() {
+            w.reg_opcode(*op)?;
+        }
+
+        w.len(self._chlidren.len())?;
+        for child in self._chlidren.as_slice() {
+            child._serialize(w)?;
+        }
+        Ok(())
+    }
+
+    /// 读取 serialize 写出的内容。
+    /// 版本不同、校验值不符、内容不完整或未通过 verify 时返回错误。
+    pub fn deserialize(data: &[u8]) -> Result<Ref<RScriptCode>, Error> {
+        let mut r = Reader::new(data)?;
+        let code = Self::_deserialize(&mut r)?;
+        if !r.is_end() {
+            return Err(runtime_error_fmt!("invalid bytecode: trailing data"));
+        }
+        code.verify()?;
+        Ok(code)
+    }
+
+    /// 检查指令是否可以安全地执行，见 verifier 模块。
+    /// 来自不可信来源的代码在执行前必须通过检查。
+    pub fn verify(&self) -> Result<(), Error> {
+        verify(self)
+    }
+
+    fn _deserialize(r: &mut Reader) -> Result<Ref<RScriptCode>, Error> {
+        let mut code = Self::new()?;
+        code._paramet_count = r.u32()?;
+        code._variable = r.bool()?;
+        code._generator = r.bool()?;
+        code._register_count = r.len()?;
+
+        for _ in 0..r.len()? {
+            let s = RString::new(r.str()?)?;
+            code._strings.push(s).map_err(|_| Error::OutOfMemory)?;
+        }
+        for _ in 0..r.len()? {
+            let n = match r.u8()? {
+                0 => RInt::new(r.i64()? as Int)?.cast_value(),
+                1 => RFloat::new(r.f64()? as Float)?.cast_value(),
+                _ => return Err(runtime_error_fmt!("invalid bytecode: bad number constant")),
+            };
+            code._numbers.push(n).map_err(|_| Error::OutOfMemory)?;
+        }
+        let c = &mut *code;
+        for vars in [&mut c._captured_vars, &mut c._local_vars] {
+            for _ in 0..r.len()? {
+                let k = RString::new(r.str()?)?;
+                let v = r.u32()?;
+                vars.insert(k, v).map_err(|_| Error::OutOfMemory)?;
+            }
+        }
+
+        for _ in 0..r.len()? {
+            let op = r.opcode()?;
+            code._opcodes.push(op).map_err(|_| Error::OutOfMemory)?;
+        }
+        for _ in 0..r.len()? {
+            let op = r.reg_opcode()?;
+            code._reg_opcodes.push(op).map_err(|_| Error::OutOfMemory)?;
+        }
+
+        for _ in 0..r.len()? {
+            let mut child = Self::_deserialize(r)?;
+            child._parent = Some(code.clone());
+            code._chlidren.push(child).map_err(|_| Error::OutOfMemory)?;
+        }
+
+        code._compute_max_stack()?;
+        code._init_inline_caches()?;
+        Ok(code)
+    }
+
+    fn _init_inline_caches(&mut self) -> Result<(), Error> {
+        while self._inline_caches.pop().is_some() {}
+        let n = self._opcodes.len().max(self._reg_opcodes.len());
+        for _ in 0..n {
+            self._inline_caches
+                .push(InlineCache::new())
+                .map_err(|_| Error::OutOfMemory)?;
+        }
+        Ok(())
+    }
+
+    /// 沿着所有可能的执行路径计算栈深度，取其最大值。
+    fn _compute_max_stack(&mut self) -> Result<(), Error> {
+        let ops = self._opcodes.as_slice();
+
+        let mut visited = Array::new(allocator());
+        visited
+            .resize(ops.len(), false)
+            .map_err(|_| Error::OutOfMemory)?;
+        let mut work: Array<(usize, usize)> = Array::new(allocator());
+        work.push((0, 0)).map_err(|_| Error::OutOfMemory)?;
+
+        let mut max_stack = 0;
+        while let Some((ip, depth)) = work.pop() {
+            if ip >= ops.len() || visited.as_slice()[ip] {
+                continue;
+            }
+            visited.as_slice_mut()[ip] = true;
+
+            let (pop, push) = ops[ip].stack_effect();
+            max_stack = max_stack.max(depth.saturating_sub(pop) + push);
+
+            for n in ops[ip].successors(ip, depth).into_iter().flatten() {
+                work.push(n).map_err(|_| Error::OutOfMemory)?;
+            }
+        }
+
+        self._max_stack = max_stack;
+        Ok(())
+    }
+
+    pub(crate) fn opcode_mut(&mut self) -> &mut Array<Opcode> {
+        &mut self._opcodes
+    }
+
+    pub(crate) fn push_const_number(&mut self, n: RValue) -> Result<usize, Error> {
+        self._numbers.push(n).map_err(|_| Error::OutOfMemory)?;
+        Ok(self._numbers.len() - 1)
+    }
 }
 
 pub(crate) fn _init_type_script_code(mut tp: Ref<RType>) -> Result<(), Error> {
@@ -156,6 +417,11 @@ fn script_code__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
         for (k, _) in code._local_vars.iter() {
             visitor.visit_value(k.cast_value_ref());
         }
+        for cache in code._inline_caches.as_slice() {
+            if let Some(v) = cache.value() {
+                visitor.visit_value(v);
+            }
+        }
     }
 }
 
@@ -231,6 +497,14 @@ impl ScriptCodeBuilder {
         self._code._variable = var;
     }
 
+    pub fn with_generator(&mut self, generator: bool) {
+        self._code._generator = generator;
+    }
+
+    pub fn has_parent(&self) -> bool {
+        self._parent.is_some()
+    }
+
     // create a label, return the label id.
     pub fn with_label(&mut self, op_pos: usize) -> Result<u32, Error> {
         let id = self._labels.len();
@@ -340,7 +614,8 @@ impl ScriptCodeBuilder {
         }
     }
 
-    pub fn with_child(&mut self, code: Ref<RScriptCode>) -> Result<usize, Error> {
+    pub fn with_child(&mut self, mut code: Ref<RScriptCode>) -> Result<usize, Error> {
+        code._parent = Some(self._code.clone());
         self._code
             ._chlidren
             .push(code)
@@ -491,6 +766,10 @@ impl ScriptCodeBuilder {
         self._code.opcode()
     }
 
+    pub(crate) fn local_count(&self) -> usize {
+        self._code.local_count()
+    }
+
     fn _replace_label(&mut self) -> Result<(), Error> {
         let ops = self._code._opcodes.as_slice_mut();
         for i in 0..ops.len() {
@@ -531,6 +810,24 @@ impl ScriptCodeBuilder {
     pub fn build(mut self) -> Result<Ref<RScriptCode>, Error> {
         self._replace_label()?;
         self._fill_const()?;
+        if optimize_enabled() {
+            optimize(&mut self._code)?;
+        }
+        self._code._compute_max_stack()?;
+        self._code._init_inline_caches()?;
+        Ok(self._code)
+    }
+
+    /// 与 build 相同，但生成的是寄存器指令的代码。
+    pub(crate) fn build_register(
+        mut self,
+        ops: Array<RegOp>,
+        register_count: usize,
+    ) -> Result<Ref<RScriptCode>, Error> {
+        self._fill_const()?;
+        self._code._reg_opcodes = ops;
+        self._code._register_count = register_count;
+        self._code._init_inline_caches()?;
         Ok(self._code)
     }
 }
@@ -0,0 +1,109 @@
+use core::mem::size_of;
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::alloc::Allocator;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+use crate::value::RValue;
+
+/// 所有脚本帧共用的值栈，每个帧是栈上的一段窗口。
+///
+/// 容量在创建时确定，之后不会重新分配，
+/// 所以取自栈上的切片在调用其他函数（可能压入新的帧）期间依然有效。
+pub(crate) struct VmStack {
+    _allocator: &'static dyn Allocator,
+    _data: NonNull<RValue>,
+    _capacity: usize,
+    _len: usize,
+}
+
+impl VmStack {
+    pub(crate) fn new(allocator: &'static dyn Allocator, capacity: usize) -> Result<Self, Error> {
+        let data = unsafe {
+            let ptr = allocator.alloc_block(capacity * size_of::<RValue>()) as *mut RValue;
+            NonNull::new(ptr).ok_or(Error::OutOfMemory)?
+        };
+        Ok(Self {
+            _allocator: allocator,
+            _data: data,
+            _capacity: capacity,
+            _len: 0,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self._len
+    }
+
+    /// 确保栈上还能再压入 n 个值。
+    #[inline]
+    pub(crate) fn ensure(&self, n: usize) -> Result<(), Error> {
+        if self._capacity - self._len < n {
+            Err(runtime_error_fmt!("stack overflow"))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    pub(crate) fn push(&mut self, v: RValue) -> Result<(), Error> {
+        self.ensure(1)?;
+        unsafe { self._data.as_ptr().add(self._len).write(v) };
+        self._len += 1;
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn pop(&mut self) -> Option<RValue> {
+        if self._len == 0 {
+            None
+        } else {
+            self._len -= 1;
+            unsafe { Some(self._data.as_ptr().add(self._len).read()) }
+        }
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        while self._len > len {
+            self.pop();
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, idx: usize) -> Option<&RValue> {
+        self.as_slice().get(idx)
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, idx: usize, v: RValue) {
+        if let Some(slot) = self.as_slice_mut().get_mut(idx) {
+            *slot = v;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn as_slice(&self) -> &[RValue] {
+        unsafe { slice::from_raw_parts(self._data.as_ptr(), self._len) }
+    }
+
+    #[inline]
+    pub(crate) fn as_slice_mut(&mut self) -> &mut [RValue] {
+        unsafe { slice::from_raw_parts_mut(self._data.as_ptr(), self._len) }
+    }
+}
+
+impl Drop for VmStack {
+    fn drop(&mut self) {
+        self.truncate(0);
+        unsafe {
+            self._allocator.free_block(
+                self._data.as_ptr() as *mut u8,
+                self._capacity * size_of::<RValue>(),
+            );
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! 各模块测试共用的分配器与加载器。
+
+use crate::alloc;
+use crate::alloc::Allocator;
+use crate::error::*;
+use crate::function::RFunction;
+use crate::module::RModule;
+use crate::runtime::Loader;
+use crate::string::RString;
+use crate::value::Ref;
+
+pub(crate) fn allocator() -> &'static dyn Allocator {
+    alloc::default_allocator()
+}
+
+struct NoLoader;
+
+impl Loader for NoLoader {
+    fn normalize_name(
+        &mut self,
+        _requester: Ref<RModule>,
+        _name: Ref<RString>,
+    ) -> Result<Ref<RString>, Error> {
+        Err(new_runtime_error_str("modules are not available in tests"))
+    }
+
+    fn load(&mut self, _normalized_name: Ref<RString>) -> Result<Ref<RFunction>, Error> {
+        Err(new_runtime_error_str("modules are not available in tests"))
+    }
+}
+
+/// 不能加载任何模块的加载器。
+pub(crate) fn loader() -> &'static mut dyn Loader {
+    Box::leak(Box::new(NoLoader))
+}
@@ -171,8 +171,10 @@ pub enum TokenType {
     While,     // 'while'
     For,       // 'For'
     Return,    // 'return'
+    Yield,     // 'yield'
     Function,  // "function"
     Type,      // "type"
+    Enum,      // "enum"
     Public,    // "public"
     Eof,
     Comment,
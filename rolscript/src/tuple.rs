@@ -145,8 +145,8 @@ fn tuple__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
 
 fn tuple__destory(value: &RValue) -> Result<(), Error> {
     let mut tuple = unsafe { value.expect_cast::<RTuple>(tuple_type())? };
-    for i in 0..tuple._len {
-        unsafe { tuple.as_mut_ptr().add(i).drop_in_place() }
+    for item in tuple.as_slice_mut() {
+        unsafe { (item as *mut RValue).drop_in_place() }
     }
     Ok(())
 }
@@ -225,6 +225,7 @@ fn tuple__iter(value: &RValue) -> Result<RValue, Error> {
     Ok(it.cast_value())
 }
 
+#[repr(C)]
 pub struct RTupleIter {
     _header: GcHeader,
     _tuple: Ref<RTuple>,
@@ -254,13 +255,81 @@ pub(crate) fn _init_type_tupleiter(mut tp: Ref<RType>) -> Result<(), Error> {
     tp.with_hash(default_value_hash);
     tp.with_eq(default_value_eq);
 
+    tp.with_visit(tuple_iter__visit);
+    tp.with_destory(tuple_iter__destory);
+
     tp.with_next(tuple_iter__next);
 
     Ok(())
 }
 
+fn tuple_iter__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let it = value_ptr.cast::<RTupleIter>();
+        visitor.visit_value(it.as_ref()._tuple.cast_value_ref());
+    }
+}
+
+fn tuple_iter__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut it = value.expect_cast::<RTupleIter>(tuple_iter_type())?;
+        addr_of_mut!(it._tuple).drop_in_place();
+    }
+    Ok(())
+}
+
 fn tuple_iter__next(value: &RValue) -> Result<Ref<ROption>, Error> {
     let mut it = unsafe { value.expect_cast::<RTupleIter>(tuple_iter_type())? };
     let nv = it.next()?;
     ROption::new(nv)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::array::RArray;
+    use crate::runtime::{eval, initialize, run_gc};
+    use crate::test_util::{allocator, loader};
+
+    fn ref_count<T>(value: &Ref<T>) -> usize {
+        value.cast_value_ref().as_ref().ref_count()
+    }
+
+    #[test]
+    fn test_tuple_destory() {
+        initialize(allocator(), loader()).unwrap();
+
+        let s = RString::new("item").unwrap();
+        let count = ref_count(&s);
+
+        let tuple = RTuple::from_slice(&[s.cast_value(), s.cast_value()]).unwrap();
+        assert_eq!(ref_count(&s), count + 2);
+
+        drop(tuple);
+        run_gc().unwrap();
+        assert_eq!(ref_count(&s), count);
+    }
+
+    #[test]
+    fn test_tuple_iter() {
+        initialize(allocator(), loader()).unwrap();
+
+        let v = eval("s = 0; for (n : (1, 2, 3)) s = s + n; s").unwrap();
+        assert_eq!(unsafe { v.cast_ref::<RInt>().as_number() }, 6);
+
+        // array -> iter -> tuple -> array 的循环引用可以被回收。
+        let s = RString::new("item").unwrap();
+        let count = ref_count(&s);
+
+        let mut array = RArray::new().unwrap();
+        let tuple = RTuple::from_slice(&[s.cast_value(), array.cast_value()]).unwrap();
+        let it = RTupleIter::new(&tuple).unwrap();
+        array.push(it.cast_value()).unwrap();
+        drop(it);
+        drop(tuple);
+        drop(array);
+
+        run_gc().unwrap();
+        assert_eq!(ref_count(&s), count);
+    }
+}
@@ -55,8 +55,11 @@ pub struct RType {
     _header: GcHeader,
 
     pub(crate) _isdyn: bool,
+    pub(crate) _isenum: bool,
     _name: Ref<RString>,
     _attrs: StringMap<RValue>,
+    // _attrs 改变时更新，见 Runtime::next_type_version。
+    _version: u64,
 
     // 用于遍历对象所引用的值。
     // 主要用于垃圾回收。
@@ -110,8 +113,10 @@ impl RType {
     ) {
         let r = ptr.as_mut();
         addr_of_mut!(r._isdyn).write(false);
+        addr_of_mut!(r._isenum).write(false);
         addr_of_mut!(r._name).write(name);
         addr_of_mut!(r._attrs).write(StringMap::new(allocator));
+        addr_of_mut!(r._version).write(runtime().next_type_version());
 
         addr_of_mut!(r._visit).write(None);
 
@@ -176,6 +181,10 @@ impl RType {
         self._isdyn = is_dyn;
     }
 
+    pub(crate) fn set_enum(&mut self, is_enum: bool) {
+        self._isenum = is_enum;
+    }
+
     pub fn new(name: Ref<RString>) -> Result<Ref<Self>, Error> {
         unsafe {
             let tptp = type_type().clone();
@@ -198,6 +207,14 @@ impl RType {
     pub fn name(&self) -> &Ref<RString> {
         &self._name
     }
+
+    pub(crate) fn version(&self) -> u64 {
+        self._version
+    }
+
+    fn _attrs_changed(&mut self) {
+        self._version = runtime().next_type_version();
+    }
 }
 
 // native
@@ -338,12 +355,14 @@ impl RType {
 impl Ref<RType> {
     #[inline]
     pub fn set_attr(&mut self, name: &Ref<RString>, attr: RValue) -> Result<(), Error> {
+        self._attrs_changed();
         self._attrs.insert(name.clone(), attr).map(|_| ())
     }
 
     #[inline]
     pub fn set_attr_str(&mut self, name: &str, attr: RValue) -> Result<(), Error> {
         let name = RString::new(name)?;
+        self._attrs_changed();
         self._attrs.insert(name, attr).map(|_| ())
     }
 
@@ -356,6 +375,7 @@ impl Ref<RType> {
     pub fn add_method_str_light(&mut self, name: &str, method: RRustFunction) -> Result<(), Error> {
         let name = RString::new(name)?;
         let method = RFunction::from_rust_func(method)?;
+        self._attrs_changed();
         self._attrs.insert(name, method.cast_value()).map(|_| ())
     }
 }
@@ -394,8 +414,9 @@ fn type__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
         let tp = value_ptr.cast::<RType>().as_ref();
 
         visitor.visit_value(tp._name.cast_value_ref());
-        for (k, _) in tp._attrs.iter() {
+        for (k, v) in tp._attrs.iter() {
             visitor.visit_value(k.cast_value_ref());
+            visitor.visit_value(v);
         }
 
         if let Some(v) = &tp._new_dyn {
@@ -476,6 +497,7 @@ fn type__get_attr(value: &RValue, name: &Ref<RString>) -> Result<RValue, Error>
 #[allow(non_snake_case)]
 fn type__set_attr(value: &RValue, name: &Ref<RString>, attr_value: &RValue) -> Result<(), Error> {
     let mut tp = unsafe { value.expect_cast::<RType>(type_type())? };
+    tp._attrs_changed();
     tp._attrs
         .insert(name.clone(), attr_value.clone())
         .map(|_| ())
@@ -517,3 +539,68 @@ fn type__name(this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
     let tp = unsafe { this.expect_cast::<RType>(type_type())? };
     Ok(tp.name().cast_value())
 }
+
+#[cfg(test)]
+mod test {
+    use super::RType;
+    use crate::builtin::*;
+    use crate::number::{Int, RInt};
+    use crate::runtime::{eval, initialize, set_backend, Backend};
+    use crate::test_util::{allocator, loader};
+
+    fn eval_int(script: &str) -> Int {
+        let v = eval(script).unwrap();
+        unsafe { v.cast_ref::<RInt>().as_number() }
+    }
+
+    #[test]
+    fn test_type_name() {
+        initialize(allocator(), loader()).unwrap();
+
+        for tp in [type_type(), string_type(), int_type(), tuple_type()] {
+            assert!(tp.name().is_type(string_type()));
+        }
+    }
+
+    #[test]
+    fn test_inline_cache_invalidation() {
+        initialize(allocator(), loader()).unwrap();
+
+        // 循环中的同一条指令在类型的属性改变前后查找到不同的方法和属性。
+        let method = "
+            type A { function [new]() { } public function f() { return 1; } }
+            function g() { return 2; }
+            a = A();
+            s = 0; i = 0;
+            while (i < 4) {
+                s = s * 10 + a.f();
+                if (i == 1) A.f = g;
+                i = i + 1;
+            }
+            s
+        ";
+        let attr = "
+            type B { public x = 1; }
+            s = 0; i = 0;
+            while (i < 4) {
+                s = s * 10 + B.x;
+                if (i == 1) B.x = 2;
+                i = i + 1;
+            }
+            s
+        ";
+        for backend in [Backend::Stack, Backend::Register] {
+            set_backend(backend);
+            assert_eq!(eval_int(method), 1122);
+            assert_eq!(eval_int(attr), 1122);
+        }
+        set_backend(Backend::Stack);
+
+        // 设置属性会更新类型的版本号。
+        let mut tp = eval("type C { } C").unwrap();
+        let tp = unsafe { tp.cast_mut::<RType>() };
+        let version = tp.version();
+        tp.set_attr_str("x", null().cast_value()).unwrap();
+        assert_ne!(tp.version(), version);
+    }
+}
@@ -1,6 +1,8 @@
 use core::fmt::{Debug, Formatter, Result as FmtResult};
 use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
 use core::mem::MaybeUninit;
+use core::num::NonZeroU64;
 use core::ptr::addr_of_mut;
 use core::ptr::NonNull;
 
@@ -22,6 +24,19 @@ use crate::builtin::*;
 
 use crate::nonnull_of;
 
+/// 对象在试验删除中的状态，只在一次回收（或一个增量回收周期）内有意义。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum GcState {
+    /// 不属于当前周期，例如周期开始之后分配的对象。
+    None,
+    /// 周期开始时已存在，尚未确定是否可达。
+    Candidate,
+    /// 暂时认为不可达。
+    Unreached,
+    /// 可达。
+    Reached,
+}
+
 #[repr(C)]
 pub struct GcHeader {
     _list_node: ListNodeBase,
@@ -29,6 +44,10 @@ pub struct GcHeader {
     _block_size: usize,
     _ref_count: usize,
     _mark: bool,
+    _gc_state: GcState,
+    _gc_age: u8,
+    _finalized: bool,
+    _gc_refs: usize,
 }
 
 impl ToListNode for NonNull<GcHeader> {
@@ -55,6 +74,10 @@ impl GcHeader {
         addr_of_mut!(ptr.as_mut()._block_size).write(block_size);
         addr_of_mut!(ptr.as_mut()._ref_count).write(0);
         addr_of_mut!(ptr.as_mut()._mark).write(false);
+        addr_of_mut!(ptr.as_mut()._gc_state).write(GcState::None);
+        addr_of_mut!(ptr.as_mut()._gc_age).write(0);
+        addr_of_mut!(ptr.as_mut()._finalized).write(false);
+        addr_of_mut!(ptr.as_mut()._gc_refs).write(0);
     }
 
     pub(crate) fn drop_head(mut ptr: NonNull<Self>) {
@@ -70,6 +93,37 @@ impl GcHeader {
         self._mark = m;
     }
 
+    pub(crate) fn gc_state(&self) -> GcState {
+        self._gc_state
+    }
+    pub(crate) fn set_gc_state(&mut self, state: GcState) {
+        self._gc_state = state;
+    }
+
+    /// 在年轻代回收中存活的次数。
+    pub(crate) fn gc_age(&self) -> u8 {
+        self._gc_age
+    }
+    pub(crate) fn set_gc_age(&mut self, age: u8) {
+        self._gc_age = age;
+    }
+
+    /// 终结器（[destory] 重载）是否已经运行过。
+    pub(crate) fn finalized(&self) -> bool {
+        self._finalized
+    }
+    pub(crate) fn set_finalized(&mut self, finalized: bool) {
+        self._finalized = finalized;
+    }
+
+    /// 试验删除中扣除候选对象之间的引用之后剩余的引用数。
+    pub(crate) fn gc_refs(&self) -> usize {
+        self._gc_refs
+    }
+    pub(crate) fn set_gc_refs(&mut self, n: usize) {
+        self._gc_refs = n;
+    }
+
     pub(crate) fn block_size(&self) -> usize {
         self._block_size
     }
@@ -104,7 +158,9 @@ impl<T: ?Sized> TypeUninitRef<T> {
 
     #[inline]
     pub(crate) unsafe fn force_into(self) -> Ref<T> {
-        let n = Ref(self.0);
+        let n = Ref::from_word(NonZeroU64::new_unchecked(
+            self.0.as_ptr() as *mut u8 as usize as u64,
+        ));
         core::mem::forget(self);
         n
     }
@@ -170,14 +226,159 @@ impl<T: ?Sized> core::ops::DerefMut for TypeUninitRef<T> {
     }
 }
 
+/// Ref 内部是一个 64 位的字，除了指向 gc 对象的指针之外，
+/// 也可以直接存放 Int、Float、Bool 和 Null，这些值不需要分配内存。
+///
+/// 编码方式（按高 16 位区分）：
+/// - `0x0000`：指向 gc 对象的指针。假设地址的高 16 位总是 0，
+///   64 位平台上用户空间的地址只有 48 位（x86-64 与 AArch64 均是如此），32 位平台上自然成立。
+/// - `0x0001`：null、false、true。
+/// - `0xFFFF`：Int，低 48 位为补码表示的整数。
+/// - 其余：Float，f64 的位模式加上 2^49，NaN 会被规范化。
+///
+/// 引用计数不是原子的，对象也只属于创建它的 Runtime，因此 Ref 既不是 Send 也不是 Sync。
+/// 在线程之间传递数据使用 Message 或 channel。
 #[repr(transparent)]
-pub struct Ref<T: ?Sized>(NonNull<T>);
+pub struct Ref<T: ?Sized>(NonZeroU64, PhantomData<NonNull<T>>);
+
+pub(crate) mod immediate {
+    use core::num::NonZeroU64;
+
+    use crate::number::{Float, Int};
+
+    const TAG_SHIFT: u32 = 48;
+    const PAYLOAD_MASK: u64 = (1 << TAG_SHIFT) - 1;
+
+    const TAG_POINTER: u64 = 0x0000;
+    const TAG_SPECIAL: u64 = 0x0001;
+    const TAG_INT: u64 = 0xFFFF;
+
+    const FLOAT_OFFSET: u64 = 1 << 49;
+    const CANONICAL_NAN: u64 = 0x7FF8_0000_0000_0000;
+
+    pub(crate) const NULL: NonZeroU64 = special(0);
+    pub(crate) const FALSE: NonZeroU64 = special(1);
+    pub(crate) const TRUE: NonZeroU64 = special(2);
+
+    const INT_MIN: i64 = -(1 << (TAG_SHIFT - 1));
+    const INT_MAX: i64 = (1 << (TAG_SHIFT - 1)) - 1;
+
+    const fn special(n: u64) -> NonZeroU64 {
+        match NonZeroU64::new(TAG_SPECIAL << TAG_SHIFT | n) {
+            Some(w) => w,
+            None => unreachable!(),
+        }
+    }
+
+    #[inline]
+    fn tag(word: NonZeroU64) -> u64 {
+        word.get() >> TAG_SHIFT
+    }
+
+    #[inline]
+    pub(crate) fn is_pointer(word: NonZeroU64) -> bool {
+        tag(word) == TAG_POINTER
+    }
+
+    #[inline]
+    pub(crate) fn is_int(word: NonZeroU64) -> bool {
+        tag(word) == TAG_INT
+    }
+
+    #[inline]
+    pub(crate) fn is_float(word: NonZeroU64) -> bool {
+        let t = tag(word);
+        t != TAG_POINTER && t != TAG_SPECIAL && t != TAG_INT
+    }
+
+    #[inline]
+    pub(crate) fn is_bool(word: NonZeroU64) -> bool {
+        word == TRUE || word == FALSE
+    }
+
+    #[inline]
+    pub(crate) fn from_int(n: Int) -> Option<NonZeroU64> {
+        let n = n as i64;
+        if !(INT_MIN..=INT_MAX).contains(&n) {
+            return None;
+        }
+        let payload = (n as u64) & PAYLOAD_MASK;
+        NonZeroU64::new(TAG_INT << TAG_SHIFT | payload)
+    }
+
+    #[inline]
+    pub(crate) fn to_int(word: NonZeroU64) -> Int {
+        // 左移后再算术右移，恢复符号位。
+        (((word.get() << 16) as i64) >> 16) as Int
+    }
+
+    #[inline]
+    pub(crate) fn from_float(n: Float) -> NonZeroU64 {
+        // 32 位平台上 Float 是 f32，统一按 f64 的位模式编码。
+        #[cfg(target_pointer_width = "32")]
+        let n = n as f64;
+        let bits = if n.is_nan() {
+            CANONICAL_NAN
+        } else {
+            n.to_bits()
+        };
+        match NonZeroU64::new(bits.wrapping_add(FLOAT_OFFSET)) {
+            Some(w) => w,
+            None => unreachable!(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn to_float(word: NonZeroU64) -> Float {
+        f64::from_bits(word.get().wrapping_sub(FLOAT_OFFSET)) as Float
+    }
+
+    #[inline]
+    pub(crate) fn from_bool(b: bool) -> NonZeroU64 {
+        if b {
+            TRUE
+        } else {
+            FALSE
+        }
+    }
+}
 
 #[allow(dead_code)]
 impl<T: ?Sized> Ref<T> {
     pub unsafe fn from_raw(ptr: NonNull<T>) -> Self {
         ptr.cast::<GcHeader>().as_mut().inc_ref();
-        Self(ptr)
+        let word = NonZeroU64::new_unchecked(ptr.as_ptr() as *mut u8 as usize as u64);
+        debug_assert!(
+            immediate::is_pointer(word),
+            "address does not fit in 48 bits"
+        );
+        Self::from_word(word)
+    }
+
+    #[inline]
+    pub(crate) const unsafe fn from_word(word: NonZeroU64) -> Self {
+        Self(word, PhantomData)
+    }
+
+    #[inline]
+    pub(crate) fn word(&self) -> NonZeroU64 {
+        self.0
+    }
+
+    /// 是否为不需要分配内存的值（Int、Float、Bool 或 Null）。
+    #[inline]
+    pub fn is_immediate(&self) -> bool {
+        !immediate::is_pointer(self.0)
+    }
+
+    #[inline]
+    fn header_ptr(&self) -> Option<NonNull<GcHeader>> {
+        if immediate::is_pointer(self.0) {
+            let ptr = self.0.get() as usize as *mut GcHeader;
+            unsafe { Some(NonNull::new_unchecked(ptr)) }
+        } else {
+            None
+        }
     }
 
     pub(crate) fn cast_value_ref(&self) -> &Ref<GcHeader> {
@@ -188,14 +389,14 @@ impl<T: ?Sized> Ref<T> {
     }
 
     pub fn cast_value(&self) -> Ref<GcHeader> {
-        unsafe { Ref::from_raw(self.0.cast()) }
+        unsafe { self.clone().cast() }
     }
 
     #[inline]
     pub unsafe fn cast<U>(self) -> Ref<U> {
-        let ptr = self.0;
+        let word = self.0;
         core::mem::forget(self);
-        Ref(ptr.cast())
+        Ref::from_word(word)
     }
 
     #[inline]
@@ -217,62 +418,79 @@ impl<T: ?Sized> Ref<T> {
         }
     }
 
+    /// 对于 gc 对象比较是否为同一个对象，对于 Int、Float、Bool 和 Null 比较其编码。
     #[inline]
     pub fn ptr_eq(a: &Self, b: &Self) -> bool {
-        a.as_ptr() == b.as_ptr()
+        a.0 == b.0
     }
+}
 
+/// 对象的地址保存在 64 位的字中，无法携带胖指针的元数据，
+/// 因此只有 T 为 Sized 时才能得到指向对象的指针。
+#[allow(dead_code)]
+impl<T> Ref<T> {
     pub(crate) fn as_ptr(&self) -> *const T {
-        unsafe { self.0.as_ref() as *const T }
+        self.0.get() as usize as *const T
     }
     pub(crate) fn as_mut_ptr(&mut self) -> *mut T {
-        unsafe { self.0.as_mut() as *mut T }
+        self.0.get() as usize as *mut T
     }
 
     pub(crate) fn as_nonnull_ptr(&self) -> NonNull<T> {
-        self.0
+        debug_assert!(!self.is_immediate());
+        unsafe { NonNull::new_unchecked(self.as_ptr() as *mut T) }
     }
 
     pub(crate) fn as_ref(&self) -> &T {
-        unsafe { self.0.as_ref() }
+        debug_assert!(!self.is_immediate());
+        unsafe { &*self.as_ptr() }
     }
     pub(crate) fn as_mut(&mut self) -> &mut T {
-        unsafe { self.0.as_mut() }
+        debug_assert!(!self.is_immediate());
+        unsafe { &mut *self.as_mut_ptr() }
     }
 }
 
 impl<T: ?Sized> Ref<T> {
     pub fn is_type(&self, tp: &Ref<RType>) -> bool {
-        unsafe { Ref::ptr_eq(self.cast_value_ref()._type.assume_init_ref(), tp) }
+        Ref::ptr_eq(self.get_type(), tp)
     }
 
     pub fn get_type(&self) -> &Ref<RType> {
-        unsafe { self.cast_value_ref()._type.assume_init_ref() }
+        match self.header_ptr() {
+            Some(header) => unsafe { header.as_ref()._type.assume_init_ref() },
+            None if immediate::is_int(self.0) => int_type(),
+            None if immediate::is_float(self.0) => float_type(),
+            None if immediate::is_bool(self.0) => bool_type(),
+            None => null_type(),
+        }
     }
 }
 
-unsafe impl<T: ?Sized> Send for Ref<T> {}
-unsafe impl<T: ?Sized> Sync for Ref<T> {}
-
 impl<T: ?Sized> Drop for Ref<T> {
     fn drop(&mut self) {
-        self.cast_value_mut().dec_ref();
+        if let Some(mut header) = self.header_ptr() {
+            unsafe { header.as_mut().dec_ref() }
+        }
     }
 }
 
 impl<T: ?Sized> Clone for Ref<T> {
     fn clone(&self) -> Self {
-        unsafe { Ref::from_raw(self.0) }
+        if let Some(mut header) = self.header_ptr() {
+            unsafe { header.as_mut().inc_ref() }
+        }
+        Self(self.0, PhantomData)
     }
 }
 
-impl<T: ?Sized> core::ops::Deref for Ref<T> {
+impl<T> core::ops::Deref for Ref<T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         self.as_ref()
     }
 }
-impl<T: ?Sized> core::ops::DerefMut for Ref<T> {
+impl<T> core::ops::DerefMut for Ref<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.as_mut()
     }
@@ -288,6 +506,14 @@ impl Hash for RValue {
 
 impl Debug for RValue {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let word = self.word();
+        if immediate::is_int(word) {
+            return f.write_fmt(format_args!("<Int {}>", immediate::to_int(word)));
+        } else if immediate::is_float(word) {
+            return f.write_fmt(format_args!("<Float {}>", immediate::to_float(word)));
+        } else if self.is_immediate() {
+            return f.write_fmt(format_args!("<{}>", self.get_type().name().as_str()));
+        }
         f.write_fmt(format_args!(
             "<{} at 0x{:X}>",
             self.get_type().name().as_str(),
@@ -700,3 +926,61 @@ pub fn default_value_str(value: &RValue) -> Result<Ref<RString>, Error> {
         value.as_ptr() as usize
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use super::immediate;
+    use crate::number::{Float, Int};
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_immediate_int() {
+        let max = (1 << 47) - 1;
+        let min = -(1 << 47);
+        for n in [0, 1, -1, 12345, -12345, max, min] {
+            let w = immediate::from_int(n).unwrap();
+            assert!(immediate::is_int(w));
+            assert!(!immediate::is_float(w));
+            assert_eq!(immediate::to_int(w), n);
+        }
+        assert!(immediate::from_int(max + 1).is_none());
+        assert!(immediate::from_int(min - 1).is_none());
+        assert!(immediate::from_int(Int::MAX).is_none());
+    }
+
+    #[test]
+    fn test_immediate_float() {
+        let values: [Float; 8] = [
+            0.0,
+            -0.0,
+            1.5,
+            -2.25,
+            Float::MAX,
+            Float::MIN_POSITIVE,
+            Float::INFINITY,
+            Float::NEG_INFINITY,
+        ];
+        for n in values {
+            let w = immediate::from_float(n);
+            assert!(immediate::is_float(w));
+            assert!(!immediate::is_int(w));
+            assert_eq!(immediate::to_float(w).to_bits(), n.to_bits());
+        }
+
+        let w = immediate::from_float(-Float::NAN);
+        assert!(immediate::is_float(w));
+        assert!(immediate::to_float(w).is_nan());
+    }
+
+    #[test]
+    fn test_immediate_special() {
+        assert!(immediate::is_bool(immediate::from_bool(true)));
+        assert!(immediate::is_bool(immediate::from_bool(false)));
+        assert!(!immediate::is_bool(immediate::NULL));
+        for w in [immediate::NULL, immediate::TRUE, immediate::FALSE] {
+            assert!(!immediate::is_pointer(w));
+            assert!(!immediate::is_int(w));
+            assert!(!immediate::is_float(w));
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use crate::collections::Array;
+
+use crate::runtime::*;
+
+use crate::op::*;
+use crate::register::RegOp;
+use crate::script_code::RScriptCode;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+fn invalid(ip: usize, what: &str) -> Error {
+    runtime_error_fmt!("invalid bytecode at {}: {}", ip, what)
+}
+
+fn target(ip: usize, off: i32, len: usize) -> Result<usize, Error> {
+    let t = ip as isize + off as isize;
+    if t >= 0 && (t as usize) < len {
+        Ok(t as usize)
+    } else {
+        Err(invalid(ip, "jump target out of range"))
+    }
+}
+
+/// 检查代码及其子代码，使执行时不会越界访问指令、常量、子代码、局部变量与寄存器，
+/// 栈指令的每个位置无论从哪条路径到达栈深度都相同且不会下溢，
+/// 所有路径都以 Return 结束而不会越过最后一条指令。
+pub(crate) fn verify(code: &RScriptCode) -> Result<(), Error> {
+    if code.is_register() {
+        verify_register(code)?;
+    } else {
+        verify_stack(code)?;
+    }
+    for i in 0..code.children_count() {
+        verify(&code.get_child(i).unwrap())?;
+    }
+    Ok(())
+}
+
+fn check_str(code: &RScriptCode, ip: usize, idx: usize) -> Result<(), Error> {
+    if code.get_const_string(idx).is_some() {
+        Ok(())
+    } else {
+        Err(invalid(ip, "const string index out of range"))
+    }
+}
+
+fn check_num(code: &RScriptCode, ip: usize, idx: usize) -> Result<(), Error> {
+    if code.get_const_number(idx).is_some() {
+        Ok(())
+    } else {
+        Err(invalid(ip, "const number index out of range"))
+    }
+}
+
+fn check_child(code: &RScriptCode, ip: usize, idx: usize) -> Result<(), Error> {
+    if idx < code.children_count() {
+        Ok(())
+    } else {
+        Err(invalid(ip, "child index out of range"))
+    }
+}
+
+fn check_capture(code: &RScriptCode, ip: usize, idx: usize) -> Result<(), Error> {
+    if idx < code.captured_count() {
+        Ok(())
+    } else {
+        Err(invalid(ip, "capture index out of range"))
+    }
+}
+
+fn verify_stack(code: &RScriptCode) -> Result<(), Error> {
+    use Opcode::*;
+
+    let ops = code.opcode();
+    if (code.paramet_count() as usize) > code.local_count() {
+        return Err(invalid(0, "more paramets than local variables"));
+    }
+
+    for (ip, op) in ops.iter().enumerate() {
+        match *op {
+            LoadConstStr(n) | GetGlobal(n) | GetAttr(n) | GetAttrDup(n) | SetAttr(n) => {
+                check_str(code, ip, n as usize)?
+            }
+            CallMethod(n, _) | CallAttr(n, _) => check_str(code, ip, n as usize)?,
+            LoadConstNum(n) => check_num(code, ip, n as usize)?,
+            NewClosure(n) => check_child(code, ip, n as usize)?,
+            GetCapture(n) => check_capture(code, ip, n as usize)?,
+            GetLocal(n) | SetLocal(n) if n as usize >= code.local_count() => {
+                return Err(invalid(ip, "local variable index out of range"));
+            }
+            SetOverload(n) if OverloadOp::from_u8(n).is_none() => {
+                return Err(invalid(ip, "invalid overload op"));
+            }
+            IfFalse(off) | Jmp(off) | IterNext(off) => {
+                target(ip, off, ops.len())?;
+            }
+            IfFalseLabel(_) | JmpLabel(_) | IterNextLabel(_) | Apply(_) => {
+                return Err(invalid(ip, "reserved instruction"));
+            }
+            _ => {}
+        }
+    }
+
+    // 每个位置执行前的栈深度，usize::MAX 表示尚未到达。
+    let mut depths = Array::new(allocator());
+    depths
+        .resize(ops.len(), usize::MAX)
+        .map_err(|_| Error::OutOfMemory)?;
+    let mut work: Array<(usize, usize, usize)> = Array::new(allocator());
+    work.push((0, 0, 0)).map_err(|_| Error::OutOfMemory)?;
+
+    let mut returned = false;
+    while let Some((from, ip, depth)) = work.pop() {
+        if ip >= ops.len() {
+            return Err(invalid(from, "execution falls off the end of code"));
+        }
+        let known = depths.as_slice()[ip];
+        if known != usize::MAX {
+            if known != depth {
+                return Err(invalid(ip, "inconsistent stack depth"));
+            }
+            continue;
+        }
+        depths.as_slice_mut()[ip] = depth;
+
+        let (pop, _) = ops[ip].stack_effect();
+        if depth < pop {
+            return Err(invalid(ip, "stack underflow"));
+        }
+        if matches!(ops[ip], Return) {
+            returned = true;
+        }
+        // 跳转目标已在上面检查过，越界的只可能是顺序执行越过了最后一条指令。
+        for (n, d) in ops[ip].successors(ip, depth).into_iter().flatten() {
+            work.push((ip, n, d)).map_err(|_| Error::OutOfMemory)?;
+        }
+    }
+
+    if !returned {
+        return Err(invalid(0, "no reachable Return"));
+    }
+    Ok(())
+}
+
+fn verify_register(code: &RScriptCode) -> Result<(), Error> {
+    use RegOp::*;
+
+    let ops = code.reg_opcode();
+    let count = code.register_count();
+    if count < code.local_count() || count < code.paramet_count() as usize {
+        return Err(invalid(0, "too few registers"));
+    }
+
+    let reg = |ip: usize, r: u16| {
+        if (r as usize) < count {
+            Ok(())
+        } else {
+            Err(invalid(ip, "register out of range"))
+        }
+    };
+    let range = |ip: usize, start: u16, n: usize| {
+        if start as usize + n <= count {
+            Ok(())
+        } else {
+            Err(invalid(ip, "register range out of range"))
+        }
+    };
+
+    for (ip, op) in ops.iter().enumerate() {
+        match *op {
+            Move(a, b) | Iter(a, b) | Unary(_, a, b) => {
+                reg(ip, a)?;
+                reg(ip, b)?;
+            }
+            LoadNull(a) | LoadInt(a, _) | LoadThis(a) | Return(a) => reg(ip, a)?,
+            LoadConstStr(a, n) | GetGlobal(a, n) => {
+                reg(ip, a)?;
+                check_str(code, ip, n as usize)?;
+            }
+            LoadConstNum(a, n) => {
+                reg(ip, a)?;
+                check_num(code, ip, n as usize)?;
+            }
+            NewTuple(a, b, n) | NewArray(a, b, n) => {
+                reg(ip, a)?;
+                range(ip, b, n as usize)?;
+            }
+            NewMap(a, b, n) => {
+                reg(ip, a)?;
+                range(ip, b, n as usize * 2)?;
+            }
+            NewClosure(a, c, b, n) => {
+                reg(ip, a)?;
+                range(ip, b, n as usize)?;
+                check_child(code, ip, c as usize)?;
+                let child = code.get_child(c as usize).unwrap();
+                if child.captured_count() != n as usize {
+                    return Err(invalid(ip, "capture count mismatch"));
+                }
+            }
+            GetCapture(a, n) => {
+                reg(ip, a)?;
+                check_capture(code, ip, n as usize)?;
+            }
+            SetCapture(a, _, b) => {
+                reg(ip, a)?;
+                reg(ip, b)?;
+            }
+            GetAttr(a, b, n) => {
+                reg(ip, a)?;
+                reg(ip, b)?;
+                check_str(code, ip, n as usize)?;
+            }
+            SetAttr(a, n, b) => {
+                reg(ip, a)?;
+                reg(ip, b)?;
+                check_str(code, ip, n as usize)?;
+            }
+            GetItem(a, b, c) | SetItem(a, b, c) | Arith(_, a, b, c) | Cmp(_, a, b, c) => {
+                reg(ip, a)?;
+                reg(ip, b)?;
+                reg(ip, c)?;
+            }
+            IfFalse(a, off) => {
+                reg(ip, a)?;
+                target(ip, off, ops.len())?;
+            }
+            Jmp(off) => {
+                target(ip, off, ops.len())?;
+            }
+            IterNext(a, b, off) => {
+                reg(ip, a)?;
+                reg(ip, b)?;
+                target(ip, off, ops.len())?;
+            }
+            Call(a, n) | TailCall(a, n) => range(ip, a, n as usize + 1)?,
+            CallMethod(a, name, n) | CallAttr(a, name, n) => {
+                range(ip, a, n as usize + 1)?;
+                check_str(code, ip, name as usize)?;
+            }
+        }
+    }
+
+    let mut visited = Array::new(allocator());
+    visited
+        .resize(ops.len(), false)
+        .map_err(|_| Error::OutOfMemory)?;
+    let mut work: Array<(usize, usize)> = Array::new(allocator());
+    work.push((0, 0)).map_err(|_| Error::OutOfMemory)?;
+
+    let mut returned = false;
+    while let Some((from, ip)) = work.pop() {
+        if ip >= ops.len() {
+            return Err(invalid(from, "execution falls off the end of code"));
+        }
+        if visited.as_slice()[ip] {
+            continue;
+        }
+        visited.as_slice_mut()[ip] = true;
+
+        let len = ops.len();
+        let next = match ops[ip] {
+            Return(_) => {
+                returned = true;
+                [None, None]
+            }
+            Jmp(off) => [Some(target(ip, off, len)?), None],
+            IfFalse(_, off) | IterNext(_, _, off) => [Some(ip + 1), Some(target(ip, off, len)?)],
+            _ => [Some(ip + 1), None],
+        };
+        for n in next.into_iter().flatten() {
+            work.push((ip, n)).map_err(|_| Error::OutOfMemory)?;
+        }
+    }
+
+    if !returned {
+        return Err(invalid(0, "no reachable Return"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::{initialize, parse_to_code, set_optimize_enabled};
+    use crate::script_code::ScriptCodeBuilder;
+    use crate::test_util::{allocator, loader};
+
+    fn build(ops: &[Opcode]) -> crate::Ref<RScriptCode> {
+        let mut builder = ScriptCodeBuilder::new(None).unwrap();
+        for op in ops {
+            builder.with_opcode(*op).unwrap();
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_verify() {
+        initialize(allocator(), loader()).unwrap();
+        set_optimize_enabled(false);
+
+        let sources = [
+            "a = 1; b = a + (a = 5); (a, b, !true, ~5)",
+            "function f(n) { if (n <= 2) return 1; return f(n - 1) + f(n - 2); } f(10)",
+            "i = 0; while (i < 10) i = i + 1; i",
+            "g = (x) => x * 2; g(3)",
+        ];
+        for source in sources {
+            let code = parse_to_code(source, true).unwrap();
+            assert!(code.verify().is_ok(), "{}", source);
+        }
+
+        use Opcode::*;
+        assert!(build(&[LoadNull, Return]).verify().is_ok());
+        assert!(build(&[Return]).verify().is_err());
+        assert!(build(&[LoadNull]).verify().is_err());
+        assert!(build(&[Jmp(5), LoadNull, Return]).verify().is_err());
+        assert!(build(&[LoadConstStr(0), Return]).verify().is_err());
+        assert!(build(&[GetLocal(0), Return]).verify().is_err());
+        assert!(build(&[NewClosure(0), Return]).verify().is_err());
+        assert!(build(&[LoadNull, Add, Return]).verify().is_err());
+        // 两条路径到达 Return 时的栈深度不同。
+        assert!(build(&[LoadNull, IfFalse(3), LoadNull, LoadNull, Return])
+            .verify()
+            .is_err());
+    }
+}
@@ -0,0 +1,290 @@
+#![allow(non_snake_case)]
+
+use core::mem::{offset_of, size_of, ManuallyDrop};
+use core::ptr::addr_of_mut;
+use core::ptr::NonNull;
+
+use crate::runtime::*;
+
+use crate::error::*;
+use crate::runtime_error_fmt;
+
+use crate::collections::{Array, HashMap, ListBase, ListNodeBase};
+
+use crate::number::*;
+use crate::option::ROption;
+use crate::type_::*;
+use crate::value::*;
+
+use crate::builtin::*;
+
+use crate::nonnull_of;
+use crate::util::expect_arg1;
+
+// gc 确定为垃圾的对象状态为 Unreached，直到被释放。
+fn _is_garbage(value: &RValue) -> bool {
+    !value.is_immediate() && value.as_ref().gc_state() == GcState::Unreached
+}
+
+/// 不增加目标引用计数的引用。
+/// 目标被回收时，gc 在析构任何对象之前把它清空。
+#[repr(C)]
+pub struct RWeakRef {
+    _header: GcHeader,
+    _weak_node: ListNodeBase,
+    _target: Option<ManuallyDrop<RValue>>,
+}
+
+impl RWeakRef {
+    unsafe fn init(mut ptr: NonNull<Self>, target: &RValue) {
+        let node = nonnull_of!(ptr.as_mut()._weak_node);
+        ListNodeBase::init(node);
+        let weak = ManuallyDrop::new(RValue::from_word(target.word()));
+        addr_of_mut!(ptr.as_mut()._target).write(Some(weak));
+        runtime().add_weak_ref(node);
+    }
+
+    unsafe fn from_weak_node(node: NonNull<ListNodeBase>) -> NonNull<Self> {
+        node.byte_sub(offset_of!(Self, _weak_node)).cast()
+    }
+
+    pub fn new(target: &RValue) -> Result<Ref<Self>, Error> {
+        let tp = weak_ref_type().clone();
+        unsafe {
+            let v = new_gc_obj(size_of::<Self>(), tp)?.cast::<Self>();
+            Self::init(v.as_nonnull_ptr(), target);
+            Ok(v)
+        }
+    }
+
+    /// 目标还没有被回收时返回它。
+    pub fn get(&self) -> Option<RValue> {
+        self._target.as_deref().cloned()
+    }
+}
+
+/// 键为弱引用的映射，键按对象本身比较，只能是 gc 对象。
+/// 键被回收时对应的项在析构任何对象之前被移除；
+/// 值只在键存活时才被认为可达，值引用自身的键并不会使键一直存活。
+#[repr(C)]
+pub struct RWeakMap {
+    _header: GcHeader,
+    _weak_node: ListNodeBase,
+    // 键是对象的地址。
+    _map: HashMap<usize, RValue>,
+}
+
+impl RWeakMap {
+    unsafe fn init(mut ptr: NonNull<Self>) {
+        let node = nonnull_of!(ptr.as_mut()._weak_node);
+        ListNodeBase::init(node);
+        addr_of_mut!(ptr.as_mut()._map).write(HashMap::new(allocator()));
+        runtime().add_weak_map(node);
+    }
+
+    unsafe fn from_weak_node(node: NonNull<ListNodeBase>) -> NonNull<Self> {
+        node.byte_sub(offset_of!(Self, _weak_node)).cast()
+    }
+
+    pub fn new() -> Result<Ref<Self>, Error> {
+        let tp = weak_map_type().clone();
+        unsafe {
+            let v = new_gc_obj(size_of::<Self>(), tp)?.cast::<Self>();
+            Self::init(v.as_nonnull_ptr());
+            Ok(v)
+        }
+    }
+
+    fn _key(key: &RValue) -> Result<usize, Error> {
+        if key.is_immediate() {
+            Err(runtime_error_fmt!(
+                "weak map key must be a gc object, not {:?}",
+                key
+            ))
+        } else {
+            Ok(key.as_ptr() as usize)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self._map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self._map.len() == 0
+    }
+
+    pub fn get(&self, key: &RValue) -> Option<&RValue> {
+        Self::_key(key).ok().and_then(|k| self._map.get(&k))
+    }
+
+    pub fn set(&mut self, key: &RValue, value: RValue) -> Result<Option<RValue>, Error> {
+        let k = Self::_key(key)?;
+        self._map
+            .insert(k, value)
+            .map_err(|_| Error::new_outofmemory())
+    }
+
+    pub fn remove(&mut self, key: &RValue) -> Option<RValue> {
+        Self::_key(key).ok().and_then(|k| self._map.remove(&k))
+    }
+
+    fn _key_is_garbage(key: usize) -> bool {
+        unsafe { (*(key as *const GcHeader)).gc_state() == GcState::Unreached }
+    }
+}
+
+/// 清空目标为垃圾的弱引用。
+pub(crate) fn _clear_weak_refs(weak_refs: &ListBase) {
+    for node in weak_refs.iter() {
+        unsafe {
+            let mut weak = RWeakRef::from_weak_node(node);
+            let weak = weak.as_mut();
+            if weak._target.as_deref().is_some_and(_is_garbage) {
+                weak._target = None;
+            }
+        }
+    }
+}
+
+/// 移除键为垃圾的项，被移除的值照常减少引用计数。
+pub(crate) fn _clear_weak_maps(weak_maps: &ListBase) -> Result<(), Error> {
+    for node in weak_maps.iter() {
+        unsafe {
+            let mut map = RWeakMap::from_weak_node(node);
+            let map = map.as_mut();
+
+            let mut dead = Array::<usize>::new(allocator());
+            for (k, _) in map._map.iter() {
+                if RWeakMap::_key_is_garbage(*k) {
+                    dead.push(*k).map_err(|_| Error::new_outofmemory())?;
+                }
+            }
+            for k in dead.as_slice() {
+                map._map.remove(k);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 对可达的弱映射中键可达的值调用 visitor，用于在标记之后补上这些值。
+pub(crate) fn _visit_weak_map_values(weak_maps: &ListBase, visitor: &mut dyn Visitor) {
+    for node in weak_maps.iter() {
+        unsafe {
+            let map = RWeakMap::from_weak_node(node);
+            if map.cast::<GcHeader>().as_ref().gc_state() == GcState::Unreached {
+                continue;
+            }
+            weak_map__visit(visitor, map.cast());
+        }
+    }
+}
+
+pub(crate) fn _init_type_weak_ref(mut tp: Ref<RType>) -> Result<(), Error> {
+    tp.with_new(weak_ref__new);
+    tp.with_destory(weak_ref__destory);
+
+    tp.with_eq(default_value_eq);
+    tp.with_hash(default_value_hash);
+    tp.with_str(default_value_str);
+
+    tp.add_method_str_light("get", weak_ref__get)?;
+
+    Ok(())
+}
+
+fn weak_ref__new(_tp: &Ref<RType>, args: &[RValue]) -> Result<RValue, Error> {
+    let target = expect_arg1(args)?;
+    Ok(RWeakRef::new(&target)?.cast_value())
+}
+
+fn weak_ref__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut w = value.expect_cast::<RWeakRef>(weak_ref_type())?;
+        w._target = None;
+        runtime().remove_weak_ref(nonnull_of!(w._weak_node));
+        Ok(())
+    }
+}
+
+fn weak_ref__get(this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    let w = unsafe { this.expect_cast::<RWeakRef>(weak_ref_type())? };
+    Ok(ROption::new(w.get())?.cast_value())
+}
+
+pub(crate) fn _init_type_weak_map(mut tp: Ref<RType>) -> Result<(), Error> {
+    tp.with_new(weak_map__new);
+    tp.with_visit(weak_map__visit);
+    tp.with_destory(weak_map__destory);
+
+    tp.with_get_item(weak_map__get_item);
+    tp.with_set_item(weak_map__set_item);
+
+    tp.with_eq(default_value_eq);
+    tp.with_hash(default_value_hash);
+    tp.with_str(default_value_str);
+
+    tp.add_method_str_light("len", weak_map__len)?;
+    tp.add_method_str_light("contains_key", weak_map__contains_key)?;
+    tp.add_method_str_light("remove", weak_map__remove)?;
+
+    Ok(())
+}
+
+fn weak_map__new(_tp: &Ref<RType>, _args: &[RValue]) -> Result<RValue, Error> {
+    Ok(RWeakMap::new()?.cast_value())
+}
+
+// 只访问键没有被判定为垃圾的值，见 RWeakMap。
+fn weak_map__visit(visitor: &mut dyn Visitor, value_ptr: NonNull<GcHeader>) {
+    unsafe {
+        let map = value_ptr.cast::<RWeakMap>();
+        for (k, v) in map.as_ref()._map.iter() {
+            if !RWeakMap::_key_is_garbage(*k) {
+                visitor.visit_value(v);
+            }
+        }
+    }
+}
+
+fn weak_map__destory(value: &RValue) -> Result<(), Error> {
+    unsafe {
+        let mut m = value.expect_cast::<RWeakMap>(weak_map_type())?;
+        runtime().remove_weak_map(nonnull_of!(m._weak_node));
+        addr_of_mut!(m._map).drop_in_place();
+        Ok(())
+    }
+}
+
+fn weak_map__get_item(value: &RValue, index: &RValue) -> Result<RValue, Error> {
+    let map = unsafe { value.expect_cast::<RWeakMap>(weak_map_type())? };
+    if let Some(v) = map.get(index) {
+        Ok(v.clone())
+    } else {
+        Ok(null().cast_value())
+    }
+}
+
+fn weak_map__set_item(value: &RValue, index: &RValue, item: &RValue) -> Result<(), Error> {
+    let mut map = unsafe { value.expect_cast::<RWeakMap>(weak_map_type())? };
+    map.set(index, item.clone())?;
+    Ok(())
+}
+
+fn weak_map__len(this: &RValue, _args: &[RValue]) -> Result<RValue, Error> {
+    let map = unsafe { this.expect_cast::<RWeakMap>(weak_map_type())? };
+    Ok(RInt::new(map.len() as Int)?.cast_value())
+}
+
+fn weak_map__contains_key(this: &RValue, args: &[RValue]) -> Result<RValue, Error> {
+    let map = unsafe { this.expect_cast::<RWeakMap>(weak_map_type())? };
+    let key = expect_arg1(args)?;
+    Ok(RBool::new(map.get(&key).is_some())?.cast_value())
+}
+
+fn weak_map__remove(this: &RValue, args: &[RValue]) -> Result<RValue, Error> {
+    let mut map = unsafe { this.expect_cast::<RWeakMap>(weak_map_type())? };
+    let key = expect_arg1(args)?;
+    Ok(ROption::new(map.remove(&key))?.cast_value())
+}